@@ -0,0 +1,4104 @@
+//! Benchmarks.
+//!
+//! Every benchmark kernel is first run through a calibration pass, which also
+//! warms up the caches, that doubles the number of iterations until the
+//! kernel runs for at least [`CALIBRATION_MSECS`], then through an explicit
+//! untimed warm-up, and is finally measured with the number of iterations
+//! scaled to run for about [`TARGET_MSECS`].
+
+use core::arch::asm;
+use core::array;
+use core::cmp::Reverse;
+use core::fmt::{Display, Formatter, Result as FormatResult, Write};
+use core::hint::spin_loop;
+use core::mem::{size_of, MaybeUninit};
+use core::ops::Range;
+use core::slice;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::cache::{self, LINE_SIZE};
+use crate::mmu::{self, Attributes, Shareability, BLOCK_SIZE, PAGE_SIZE};
+use crate::sync::{Lock, RwLock};
+use crate::timer::Duration;
+use crate::uart::{self, UART};
+use crate::{core_index, debug, dma, emmc, fb, gic, gpio, hyp, impdef, mbox, pmu, ram, rng, smp, sve, timer, user, watchdog, CPU_COUNT, PERRY_RANGE};
+
+/// Size of the benchmark buffer in bytes.
+const BUFFER_SIZE: usize = 0x1000;
+/// Target duration of each measurement in milliseconds.
+const TARGET_MSECS: usize = 1000;
+/// Minimum duration of the calibration pass in milliseconds.
+const CALIBRATION_MSECS: usize = 10;
+/// Maximum number of iterations of the calibration pass, beyond which the
+/// timer is assumed to be broken.
+const MAX_ITERATIONS: usize = 1 << 40;
+/// Number of untimed iterations run right before each measurement.
+const WARMUP_PASSES: usize = 16;
+/// Whether to verify the contents of the buffers written by the benchmarks.
+const VERIFY: bool = true;
+/// Amount added to the fill pattern for every 16 bytes into a buffer.
+const PATTERN_STEP: u64 = 0xD1B54A32D192ED03;
+/// Duration of the throttling detection run in seconds.
+const THROTTLE_SECS: usize = 60;
+/// Number of buffer fills between checks of the timer during the throttling
+/// detection run, which keeps the bookkeeping negligible.
+const THROTTLE_CHUNK: usize = 64;
+/// Number of trials averaged by the live line of the continuous write
+/// benchmark.
+const CONTINUOUS_WINDOW: usize = 8;
+/// Numbers of 32 byte store pairs between barriers swept by the fenced write
+/// benchmark.
+const FENCE_INTERVALS: [usize; 4] = [1, 4, 16, 64];
+/// Largest change in percent between the bandwidths measured with consecutive
+/// doublings of the iterations at which the plateau benchmark converges.
+const PLATEAU_TOLERANCE: u128 = 1;
+/// Maximum number of doublings of the iterations of the plateau benchmark.
+const PLATEAU_DOUBLINGS: usize = 32;
+/// Duration of a single pass of the plateau benchmark in milliseconds beyond
+/// which it gives up converging.
+const PLATEAU_MSECS: usize = 4000;
+/// ARM clock rates in hertz swept by the frequency sweep, clamped to the
+/// maximum rate reported by the firmware, which is also the last rate swept.
+const SWEEP_RATES: [u32; 4] = [600000000, 1000000000, 1500000000, u32::MAX];
+/// Time given to the clock to settle after changing its rate in
+/// milliseconds.
+const SETTLE_MSECS: u64 = 100;
+/// Number of benchmarks run by the suite.
+const SUITE_LEN: usize = FENCE_INTERVALS.len() + 8;
+/// Size of the DRAM stream compared with the caches disabled and enabled in
+/// bytes.
+const CACHES_STREAM_SIZE: usize = 0x400000;
+/// Benchmarks compared with the caches disabled and enabled and at EL2 and
+/// EL1, along with their units, the number of decimal places of their
+/// results, and whether lower results are better.
+const CACHES_KERNELS: [(&str, &str, u32, bool); 4] = [("L1 buffer write", "MB/s", 0, false),
+                                                      ("DRAM stream write", "MB/s", 0, false),
+                                                      ("DRAM stream read", "MB/s", 0, false),
+                                                      ("Pointer chase latency", "ns", 2, true)];
+/// Number of bytes stored per loop iteration by every store form kernel, which
+/// is a multiple of the width of all the forms.
+const STORE_UNROLL: usize = 192;
+/// Number of bytes of the buffer covered by the store form kernels.
+const STORE_SPAN: usize = BUFFER_SIZE / STORE_UNROLL * STORE_UNROLL;
+/// Vector store form kernels along with their names, their store
+/// instructions, the number of bytes stored by each instruction, and whether
+/// the baseline write kernel uses them.
+const STORE_FORMS: [StoreForm; 6] = [("str q", "str q", 16, fill_str, false),
+                                     ("stp q, q", "stp q", 32, fill_stp, true),
+                                     ("st1 {v}", "st1", 16, fill_st1x1, false),
+                                     ("st1 {v, v}", "st1", 32, fill_st1x2, false),
+                                     ("st1 {v, v, v}", "st1", 48, fill_st1x3, false),
+                                     ("st1 {v, v, v, v}", "st1", 64, fill_st1x4, false)];
+/// Store instruction of the SVE write kernel, each of which stores a whole
+/// vector.
+const SVE_STORE: &str = "st1b";
+/// Addressing mode kernels along with their names, their store instructions,
+/// the number of bytes stored by each instruction, and whether the baseline
+/// write kernel uses them.
+const ADDRESSING_FORMS: [StoreForm; 4] = [("str post-index", "str q", 16, fill_str, false),
+                                          ("str register offset", "str q", 16, fill_str_offset, false),
+                                          ("stp post-index", "stp q", 32, fill_stp, true),
+                                          ("stp immediate offset and add", "stp q", 32, fill_stp_add, false)];
+/// Size of the buffer zeroed by the memset strategies, which is far larger than
+/// the caches.
+const ZERO_SIZE: usize = 0x1000000;
+/// Prohibited flag of the data cache zero identification register.
+const DCZID_DZP: usize = 0x10;
+/// Memset strategy kernels along with their names and whether they zero whole
+/// blocks with `dc zva`.
+const ZERO_FORMS: [ZeroForm; 5] = [("strb byte loop", zero_bytes, false),
+                                   ("stp x, x", zero_stp_x, false),
+                                   ("stp q, q", zero_stp_q, false),
+                                   ("st1 {v, v, v, v}", zero_st1x4, false),
+                                   ("dc zva", zero_zva, true)];
+/// Number of bit manipulation instructions run per loop iteration by every
+/// bit manipulation kernel, which is a multiple of [`BITS_CHAINS`].
+const BITS_OPS: usize = 128;
+/// Number of independent chains of instructions run by the bit manipulation
+/// throughput kernels, which is enough to cover the latency of every
+/// instruction on all the supported cores.
+const BITS_CHAINS: usize = 8;
+/// Initial value of the registers operated on by the bit manipulation kernels.
+const BITS_SEED: u64 = 0x0123456789ABCDEF;
+/// Bit manipulation kernels along with the names of their instructions, with
+/// the throughput kernel running independent chains and the latency kernel a
+/// single dependent chain.
+const BITS_FORMS: [BitsForm; 5] = [("clz", clz_throughput, clz_latency),
+                                   ("rbit", rbit_throughput, rbit_latency),
+                                   ("rev", rev_throughput, rev_latency),
+                                   ("rev16", rev16_throughput, rev16_latency),
+                                   ("extr", extr_throughput, extr_latency)];
+/// Number of multiplications run per loop iteration by every multiplication
+/// kernel, which is a multiple of [`BITS_CHAINS`].
+const MUL_OPS: usize = 128;
+/// Initial multiplier of the multiplication kernels, which is odd and lies
+/// between 2^62 and 2^63 like all the multipliers that follow it.
+const MUL_SEED: u64 = 0x6A09E667F3BCC909;
+/// Even value added to the multiplier of the multiplication kernels after
+/// every loop iteration, so that the operands keep changing.
+const MUL_STEP: u64 = 0x3C6EF372FE94F82A;
+/// Number of latency samples taken for every multiplication, each starting
+/// with a different multiplier.
+const MUL_SAMPLES: usize = 256;
+/// Number of loop iterations of every multiplication latency sample.
+const MUL_SAMPLE_ITERATIONS: usize = 16;
+/// Multiplication kernels along with the names of their instructions, with the
+/// throughput kernel running independent multiplications and the latency
+/// kernel a single dependent chain, and whether the chain follows every
+/// multiplication with a single cycle `orr` that keeps the high half from
+/// decaying to zero, whose latency is subtracted.
+const MUL_FORMS: [MulForm; 4] = [("mul", mul_throughput, mul_latency, false),
+                                 ("madd", madd_throughput, madd_latency, false),
+                                 ("umulh", umulh_throughput, umulh_latency, true),
+                                 ("smulh", smulh_throughput, smulh_latency, true)];
+/// Minimum interval between progress reports in milliseconds.
+const PROGRESS_MSECS: usize = 1000;
+/// Size of the pointer chain of the hot latency benchmark, which fits in the
+/// L1 data cache.
+const HOT_CHAIN_SIZE: usize = 0x4000;
+/// Size of the pointer chain of the cold latency benchmark, which is much
+/// larger than the L2 cache.
+const COLD_CHAIN_SIZE: usize = 0x4000000;
+/// Assumed size of a DRAM row, which is the page size of a 16 bit LPDDR4
+/// channel.
+const DRAM_ROW_SIZE: usize = 0x800;
+/// Distance between consecutive pointers of the row miss chain, which is
+/// larger than a DRAM row.
+const DRAM_PAGE_SIZE: usize = 0x1000;
+/// Number of pointers in each DRAM latency chain, which must be a power of
+/// two.
+const DRAM_CHAIN_LOADS: usize = 0x1000;
+/// Number of passes over each DRAM latency chain, each starting with the chain
+/// flushed from the caches.
+const DRAM_PASSES: usize = 64;
+
+/// Size of the buffer written in random order by the random write benchmark,
+/// which is much larger than the L2 cache and a power of two.
+const RANDOM_SIZE: usize = 0x2000000;
+/// Number of cache lines of the buffer written by the random write benchmark.
+const RANDOM_LINES: usize = RANDOM_SIZE / LINE_SIZE;
+/// Number of cache lines written per loop iteration by the random write
+/// kernels, which divides [`RANDOM_LINES`].
+const RANDOM_UNROLL: usize = 8;
+/// Multiplier of the hash that generates the line order of the random write
+/// benchmark from a counter, which is odd so that multiplying by it modulo
+/// [`RANDOM_LINES`] maps the line indices onto themselves.
+const RANDOM_MULTIPLIER: u64 = 6364136223846793005;
+/// Number of bits that the hash of the random write benchmark folds down onto
+/// the low bits of a line index after every multiplication, which is about
+/// half the width of the line indices so that every bit of the result
+/// depends on every bit of the counter.
+const RANDOM_FOLD: u32 = RANDOM_LINES.trailing_zeros().div_ceil(2);
+/// Size of the stream written and read at every placement by the placement
+/// benchmark, which is much larger than the L2 cache.
+const PLACEMENT_SIZE: usize = 0x800000;
+/// Offsets of the stream from the start of a block along with their names
+/// measured by the placement benchmark, which reserves a block more than the
+/// stream for the largest one.
+const PLACEMENTS: [(&str, usize); 5] = [("2MB aligned", 0),
+                                        ("cache line past 2MB", 0x40),
+                                        ("page past 2MB", 0x1000),
+                                        ("16KB past 2MB", 0x4000),
+                                        ("next 2MB", BLOCK_SIZE)];
+/// Size of the region accessed with 4KB pages and 2MB blocks by the granule
+/// benchmark, which is far beyond the reach of the TLBs with 4KB pages but not
+/// with 2MB blocks.
+const GRANULE_SIZE: usize = 0x4000000;
+/// Size of the stream read with and without software prefetching, which is
+/// much larger than the L2 cache.
+const PREFETCH_SIZE: usize = 0x4000000;
+/// Distance ahead of the loads at which the prefetched stream is prefetched in
+/// bytes, which must be a multiple of 8 no larger than 32760.
+const PREFETCH_DISTANCE: usize = 0x200;
+/// Size of the buffer incremented in place by the read-modify-write
+/// benchmark, which is much larger than the L2 cache.
+const RMW_SIZE: usize = 0x2000000;
+/// Number of logarithmic buckets in the latency histograms, each covering
+/// twice the range of cycles of the previous one.
+const HISTOGRAM_BUCKETS: usize = 16;
+/// Number of loads sampled for the latency histograms.
+const HISTOGRAM_SAMPLES: usize = 0x10000;
+/// Interval between the loads sampled for the latency histograms.
+const HISTOGRAM_INTERVAL: usize = 16;
+/// Width of the longest bar in the latency histograms.
+const HISTOGRAM_WIDTH: usize = 50;
+
+/// Register read by the MMIO read latency benchmark, which is the lower word
+/// of the free running system timer.
+const MMIO_READ_REG: *const u32 = (0x2003004 + PERRY_RANGE.start) as _;
+/// Register written by the MMIO write benchmark, which is the scratch register
+/// of the mini UART.
+const MMIO_WRITE_REG: *mut u32 = (0x221505C + PERRY_RANGE.start) as _;
+/// Number of register accesses per iteration of the MMIO benchmarks.
+const MMIO_BURST: usize = 64;
+/// GPIO pin toggled by the GPIO benchmark, which is physical pin 40 on the
+/// header.
+///
+/// **Change this or leave the pin disconnected if anything is attached to it,
+/// since the benchmark drives it as an output.**
+const TOGGLE_PIN: usize = 21;
+/// Number of round trips timed by the mailbox latency benchmark.
+const MAILBOX_SAMPLES: usize = 4096;
+/// Software generated interrupt sent by the inter-core interrupt latency
+/// benchmark.
+pub const SGI: u32 = 0;
+/// Core that receives the interrupts sent by the inter-core interrupt latency
+/// benchmark.
+const SGI_TARGET: usize = 1;
+/// Number of interrupts timed by the inter-core interrupt latency benchmark.
+const SGI_SAMPLES: usize = 4096;
+/// Time given to the target core to handle each interrupt in milliseconds.
+const SGI_TIMEOUT_MSECS: usize = 10;
+/// Size of the buffers copied by the DMA benchmark.
+const DMA_SIZE: usize = 0x4000000;
+/// Size of the region written by the CPU in the interference benchmark, which
+/// is much larger than the L2 cache.
+const STREAM_SIZE: usize = 0x2000000;
+/// Address of the first block read by the SD card benchmark.
+const EMMC_LBA: u32 = 0;
+/// Number of bytes read by the SD card benchmark when run from the menu.
+const EMMC_SIZE: usize = 0x1000000;
+/// Duration of the RNG benchmark in milliseconds.
+const RNG_MSECS: usize = 1000;
+/// Duration of the contended lock benchmark in milliseconds.
+const CONTENTION_MSECS: usize = 1000;
+/// Mask of the cores that read the shared region in the duplex benchmark.
+const DUPLEX_READERS: u8 = 0b0011;
+/// Mask of the cores that write the separate region in the duplex benchmark.
+const DUPLEX_WRITERS: u8 = 0b1100;
+/// Size of each of the regions of the duplex benchmark, which is much larger
+/// than the L2 cache.
+const DUPLEX_SIZE: usize = 0x2000000;
+/// Duration of the duplex benchmark in milliseconds.
+const DUPLEX_MSECS: usize = 2000;
+/// Size of the region written by core #0 in the idle cores benchmark, which is
+/// much larger than the L2 cache.
+const IDLE_SIZE: usize = 0x2000000;
+
+/// Lock that all the cores fight over in the contended lock benchmark.
+static CONTENDED: Lock<()> = Lock::new(());
+/// Flag that all the cores fight over in the contended lock benchmark with
+/// plain busy spinning, as a baseline for the bus traffic of the lock.
+static SPINNING: AtomicBool = AtomicBool::new(false);
+/// Number of acquisitions of each logical CPU in the contended lock benchmark.
+static ACQUISITIONS: [AtomicUsize; CPU_COUNT] = [const { AtomicUsize::new(0) }; CPU_COUNT];
+/// Summaries of the results of the benchmark suite of every logical CPU.
+static SUMMARIES: RwLock<[Option<Summary>; CPU_COUNT]> = RwLock::new([None; CPU_COUNT]);
+/// Timer count when the target core handled the last inter-core interrupt, or
+/// zero if it hasn't handled it yet.
+static SGI_STAMP: AtomicUsize = AtomicUsize::new(0);
+/// Whether the idle cores wait for interrupts instead of spinning in the idle
+/// cores benchmark.
+static IDLE_WFI: AtomicBool = AtomicBool::new(false);
+/// Whether core #0 has finished measuring in the idle cores benchmark.
+static IDLE_DONE: AtomicBool = AtomicBool::new(false);
+/// Latency histograms of every logical CPU.
+static HISTOGRAMS: [[AtomicUsize; HISTOGRAM_BUCKETS]; CPU_COUNT] = [const { [const { AtomicUsize::new(0) }; HISTOGRAM_BUCKETS] }; CPU_COUNT];
+
+/// Store form kernel along with its name, its store instruction, the number of
+/// bytes stored by each instruction, and whether the baseline write kernel uses
+/// it.
+type StoreForm = (&'static str, &'static str, usize, fn(*mut u8, u64, usize), bool);
+/// Memset strategy kernel along with its name and whether it zeroes whole
+/// blocks with `dc zva`.
+type ZeroForm = (&'static str, fn(Range<usize>, usize), bool);
+/// Bit manipulation kernels along with the name of their instruction, with the
+/// throughput kernel first and the latency kernel second.
+type BitsForm = (&'static str, fn(usize), fn(usize));
+/// Multiplication kernels along with the name of their instruction, with the
+/// throughput kernel first and the latency kernel second, and whether the
+/// latency of the `orr` that follows every multiplication of the chain is
+/// subtracted.
+type MulForm = (&'static str, fn(usize, u64), fn(usize, u64), bool);
+
+/// Benchmark buffer, aligned to the assumed [`LINE_SIZE`].
+#[repr(align(64), C)]
+struct Buffer([u8; BUFFER_SIZE]);
+
+/// Benchmark buffer with room to start the stores anywhere within its first
+/// cache line, aligned to the assumed [`LINE_SIZE`].
+#[repr(align(64), C)]
+struct MisalignedBuffer([u8; BUFFER_SIZE + LINE_SIZE]);
+
+/// State of the caches when the latency benchmark starts chasing pointers.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CacheState
+{
+    /// The pointer chain is established in the cache by the warm-up.
+    Hot,
+    /// The pointer chain is flushed from the cache by the warm-up.
+    Cold,
+}
+
+/// Reason why a benchmark kernel could not be measured, formatted for display.
+#[derive(Clone, Copy, Debug)]
+enum Unmeasurable
+{
+    /// The timer runs at the given frequency in hertz, which is too slow.
+    SlowTimer(usize),
+    /// The kernel runs too fast for the timer even at the maximum number of
+    /// iterations.
+    ShortInterval,
+}
+
+/// Summary of the results of the benchmark suite.
+#[derive(Clone, Copy, Debug)]
+struct Summary
+{
+    /// Baseline write bandwidth in megabytes per second.
+    write: Option<u128>,
+    /// Unrolled write bandwidth in megabytes per second.
+    wide: Option<u128>,
+    /// Hot load latency in hundredths of a nanosecond.
+    hot: Option<u128>,
+    /// Cold load latency in hundredths of a nanosecond.
+    cold: Option<u128>,
+}
+
+/// Results of the benchmark suite at one ARM clock rate.
+#[derive(Clone, Copy, Debug)]
+struct SweepRow
+{
+    /// Requested clock rate in hertz.
+    requested: u32,
+    /// Clock rate read back from the firmware in hertz.
+    applied: Option<u32>,
+    /// SoC temperature in thousandths of a degree Celsius.
+    temp: Option<u32>,
+    /// Results of the benchmark suite.
+    summary: Summary,
+}
+
+/// Table cell that may be missing a value, formatted for display.
+struct Cell<T: Display>(Option<T>);
+
+/// Fixed point number with its value and number of decimal places, formatted
+/// for display.
+struct Fixed(u128, u32);
+
+/// Ratio of a result to a baseline result with two decimal places, formatted
+/// for display as a multiplier.
+struct Ratio(u128, u128);
+
+/// Relative change of a result from a baseline result with two decimal
+/// places, formatted for display as a signed percentage.
+struct Change(u128, u128);
+
+/// Range of cycles covered by a latency histogram bucket, formatted for
+/// display.
+struct BucketRange(usize);
+
+/// Number of bytes, formatted for display with two decimal places in the
+/// largest binary unit that it reaches.
+struct Bytes(u128);
+
+/// Memory access pattern of a benchmark kernel, formatted for display along
+/// with the memory attributes that its range is mapped with.
+struct Access
+{
+    /// Instruction performing the accesses.
+    instruction: &'static str,
+    /// Number of bytes accessed by each instruction.
+    width: usize,
+    /// Order of the accesses.
+    order: &'static str,
+    /// Accessed memory range.
+    range: Range<usize>,
+}
+
+/// Progress through a sequence of benchmarks, reported at most once every
+/// [`PROGRESS_MSECS`] so that long runs show signs of life.
+#[derive(Debug)]
+struct Progress
+{
+    /// Description of the sequence.
+    desc: &'static str,
+    /// Number of benchmarks started so far.
+    started: usize,
+    /// Total number of benchmarks.
+    total: usize,
+    /// Timer count when the sequence started.
+    start: usize,
+    /// Timer count when progress was last reported.
+    last: Option<usize>,
+}
+
+/// Arguments and results of the core benchmarks run at EL2.
+#[derive(Debug)]
+struct LevelsArgs
+{
+    /// Benchmark buffer.
+    buf: *mut u8,
+    /// DRAM stream.
+    stream: Range<usize>,
+    /// Linked pointer chain.
+    chain: Range<usize>,
+    /// Results in the order of [`CACHES_KERNELS`], once the kernels have run.
+    results: Option<[Result<u128, Unmeasurable>; CACHES_KERNELS.len()]>,
+}
+
+/// Arguments and results of the kernels run at EL0, which must be placed in
+/// memory mapped for EL0.
+#[derive(Debug)]
+struct UserArgs
+{
+    /// DRAM stream.
+    stream: Range<usize>,
+    /// Pattern stored to the stream.
+    pattern: u64,
+    /// Number of passes over the stream.
+    passes: usize,
+    /// Number of supervisor calls.
+    calls: usize,
+    /// Duration of the passes over the stream in generic timer ticks.
+    stream_ticks: usize,
+    /// Duration of the supervisor calls in generic timer ticks.
+    call_ticks: usize,
+    /// Duration of the supervisor calls in CPU cycles.
+    call_cycles: usize,
+}
+
+/// Result of a measurement.
+#[derive(Clone, Copy, Debug)]
+pub struct Measurement
+{
+    /// Number of iterations of the kernel.
+    pub iterations: usize,
+    /// Duration in timer ticks.
+    pub ticks: usize,
+    /// Duration in CPU cycles, counted over the same run as the timer ticks.
+    pub cycles: usize,
+}
+
+/// Runs all the benchmarks on the calling core.
+///
+/// Must be run as a job on all the selected cores simultaneously.
+pub fn run()
+{
+    let core = core_index();
+    let summary = suite(&mut Progress::new("suite", SUITE_LEN));
+    smp::record(summary.write.unwrap_or(0) as u64);
+    if core == smp::leader() {
+        bench_shareability();
+    }
+    SUMMARIES.write()[core] = Some(summary);
+    // The contended lock benchmark starts with a barrier, so all the selected
+    // cores have recorded their summaries by the time it returns.
+    bench_lock_contended();
+    if core == smp::leader() {
+        aggregate();
+    }
+}
+
+/// Reports the aggregated results of the benchmark suite across all the
+/// selected cores.
+fn aggregate()
+{
+    let mask = smp::mask();
+    let summaries = SUMMARIES.read();
+    let summaries = summaries.iter()
+                             .enumerate()
+                             .filter(|(core, _)| mask & 1 << core != 0)
+                             .filter_map(|(_, summary)| summary.as_ref());
+    let total = |field: fn(&Summary) -> Option<u128>| summaries.clone().filter_map(field).sum::<u128>();
+    let mean = |field: fn(&Summary) -> Option<u128>| {
+        let count = summaries.clone().filter_map(field).count().max(1);
+        total(field) / count as u128
+    };
+    debug!("Cores 0b{mask:04b}: Write: {}MB/s, Wide: {}MB/s, Hot latency: {}ns, Cold latency: {}ns",
+           total(|summary| summary.write),
+           total(|summary| summary.wide),
+           Fixed(mean(|summary| summary.hot), 2),
+           Fixed(mean(|summary| summary.cold), 2));
+}
+
+/// Runs the benchmark suite at several ARM clock rates on the calling core and
+/// prints a table of the results, which tells the benchmarks bound by the core
+/// apart from those bound by the DRAM.
+pub fn sweep()
+{
+    let max = match mbox::max_clock_rate(mbox::ARM_CLOCK) {
+        Ok(rate) => rate,
+        Err(err) => {
+            debug!("Failed to read the maximum ARM clock rate: {err}");
+            return;
+        }
+    };
+    let mut progress = Progress::new("sweep", SWEEP_RATES.len() * SUITE_LEN);
+    let rows = SWEEP_RATES.map(|rate| rate.min(max)).map(|requested| {
+        if let Err(err) = mbox::set_clock_rate(mbox::ARM_CLOCK, requested) {
+            debug!("Firmware rejected setting the ARM clock to {}MHz: {err}",
+                   requested / 1000000);
+            return None;
+        }
+        timer::delay_ms(SETTLE_MSECS);
+        let applied = mbox::clock_rate(mbox::ARM_CLOCK).ok();
+        let temp = mbox::temperature().ok();
+        Some(SweepRow { requested,
+                        applied,
+                        temp,
+                        summary: suite(&mut progress) })
+    });
+    let mut uart = UART.lock();
+    // Every cell fits in a tab stop, which keeps the columns aligned.
+    writeln!(uart, "Request\tApplied\tTemp\tWrite\tWide\tHot\tCold").unwrap();
+    writeln!(uart, "MHz\tMHz\tC\tMB/s\tMB/s\tns\tns").unwrap();
+    for row in rows.iter().flatten() {
+        let SweepRow { requested, applied, temp, summary } = row;
+        writeln!(uart,
+                 "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                 requested / 1000000,
+                 Cell(applied.map(|rate| rate / 1000000)),
+                 Cell(temp.map(|temp| Fixed(temp as u128 / 100, 1))),
+                 Cell(summary.write),
+                 Cell(summary.wide),
+                 Cell(summary.hot.map(|centis| Fixed(centis, 2))),
+                 Cell(summary.cold.map(|centis| Fixed(centis, 2))))
+        .unwrap();
+    }
+    drop(uart);
+    pin_clock();
+}
+
+/// Runs the benchmark suite on the calling core.
+///
+/// * `progress`: Progress to advance before every benchmark, in between the
+///   timed measurements.
+///
+/// Returns the summary of the results.
+fn suite(progress: &mut Progress) -> Summary
+{
+    progress.advance();
+    let write = bench_write();
+    progress.advance();
+    let wide = bench_write_wide();
+    compare("wide", write, wide);
+    progress.advance();
+    if let Some(sve) = bench_write_sve() {
+        compare("SVE", write, Some(sve));
+    }
+    for stores in FENCE_INTERVALS {
+        progress.advance();
+        compare(format_args!("fenced every {stores} store pairs"),
+                write,
+                bench_write_fenced(stores));
+    }
+    progress.advance();
+    let hot = bench_latency(CacheState::Hot);
+    progress.advance();
+    let cold = bench_latency(CacheState::Cold);
+    progress.advance();
+    bench_lock();
+    progress.advance();
+    bench_mmio_read();
+    progress.advance();
+    bench_mmio_write();
+    Summary { write,
+              wide,
+              hot,
+              cold }
+}
+
+/// Raises the ARM clock to its maximum rate and reports the requested and
+/// applied rates, since the firmware boots the cores at a conservative rate.
+pub fn pin_clock()
+{
+    let current = match mbox::clock_rate(mbox::ARM_CLOCK) {
+        Ok(rate) => rate,
+        Err(err) => {
+            debug!("Failed to read the ARM clock rate: {err}");
+            return;
+        }
+    };
+    let max = match mbox::max_clock_rate(mbox::ARM_CLOCK) {
+        Ok(rate) => rate,
+        Err(err) => {
+            debug!("Failed to read the maximum ARM clock rate, continuing at {}MHz: {err}",
+                   current / 1000000);
+            return;
+        }
+    };
+    match mbox::set_clock_rate(mbox::ARM_CLOCK, max) {
+        Ok(applied) => debug!("ARM clock: Requested: {}MHz, Applied: {}MHz (Boot: {}MHz)",
+                              max / 1000000,
+                              applied / 1000000,
+                              current / 1000000),
+        Err(err) => debug!("Firmware rejected setting the ARM clock to {}MHz, continuing at {}MHz: {err}",
+                           max / 1000000,
+                           current / 1000000),
+    }
+}
+
+/// Reports the SoC temperature so that throttling can be told apart from slow
+/// results.
+///
+/// * `when`: When the temperature is being reported relative to the
+///   benchmarks.
+pub fn temperature(when: &str)
+{
+    let temp = match mbox::temperature() {
+        Ok(temp) => temp,
+        Err(err) => {
+            debug!("Failed to read the SoC temperature {when} the benchmarks: {err}");
+            return;
+        }
+    };
+    match mbox::max_temperature() {
+        Ok(max) => debug!("SoC temperature {when} the benchmarks: {}.{}C (Maximum: {}.{}C)",
+                          temp / 1000,
+                          temp % 1000 / 100,
+                          max / 1000,
+                          max % 1000 / 100),
+        Err(err) => debug!("SoC temperature {when} the benchmarks: {}.{}C (Failed to read the maximum: {err})",
+                           temp / 1000,
+                           temp % 1000 / 100),
+    }
+}
+
+/// Reports whether the MMU and the caches are enabled and whether the FP/SIMD
+/// instructions are trapped on the calling core, warning when the data cache
+/// is disabled since that invalidates any comparison with cached results.
+pub fn control_state()
+{
+    let core = core_index();
+    let sctlr = mmu::sctlr();
+    debug!("Core #{core} {sctlr}");
+    debug!("Core #{core} {}", sve::access());
+    if !sctlr.data_cache() {
+        debug!("WARNING: D-cache disabled, bandwidth numbers reflect uncached accesses");
+    }
+}
+
+/// Measures the write bandwidth to a buffer that is kept in the L1 cache with
+/// one 32 byte store pair per loop iteration.
+///
+/// Returns the bandwidth in megabytes per second, or `None` if it could not be
+/// measured.
+fn bench_write() -> Option<u128>
+{
+    bench_fill("baseline", baseline_store(), fill)
+}
+
+/// Measures the write bandwidth to a buffer that is kept in the L1 cache with
+/// the stores starting at a misalignment, relative to the same stores starting
+/// at the beginning of a cache line.
+///
+/// The alignment check is disabled for the duration of the benchmark, and the
+/// misaligned stores cover the same number of bytes as the aligned ones.
+///
+/// * `offset`: Misalignment in bytes, which must be a multiple of 4 less than
+///   [`LINE_SIZE`].
+pub fn bench_unaligned(offset: usize)
+{
+    let core = core_index();
+    if offset % 4 != 0 || offset >= LINE_SIZE {
+        debug!("Misalignment must be a multiple of 4 bytes less than {LINE_SIZE} bytes");
+        return;
+    }
+    let mut buf = MaybeUninit::<MisalignedBuffer>::uninit();
+    let base = buf.as_mut_ptr() as usize;
+    mmu::check_alignment(false);
+    let rates = [0, offset].map(|offset| {
+        let range = base + offset .. base + offset + BUFFER_SIZE;
+        let pattern = pattern(range.start);
+        let measurement = measure(|iterations| fill_stream(range.clone(), pattern, iterations),
+                                  || fill_stream(range.clone(), pattern, WARMUP_PASSES))?;
+        if VERIFY {
+            verify(range.start as *const u64, BUFFER_SIZE, pattern);
+        }
+        let bytes = measurement.iterations as u128 * BUFFER_SIZE as u128;
+        Some((bytes * timer::frequency() as u128 / measurement.ticks as u128) >> 20)
+    });
+    mmu::check_alignment(true);
+    if let [Some(aligned), Some(misaligned)] = rates {
+        debug!("Core #{core} stores misaligned by {offset} bytes: {misaligned}MB/s against {aligned}MB/s aligned ({}% penalty)",
+               aligned.saturating_sub(misaligned) * 100 / aligned.max(1));
+    }
+}
+
+/// Measures the write bandwidth to a buffer that is kept in the L1 cache with
+/// a loop unrolled to store 64 bytes from four vector registers per iteration,
+/// which tells whether the baseline is bound by the loop overhead.
+///
+/// Returns the bandwidth in megabytes per second, or `None` if it could not be
+/// measured.
+fn bench_write_wide() -> Option<u128>
+{
+    bench_fill("wide", baseline_store(), fill_wide)
+}
+
+/// Measures the write bandwidth to a buffer that is kept in the L1 cache with
+/// SVE stores of whole vectors, if the calling core implements SVE.
+///
+/// Returns the bandwidth in megabytes per second, or `None` if it could not be
+/// measured.
+fn bench_write_sve() -> Option<u128>
+{
+    let core = core_index();
+    if !sve::implemented() {
+        debug!("Core #{core} SVE: not implemented");
+        return None;
+    }
+    let len = sve::enable();
+    debug!("Core #{core} SVE: {}-bit vectors", len * 8);
+    bench_fill(format_args!("SVE {}-bit", len * 8), (SVE_STORE, len), fill_sve)
+}
+
+/// Measures the write throughput of each vector store form in
+/// [`STORE_FORMS`] to a buffer that is kept in the L1 cache, in bytes per CPU
+/// cycle.
+pub fn bench_store_forms()
+{
+    bench_forms(&STORE_FORMS)
+}
+
+/// Measures the write throughput of each addressing mode in
+/// [`ADDRESSING_FORMS`] to a buffer that is kept in the L1 cache, in bytes per
+/// CPU cycle, which tells whether the address generation or the stores are
+/// the bottleneck.
+pub fn bench_addressing()
+{
+    bench_forms(&ADDRESSING_FORMS)
+}
+
+/// Measures the bandwidth of zeroing a buffer far larger than the caches with
+/// each memset strategy in [`ZERO_FORMS`], printing a table of the results
+/// sorted from the fastest to the slowest strategy.
+///
+/// Every strategy is warmed up the same way, ends with the same barrier before
+/// the end timestamp is taken, and has the buffer checked for zeroes after it,
+/// which is filled with a pattern beforehand so that a strategy that stores
+/// nothing cannot pass.
+pub fn bench_memset()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < ZERO_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the memset benchmark");
+        return;
+    }
+    let range = share.start .. share.start + ZERO_SIZE;
+    let zva = zva_block();
+    let mut results = ZERO_FORMS.map(|(name, kernel, needs_zva)| {
+                                    if needs_zva && zva.is_none() {
+                                        debug!("Core #{core} {name} is prohibited");
+                                        return (name, None);
+                                    }
+                                    fill_stream(range.clone(), pattern(range.start), 1);
+                                    let measurement = measure(|iterations| kernel(range.clone(), iterations),
+                                                              || kernel(range.clone(), 1));
+                                    if VERIFY {
+                                        verify_zeroed(range.clone());
+                                    }
+                                    // Hundredths of gigabytes per second.
+                                    let rate = measurement.map(|Measurement { iterations, ticks, .. }| {
+                                                                   (iterations as u128 * ZERO_SIZE as u128 * timer::frequency() as u128 * 100
+                                                                    / ticks as u128)
+                                                                   >> 30
+                                                               });
+                                    (name, rate)
+                                });
+    results.sort_unstable_by_key(|&(_, rate)| Reverse(rate));
+    let mut uart = UART.lock();
+    writeln!(uart, "Core #{core} zeroing {}MB:", ZERO_SIZE >> 20).unwrap();
+    writeln!(uart, "GB/s\tStrategy").unwrap();
+    for (name, rate) in results {
+        writeln!(uart, "{}\t{name}", Cell(rate.map(|rate| Fixed(rate, 2)))).unwrap();
+    }
+}
+
+/// Measures the throughput of the bit manipulation instructions in
+/// instructions per CPU cycle and their latency in CPU cycles on this core.
+pub fn bench_bits()
+{
+    let core = core_index();
+    let results = BITS_FORMS.map(|(name, throughput, latency)| (name, measure_cycles(throughput), measure_cycles(latency)));
+    let ops = |&(iterations, cycles): &(usize, usize)| ((iterations * BITS_OPS) as u128, cycles.max(1) as u128);
+    let mut uart = UART.lock();
+    writeln!(uart, "Core #{core} bit manipulation instructions:").unwrap();
+    writeln!(uart, "Ops/cycle\tLatency\tInstruction").unwrap();
+    for (name, throughput, latency) in results {
+        writeln!(uart,
+                 "{}\t{}\t{name}",
+                 Cell(throughput.as_ref().map(ops).map(|(ops, cycles)| Fixed(ops * 100 / cycles, 2))),
+                 Cell(latency.as_ref().map(ops).map(|(ops, cycles)| Fixed(cycles * 100 / ops, 2))))
+        .unwrap();
+    }
+}
+
+/// Measures the reciprocal throughput and the latency of the multiplication
+/// instructions in CPU cycles on this core, reporting both the median and the
+/// worst latency sampled across different operands, which also includes any
+/// interference from interrupts.
+pub fn bench_mul()
+{
+    let core = core_index();
+    let results = MUL_FORMS.map(|(name, throughput, latency, fixup)| {
+                               let throughput = measure_cycles(|iterations| throughput(iterations, MUL_SEED));
+                               uart::settle();
+                               let mut samples = [0; MUL_SAMPLES];
+                               for (idx, sample) in samples.iter_mut().enumerate() {
+                                   let seed = MUL_SEED.wrapping_add(MUL_STEP.wrapping_mul(idx as u64 + 1));
+                                   let start = pmu::cycles();
+                                   latency(MUL_SAMPLE_ITERATIONS, seed);
+                                   let cycles = (pmu::cycles() - start) as u128 * 100 / (MUL_SAMPLE_ITERATIONS * MUL_OPS) as u128;
+                                   *sample = cycles.saturating_sub(if fixup { 100 } else { 0 });
+                               }
+                               samples.sort_unstable();
+                               (name, throughput, samples[MUL_SAMPLES / 2], samples[MUL_SAMPLES - 1])
+                           });
+    let mut uart = UART.lock();
+    writeln!(uart, "Core #{core} multiplication instructions in cycles:").unwrap();
+    writeln!(uart, "Throughput\tLatency\tWorst\tInstruction").unwrap();
+    for (name, throughput, typical, worst) in results {
+        writeln!(uart,
+                 "{}\t{}\t{}\t{name}",
+                 Cell(throughput.map(|(iterations, cycles)| Fixed(cycles as u128 * 100 / (iterations * MUL_OPS) as u128, 2))),
+                 Fixed(typical, 2),
+                 Fixed(worst, 2))
+        .unwrap();
+    }
+}
+
+/// Measures a compute kernel in CPU cycles.
+///
+/// * `kernel`: Kernel to measure, which takes the number of iterations to run.
+///
+/// Returns the number of iterations, which runs for about the target duration,
+/// and the number of cycles that it took, or `None` if the kernel could not be
+/// measured.
+fn measure_cycles(kernel: impl Fn(usize)) -> Option<(usize, usize)>
+{
+    let Measurement { iterations, .. } = measure(&kernel, || kernel(WARMUP_PASSES))?;
+    let start = pmu::cycles();
+    kernel(iterations);
+    Some((iterations, pmu::cycles() - start))
+}
+
+/// Finds the store form that the baseline write kernel and its variants use.
+///
+/// Returns the store instruction of the form and the number of bytes stored by
+/// each instruction.
+fn baseline_store() -> (&'static str, usize)
+{
+    let (_, instruction, width, ..) = STORE_FORMS.iter().find(|(.., baseline)| *baseline).unwrap();
+    (instruction, *width)
+}
+
+/// Measures the write throughput of store form kernels to a buffer that is
+/// kept in the L1 cache, in bytes per CPU cycle, and reports each of them
+/// relative to the form used by the baseline write kernel.
+///
+/// * `forms`: Kernels along with their names, their store instructions, the
+///   number of bytes stored by each instruction, and whether the baseline
+///   write kernel uses them.
+fn bench_forms<const N: usize>(forms: &[StoreForm; N])
+{
+    let core = core_index();
+    let mut buf = MaybeUninit::<Buffer>::uninit();
+    let ptr = buf.as_mut_ptr().cast::<u8>();
+    let pattern = pattern(ptr as usize);
+    let results = forms.map(|(name, instruction, width, kernel, _)| {
+                               debug!("Core #{core} {name} form access: {}",
+                                      Access { instruction,
+                                               width,
+                                               order: "sequential",
+                                               range: ptr as usize .. ptr as usize + STORE_SPAN });
+                               let Measurement { iterations, .. } = measure(|iterations| kernel(ptr, pattern, iterations),
+                                                                            || kernel(ptr, pattern, WARMUP_PASSES))?;
+                               // The measured number of iterations runs for
+                               // about the same time with the cycle counter
+                               // instead of the timer.
+                               let start = pmu::cycles();
+                               kernel(ptr, pattern, iterations);
+                               let cycles = (pmu::cycles() - start).max(1);
+                               if VERIFY {
+                                   verify(ptr.cast(), STORE_SPAN, pattern);
+                               }
+                               Some((iterations * STORE_SPAN) as u128 * 100 / cycles as u128)
+                           });
+    let reference = forms.iter()
+                         .zip(results)
+                         .find_map(|(&(.., baseline), centis)| if baseline { centis } else { None });
+    for (&(name, .., baseline), centis) in forms.iter().zip(results) {
+        let Some(centis) = centis else {
+            continue;
+        };
+        if baseline {
+            debug!("Core #{core} {name}: {} bytes per cycle (used by the baseline kernel)",
+                   Fixed(centis, 2));
+        } else {
+            debug!("Core #{core} {name}: {} bytes per cycle ({} the baseline form)",
+                   Fixed(centis, 2),
+                   Cell(reference.map(|reference| Ratio(centis, reference))));
+        }
+    }
+}
+
+/// Measures the write bandwidth to a buffer that is kept in the L1 cache with
+/// a barrier that waits for the stores to drain after every fixed number of
+/// store pairs, which quantifies the cost of ordering memory accesses.
+///
+/// * `stores`: Number of 32 byte store pairs between barriers, which must not
+///   be zero.
+///
+/// Returns the bandwidth in megabytes per second, or `None` if it could not be
+/// measured.
+fn bench_write_fenced(stores: usize) -> Option<u128>
+{
+    bench_fill(format_args!("fenced every {stores} store pairs"),
+               baseline_store(),
+               |buf, pattern, iterations| fill_fenced(buf, pattern, iterations, stores))
+}
+
+/// Reports the bandwidth of a kernel relative to the baseline.
+///
+/// * `name`: Name of the kernel.
+/// * `baseline`: Baseline bandwidth, if measured.
+/// * `rate`: Bandwidth of the kernel, if measured.
+fn compare(name: impl Display, baseline: Option<u128>, rate: Option<u128>)
+{
+    if let (Some(baseline), Some(rate)) = (baseline, rate) {
+        debug!("Core #{} {name} kernel is {} the baseline kernel",
+               core_index(),
+               Ratio(rate, baseline));
+    }
+}
+
+/// Measures the bandwidth of copying a working set of any size to the rest of
+/// the calling core's share of the free RAM, recording it in megabytes copied
+/// per second when run as a job.
+///
+/// * `size`: Size of the working set in bytes.
+pub fn bench_copy_sized(size: usize)
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if size == 0 || size % 32 != 0 {
+        debug!("Working set size must be a non-zero multiple of 32 bytes");
+        return;
+    }
+    if share.len() / 2 < size {
+        debug!("Core #{core} only has {}KB of free RAM for the working set and its copy",
+               share.len() >> 10);
+        return;
+    }
+    let src = share.start .. share.start + size;
+    let dst = src.end .. src.end + size;
+    debug!("Core #{core} copy access: {}",
+           Access { instruction: "ldp q and stp q",
+                    width: 32,
+                    order: "sequential",
+                    range: src.start .. dst.end });
+    let kernel = |iterations| {
+        for _ in 0 .. iterations {
+            copy_stream(dst.clone(), src.start);
+        }
+    };
+    let Some(Measurement { iterations, ticks, .. }) = measure(kernel, || kernel(WARMUP_PASSES)) else {
+        return;
+    };
+    let bytes = size as u128 * iterations as u128;
+    let rate = (bytes * timer::frequency() as u128 / ticks as u128) >> 20;
+    debug!("Core #{core} copied a {} working set {iterations} times in {} ({rate}MB/s)",
+           Bytes(size as u128),
+           Duration(ticks));
+    smp::record(rate as u64);
+}
+
+/// Measures the write bandwidth of filling a working set of any size in the
+/// calling core's share of the free RAM a fixed number of times, recording it
+/// in megabytes per second when run as a job.
+///
+/// * `size`: Size of the working set in bytes.
+/// * `iterations`: Number of times to fill the working set.
+pub fn bench_write_sized(size: usize, iterations: usize)
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if size == 0 || size % 32 != 0 || iterations == 0 {
+        debug!("Working set size must be a non-zero multiple of 32 bytes and iterations must not be zero");
+        return;
+    }
+    if share.len() < size {
+        debug!("Core #{core} only has {}KB of free RAM for the working set",
+               share.len() >> 10);
+        return;
+    }
+    write_sized(share.start .. share.start + size, iterations);
+}
+
+/// Measures the write bandwidth of filling a working set of any size at a
+/// physical address a fixed number of times on the calling core, so that the
+/// working set can be placed in specific DRAM banks.
+///
+/// * `addr`: Physical address of the working set, which must be aligned to 32
+///   bytes.
+/// * `size`: Size of the working set in bytes.
+/// * `iterations`: Number of times to fill the working set.
+pub fn bench_write_at(addr: usize, size: usize, iterations: usize)
+{
+    if size == 0 || size % 32 != 0 || iterations == 0 || addr % 32 != 0 {
+        debug!("Working set address and size must be multiples of 32 bytes, and size and iterations must not be zero");
+        return;
+    }
+    // The free RAM starts past the kernel and its stacks and ends before the
+    // peripherals.
+    let free = ram::free();
+    let Some(end) = addr.checked_add(size).filter(|&end| addr >= free.start && end <= free.end) else {
+        debug!("Working set at 0x{addr:X} does not fit in the free RAM at 0x{:X} .. 0x{:X}",
+               free.start,
+               free.end);
+        return;
+    };
+    write_sized(addr .. end, iterations);
+}
+
+/// Measures the write bandwidth of filling a working set a fixed number of
+/// times on the calling core, recording it in megabytes per second when run as
+/// a job.
+///
+/// Any number of iterations can be requested, so they are timed in chunks of
+/// about [`TARGET_MSECS`] each, sized by a timed calibration pass, with the
+/// watchdog petted in between instead of in a single run that could outlast
+/// it.
+///
+/// * `range`: Working set, which must be free RAM that no other core is using,
+///   must not be empty, and must be aligned to and a multiple of 32 bytes.
+/// * `iterations`: Number of times to fill the working set, which must not be
+///   zero.
+fn write_sized(range: Range<usize>, iterations: usize)
+{
+    let core = core_index();
+    let size = range.len();
+    let pattern = pattern(range.start);
+    debug!("Core #{core} write access: {}",
+           Access { instruction: "stp q",
+                    width: 32,
+                    order: "sequential",
+                    range: range.clone() });
+    fill_stream(range.clone(), pattern, WARMUP_PASSES.min(iterations));
+    let mut kernel = |iterations| fill_stream(range.clone(), pattern, iterations);
+    watchdog::pet();
+    let (pass, _) = time(&mut kernel, 1);
+    let chunk = (timer::frequency() * TARGET_MSECS / 1000 / pass.max(1)).max(1);
+    let (mut ticks, mut cycles) = (0, 0);
+    let mut left = iterations;
+    while left != 0 {
+        let count = left.min(chunk);
+        watchdog::pet();
+        let (chunk_ticks, chunk_cycles) = time(&mut kernel, count);
+        ticks += chunk_ticks;
+        cycles += chunk_cycles;
+        left -= count;
+    }
+    let ticks = ticks.max(1);
+    if VERIFY {
+        verify(range.start as *const u64, size, pattern);
+    }
+    let bytes = size as u128 * iterations as u128;
+    let rate = (bytes * timer::frequency() as u128 / ticks as u128) >> 20;
+    debug!("Core #{core} wrote {} as a {} working set {iterations} times in {} ({rate}MB/s, {} bytes/cycle)",
+           Bytes(bytes),
+           Bytes(size as u128),
+           Duration(ticks),
+           Fixed(bytes * 100 / cycles.max(1) as u128, 2));
+    smp::record(rate as u64);
+}
+
+/// Measures the write bandwidth of filling a working set of any size in the
+/// calling core's share of the free RAM with the number of iterations doubling
+/// until the bandwidth changes by less than [`PLATEAU_TOLERANCE`] percent
+/// between doublings, reporting every step and the converged bandwidth.
+///
+/// Gives up converging after [`PLATEAU_DOUBLINGS`] doublings or once a single
+/// pass takes longer than [`PLATEAU_MSECS`], whichever comes first.
+///
+/// * `size`: Size of the working set in bytes.
+pub fn bench_plateau(size: usize)
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if size == 0 || size % 32 != 0 {
+        debug!("Working set size must be a non-zero multiple of 32 bytes");
+        return;
+    }
+    if share.len() < size {
+        debug!("Core #{core} only has {}KB of free RAM for the working set",
+               share.len() >> 10);
+        return;
+    }
+    let range = share.start .. share.start + size;
+    let pattern = pattern(range.start);
+    let limit = timer::frequency() / 1000 * PLATEAU_MSECS;
+    fill_stream(range.clone(), pattern, 1);
+    let mut last: Option<u128> = None;
+    let mut iterations = 1;
+    for _ in 0 .. PLATEAU_DOUBLINGS {
+        watchdog::pet();
+        let start = timer::now();
+        fill_stream(range.clone(), pattern, iterations);
+        let ticks = timer::elapsed(start, timer::now()).max(1);
+        let rate = (size as u128 * iterations as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+        debug!("Core #{core} {}KB working set {iterations} times: {rate}MB/s in {}",
+               size >> 10,
+               Duration(ticks));
+        if let Some(last) = last {
+            if rate.abs_diff(last) * 100 < last * PLATEAU_TOLERANCE {
+                debug!("Core #{core} converged on {rate}MB/s after {iterations} iterations");
+                return;
+            }
+        }
+        if ticks > limit {
+            break;
+        }
+        last = Some(rate);
+        iterations *= 2;
+    }
+    debug!("Core #{core} did not converge within {PLATEAU_TOLERANCE}% before {iterations} iterations");
+}
+
+/// Measures the write bandwidth of a kernel that fills a buffer that is kept
+/// in the L1 cache, describing its access pattern first.
+///
+/// * `name`: Name of the kernel.
+/// * `store`: Store instruction used by the kernel and number of bytes stored
+///   by each instruction.
+/// * `kernel`: Kernel taking the buffer, the pattern, and the number of times
+///   to fill the buffer.
+///
+/// Returns the bandwidth in megabytes per second, or `None` if it could not be
+/// measured.
+fn bench_fill(name: impl Display,
+              store: (&'static str, usize),
+              kernel: impl Fn(*mut u8, u64, usize))
+              -> Option<u128>
+{
+    let (instruction, width) = store;
+    let mut buf = MaybeUninit::<Buffer>::uninit();
+    let ptr = buf.as_mut_ptr().cast::<u8>();
+    let pattern = pattern(ptr as usize);
+    let access = Access { instruction,
+                          width,
+                          order: "sequential",
+                          range: ptr as usize .. ptr as usize + BUFFER_SIZE };
+    debug!("Core #{} {name} kernel access: {access}", core_index());
+    unsafe {
+        asm!(
+            "add {eaddr}, {addr}, #0x1000",
+            "0:",
+            "cmp {addr}, {eaddr}",
+            "beq 0f",
+            "prfm pstl1keep, [{addr}]",
+            "add {addr}, {addr}, {line}",
+            "b 0b",
+            "0:",
+            addr = inout (reg) ptr => _,
+            eaddr = out (reg) _,
+            line = in (reg) cache::line_sizes().0,
+        );
+    }
+    let measurement = measure(|iterations| kernel(ptr, pattern, iterations),
+                              || kernel(ptr, pattern, WARMUP_PASSES))?;
+    if VERIFY {
+        verify(ptr.cast(), size_of::<Buffer>(), pattern);
+    }
+    let core = core_index();
+    let freq = timer::frequency();
+    let Measurement { iterations, ticks, cycles } = measurement;
+    let bytes = iterations as u128 * BUFFER_SIZE as u128;
+    let rate = (bytes * freq as u128 / ticks as u128) >> 20;
+    debug!("Core #{core} {name} kernel wrote {} in {} ({iterations} iterations after {WARMUP_PASSES} warm-up passes, {rate}MB/s, {} bytes/cycle)",
+           Bytes(bytes),
+           Duration(ticks),
+           Fixed(bytes * 100 / cycles.max(1) as u128, 2));
+    Some(rate)
+}
+
+/// Measures the bandwidth of the cores in [`DUPLEX_READERS`] streaming loads
+/// from a shared region while the cores in [`DUPLEX_WRITERS`] stream stores to
+/// a separate region at the same time, reporting the bandwidth of each group
+/// and the aggregate, which shows whether reads and writes share the same
+/// ports of the memory system or starve each other.
+///
+/// Must only be called from core #0.
+pub fn bench_duplex()
+{
+    smp::run_on(DUPLEX_READERS | DUPLEX_WRITERS, duplex);
+    let results = smp::results();
+    let group = |mask: u8| (0 .. CPU_COUNT).filter(|core| mask & 1 << core != 0).map(|core| results[core]).sum::<u64>();
+    let (reads, writes) = (group(DUPLEX_READERS), group(DUPLEX_WRITERS));
+    debug!("Duplex reads by cores 0b{DUPLEX_READERS:04b}: {reads}MB/s, Writes by cores 0b{DUPLEX_WRITERS:04b}: {writes}MB/s, Aggregate: {}MB/s",
+           reads + writes);
+}
+
+/// Streams loads from the shared region or stores to the calling core's half
+/// of the separate region of the duplex benchmark, depending on the role of
+/// the calling core, for [`DUPLEX_MSECS`] in sync with the other cores, and
+/// records the bandwidth in megabytes per second.
+///
+/// Must be run as a job on all the cores in [`DUPLEX_READERS`] and
+/// [`DUPLEX_WRITERS`] simultaneously.
+fn duplex()
+{
+    let core = core_index();
+    let reading = DUPLEX_READERS & 1 << core != 0;
+    let free = ram::free();
+    let read = free.start .. free.start + DUPLEX_SIZE;
+    let half = DUPLEX_SIZE / 2;
+    let writer = (DUPLEX_WRITERS & ((1 << core) - 1)).count_ones() as usize;
+    let write = read.end + half * writer .. read.end + half * (writer + 1);
+    let enough = free.len() >= DUPLEX_SIZE * 2;
+    let range = if reading { read } else { write };
+    let pattern = pattern(range.start);
+    if enough && !reading {
+        fill_stream(range.clone(), pattern, 1);
+    }
+    let limit = timer::frequency() / 1000 * DUPLEX_MSECS;
+    watchdog::pet();
+    // Every core checks the free RAM on its own but meets the others either
+    // way, so that nobody waits at the barrier forever.
+    smp::sync();
+    if !enough {
+        if core == smp::leader() {
+            debug!("Not enough free RAM for the duplex benchmark");
+        }
+        return;
+    }
+    let mut passes = 0usize;
+    let start = timer::now();
+    let ticks = loop {
+        if reading {
+            load_stream(range.clone(), 1);
+        } else {
+            fill_stream(range.clone(), pattern, 1);
+        }
+        passes += 1;
+        let ticks = timer::elapsed(start, timer::now());
+        if ticks >= limit {
+            break ticks;
+        }
+    };
+    if VERIFY && !reading {
+        verify(range.start as *const u64, range.len(), pattern);
+    }
+    let rate = (passes as u128 * range.len() as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+    debug!("Core #{core} {}: {rate}MB/s ({passes} passes over {}MB)",
+           if reading { "shared region reads" } else { "separate region writes" },
+           range.len() >> 20);
+    smp::record(rate as u64);
+}
+
+/// Measures the DRAM write bandwidth of core #0 while the other cores spin in
+/// a tight loop of `nop` instructions and then while they wait for interrupts,
+/// reporting both bandwidths and the difference, which isolates the impact of
+/// the behavior of idle cores on the memory system.
+///
+/// Must only be called from core #0.
+pub fn bench_idle()
+{
+    let mut rates = [None; 2];
+    for (wfi, rate) in rates.iter_mut().enumerate() {
+        IDLE_WFI.store(wfi != 0, Ordering::Relaxed);
+        IDLE_DONE.store(false, Ordering::Relaxed);
+        smp::run_on(smp::ALL_CORES, idle);
+        *rate = Some(smp::results()[0] as u128).filter(|&rate| rate != 0);
+    }
+    let [spinning, waiting] = rates;
+    debug!("Core #0 DRAM write bandwidth over {}MB with the other cores spinning: {}MB/s, waiting for interrupts: {}MB/s ({})",
+           IDLE_SIZE >> 20,
+           Cell(spinning),
+           Cell(waiting),
+           Cell(waiting.zip(spinning).map(|(waiting, spinning)| Change(waiting, spinning))));
+}
+
+/// Measures the DRAM write bandwidth on core #0 and records it in megabytes
+/// per second, or keeps the calling core idle in the way selected by
+/// [`IDLE_WFI`] until core #0 is done, once all the cores are set up.
+///
+/// Must be run as a job on all the cores with core #0 among them.
+fn idle()
+{
+    let core = core_index();
+    if core != 0 {
+        let wfi = IDLE_WFI.load(Ordering::Relaxed);
+        smp::sync();
+        // Core #0 sends an interrupt to every idle core once it is done, which
+        // also wakes the cores that check the flag right before waiting.
+        while !IDLE_DONE.load(Ordering::Acquire) {
+            if wfi {
+                unsafe { asm!("wfi", options (nomem, nostack, preserves_flags)) };
+            } else {
+                unsafe { asm!("nop", options (nomem, nostack, preserves_flags)) };
+            }
+        }
+        return;
+    }
+    let share = ram::share(core);
+    let enough = share.len() >= IDLE_SIZE;
+    let range = share.start .. share.start + IDLE_SIZE.min(share.len());
+    let pattern = pattern(range.start);
+    if enough {
+        fill_stream(range.clone(), pattern, 1);
+    }
+    watchdog::pet();
+    smp::sync();
+    let measured = if enough {
+        measure(|iterations| fill_stream(range.clone(), pattern, iterations),
+                || fill_stream(range.clone(), pattern, 1))
+    } else {
+        debug!("Core #{core} does not have enough free RAM for the idle cores benchmark");
+        None
+    };
+    IDLE_DONE.store(true, Ordering::Release);
+    for other in (1 .. CPU_COUNT).filter(|other| smp::mask() & 1 << other != 0) {
+        gic::send(SGI, other);
+    }
+    if let Some(Measurement { iterations, ticks, .. }) = measured {
+        if VERIFY {
+            verify(range.start as *const u64, range.len(), pattern);
+        }
+        smp::record(((iterations as u128 * IDLE_SIZE as u128 * timer::frequency() as u128 / ticks as u128) >> 20) as u64);
+    }
+}
+
+/// Runs the write bandwidth benchmark for [`THROTTLE_SECS`] on the calling
+/// core, reporting the throughput of every one second window so that thermal
+/// throttling shows up as a declining staircase.
+pub fn throttle()
+{
+    let core = core_index();
+    let freq = timer::frequency();
+    if freq == 0 {
+        debug!("Core #{core} cannot detect throttling without a timer");
+        return;
+    }
+    let mut buf = MaybeUninit::<Buffer>::uninit();
+    let ptr = buf.as_mut_ptr().cast::<u8>();
+    let pattern = pattern(ptr as usize);
+    fill(ptr, pattern, WARMUP_PASSES);
+    let mut first = 0;
+    let mut last = 0;
+    for window in 0 .. THROTTLE_SECS {
+        watchdog::pet();
+        let start = timer::now();
+        let mut chunks = 0;
+        let ticks = loop {
+            fill(ptr, pattern, THROTTLE_CHUNK);
+            chunks += 1;
+            let ticks = timer::elapsed(start, timer::now());
+            if ticks >= freq {
+                break ticks;
+            }
+        };
+        let bytes = (chunks * THROTTLE_CHUNK * BUFFER_SIZE) as u128;
+        last = (bytes * freq as u128 / ticks as u128) >> 20;
+        if window == 0 {
+            first = last;
+        }
+        debug!("Core #{core} second {:>2}: {}MB written ({last}MB/s)",
+               window + 1,
+               bytes >> 20);
+    }
+    if VERIFY {
+        verify(ptr.cast(), size_of::<Buffer>(), pattern);
+    }
+    smp::record(last as u64);
+    let drop = (first as i128 - last as i128) * 100 / first.max(1) as i128;
+    debug!("Core #{core} throughput went from {first}MB/s in the first second to {last}MB/s in the last second ({drop}% drop)");
+}
+
+/// Measures the write bandwidth to a buffer that is kept in the L1 cache on
+/// the calling core trial after trial until a key is pressed, rewriting a
+/// single line with the latest bandwidth and the average of the last
+/// [`CONTINUOUS_WINDOW`] trials after every trial, and reporting a summary of
+/// all the trials at the end.
+///
+/// Every trial waits for the line to be transmitted before it starts timing,
+/// so updating the line does not disturb the measurements.
+pub fn continuous()
+{
+    let core = core_index();
+    let mut buf = MaybeUninit::<Buffer>::uninit();
+    let ptr = buf.as_mut_ptr().cast::<u8>();
+    let pattern = pattern(ptr as usize);
+    let mut window = [0; CONTINUOUS_WINDOW];
+    let (mut trials, mut total, mut min, mut max) = (0, 0, u128::MAX, 0);
+    debug!("Core #{core} continuous write bandwidth, press any key to stop");
+    while let Some(Measurement { iterations, ticks, .. }) = measure(|iterations| fill(ptr, pattern, iterations),
+                                                                    || fill(ptr, pattern, WARMUP_PASSES)) {
+        let rate = (iterations as u128 * BUFFER_SIZE as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+        window[trials % CONTINUOUS_WINDOW] = rate;
+        trials += 1;
+        total += rate;
+        min = min.min(rate);
+        max = max.max(rate);
+        let count = trials.min(CONTINUOUS_WINDOW);
+        let average = window[.. count].iter().sum::<u128>() / count as u128;
+        let mut uart = UART.lock();
+        // The carriage return moves back to the start of the line, and the
+        // fixed width fields overwrite everything printed there before.
+        write!(uart,
+               "\rTrial {trials:>8}: {rate:>8}MB/s, Average of the last {count} trials: {average:>8}MB/s")
+        .unwrap();
+        if uart.read().is_some() {
+            // Keys such as Enter send more than one byte.
+            while uart.read().is_some() {}
+            break;
+        }
+    }
+    UART.lock().write_char('\n').unwrap();
+    if VERIFY {
+        verify(ptr.cast(), size_of::<Buffer>(), pattern);
+    }
+    if trials == 0 {
+        return;
+    }
+    debug!("Core #{core} continuous write over {trials} trials: Mean: {}MB/s, Min: {min}MB/s, Max: {max}MB/s",
+           total / trials as u128);
+}
+
+/// Measures the latency of loads that depend on each other by chasing a chain
+/// of pointers laid out in random order with one pointer per cache line.
+///
+/// * `state`: State of the caches when the measurement starts.
+///
+/// Returns the latency in hundredths of a nanosecond, or `None` if it could
+/// not be measured.
+fn bench_latency(state: CacheState) -> Option<u128>
+{
+    let size = match state {
+        CacheState::Hot => HOT_CHAIN_SIZE,
+        CacheState::Cold => COLD_CHAIN_SIZE,
+    };
+    latency(state, size, false)
+}
+
+/// Measures the latency of loads that depend on each other by chasing a chain
+/// of pointers of any size.
+///
+/// * `state`: State of the caches when the measurement starts.
+/// * `size`: Size of the pointer chain in bytes.
+/// * `histogram`: Whether to also time a sample of the loads one by one and
+///   print their histogram, which takes far longer than the measurement.
+pub fn bench_latency_sized(state: CacheState, size: usize, histogram: bool)
+{
+    if size == 0 || size % LINE_SIZE != 0 {
+        debug!("Pointer chain size must be a non-zero multiple of {LINE_SIZE} bytes");
+        return;
+    }
+    latency(state, size, histogram);
+}
+
+/// Measures the latency of loads that depend on each other by chasing a chain
+/// of pointers laid out in random order with one pointer per cache line.
+///
+/// * `state`: State of the caches when the measurement starts.
+/// * `size`: Size of the pointer chain in bytes, which must be a non-zero
+///   multiple of the cache line size.
+/// * `sampled`: Whether to also sample the latency histogram of the chain.
+///
+/// Returns the latency in hundredths of a nanosecond, or `None` if it could
+/// not be measured.
+fn latency(state: CacheState, size: usize, sampled: bool) -> Option<u128>
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < size {
+        debug!("Core #{core} does not have enough free RAM for the {state:?} latency benchmark");
+        return None;
+    }
+    let chain = share.start .. share.start + size;
+    link(chain.clone());
+    debug!("Core #{core} {state:?} latency access: {}",
+           Access { instruction: "ldr x",
+                    width: 8,
+                    order: "dependent, one per line in random order",
+                    range: chain.clone() });
+    let loads = size / LINE_SIZE;
+    let warm_up = || match state {
+        CacheState::Hot => {
+            chase(chain.start, loads * WARMUP_PASSES);
+        }
+        CacheState::Cold => cache::clean_invalidate(chain.clone()),
+    };
+    let measurement = measure(|iterations| {
+                                  chase(chain.start, loads * iterations);
+                              },
+                              warm_up)?;
+    let freq = timer::frequency();
+    let Measurement { iterations, ticks, .. } = measurement;
+    let centis = ticks as u128 * 100_000_000_000 / freq as u128 / (loads * iterations) as u128;
+    let method = match state {
+        CacheState::Hot => "warming up the chain",
+        CacheState::Cold => "flushing the chain",
+    };
+    debug!("Core #{core} {state:?} latency: {}.{:02}ns ({iterations} passes over {}KB after {method})",
+           centis / 100,
+           centis % 100,
+           size >> 10);
+    if sampled {
+        histogram(state, chain.start);
+    }
+    Some(centis)
+}
+
+/// Measures the latency of loads from DRAM that depend on each other along a
+/// chain confined to one DRAM row at a time, which mostly hits open rows, and
+/// along a chain whose successive pointers are always in different 4KB pages,
+/// which mostly misses them.
+///
+/// Both chains are flushed from the caches before every pass and visit the
+/// lines of each row or the pages in bit reversed order, which the prefetcher
+/// cannot follow.  The DRAM row size isn't discoverable, so
+/// [`DRAM_ROW_SIZE`] is assumed and reported along with the results.
+pub fn bench_dram_latency()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    let hit_size = DRAM_CHAIN_LOADS * LINE_SIZE;
+    let miss_size = DRAM_CHAIN_LOADS * DRAM_PAGE_SIZE;
+    if share.len() < hit_size + miss_size {
+        debug!("Core #{core} does not have enough free RAM for the DRAM latency benchmark");
+        return;
+    }
+    let hit = share.start;
+    let miss = hit + hit_size;
+    let row_lines = DRAM_ROW_SIZE / LINE_SIZE;
+    let hit_chain = |idx: usize| {
+        hit + idx / row_lines * DRAM_ROW_SIZE + reverse_bits(idx % row_lines, row_lines) * LINE_SIZE
+    };
+    let miss_chain = |idx: usize| miss + reverse_bits(idx, DRAM_CHAIN_LOADS) * DRAM_PAGE_SIZE;
+    let hit_centis = dram_latency(&hit_chain);
+    let miss_centis = dram_latency(&miss_chain);
+    debug!("Core #{core} DRAM row hit latency: {}ns ({DRAM_CHAIN_LOADS} loads {row_lines} per row, assuming {}KB DRAM rows)",
+           Fixed(hit_centis, 2),
+           DRAM_ROW_SIZE >> 10);
+    debug!("Core #{core} DRAM row miss latency: {}ns ({DRAM_CHAIN_LOADS} loads each in a different {}KB page, {} the row hit latency)",
+           Fixed(miss_centis, 2),
+           DRAM_PAGE_SIZE >> 10,
+           Ratio(miss_centis, hit_centis));
+}
+
+/// Writes whole cache lines in random order on the calling core, with the
+/// order read from a shuffled table of line indices and generated in
+/// registers by hashing a counter, reporting both bandwidths so that the cost
+/// of the index loads competing with the writes shows.
+///
+/// Both orders write every line of the buffer exactly once per pass.
+pub fn bench_random()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    let table_size = RANDOM_LINES * size_of::<u32>();
+    if share.len() < RANDOM_SIZE + table_size {
+        debug!("Core #{core} does not have enough free RAM for the random write benchmark");
+        return;
+    }
+    let buf = share.start .. share.start + RANDOM_SIZE;
+    let table = unsafe { slice::from_raw_parts_mut(buf.end as *mut u32, RANDOM_LINES) };
+    for (idx, entry) in table.iter_mut().enumerate() {
+        *entry = idx as u32;
+    }
+    let mut state = 0x2545F4914F6CDD1Du64;
+    for idx in (1 .. RANDOM_LINES).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        table.swap(idx, (state % (idx as u64 + 1)) as usize);
+    }
+    let pattern = pattern(buf.start);
+    let rate = |Measurement { iterations, ticks, .. }| (iterations as u128 * RANDOM_SIZE as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+    let indexed = measure(|iterations| write_indexed(buf.start, table, pattern, iterations),
+                          || write_indexed(buf.start, table, pattern, 1)).map(rate);
+    if VERIFY {
+        verify(buf.start as *const u64, RANDOM_SIZE, pattern);
+    }
+    let pattern = !pattern;
+    let generated = measure(|iterations| write_generated(buf.start, pattern, iterations),
+                            || write_generated(buf.start, pattern, 1)).map(rate);
+    if VERIFY {
+        verify(buf.start as *const u64, RANDOM_SIZE, pattern);
+    }
+    debug!("Core #{core} random line writes over {}MB: Table: {}MB/s, Generated: {}MB/s, Generated is {} the table",
+           RANDOM_SIZE >> 20,
+           Cell(indexed),
+           Cell(generated),
+           Cell(generated.zip(indexed).map(|(generated, indexed)| Ratio(generated, indexed))));
+}
+
+/// Writes and reads a stream on the calling core at every placement in
+/// [`PLACEMENTS`] within a reserved region, reporting the bandwidths along
+/// with the address of each placement so that aliasing between the stream and
+/// the cache sets or ways shows.
+pub fn bench_placement()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < PLACEMENT_SIZE + BLOCK_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the placement benchmark");
+        return;
+    }
+    let rate = |Measurement { iterations, ticks, .. }| (iterations as u128 * PLACEMENT_SIZE as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+    let results = PLACEMENTS.map(|(name, offset)| {
+                                let start = share.start + offset;
+                                let stream = start .. start + PLACEMENT_SIZE;
+                                let pattern = pattern(stream.start);
+                                let write = measure(|iterations| fill_stream(stream.clone(), pattern, iterations),
+                                                    || fill_stream(stream.clone(), pattern, 1)).map(rate);
+                                if VERIFY {
+                                    verify(stream.start as *const u64, PLACEMENT_SIZE, pattern);
+                                }
+                                let read = measure(|iterations| load_stream(stream.clone(), iterations),
+                                                   || load_stream(stream.clone(), 1)).map(rate);
+                                (name, start, write, read)
+                            });
+    let mut uart = UART.lock();
+    writeln!(uart, "Core #{core} streaming {}MB at every placement:", PLACEMENT_SIZE >> 20).unwrap();
+    writeln!(uart, "Write\tRead\tAddress\t\tPlacement").unwrap();
+    for (name, start, write, read) in results {
+        writeln!(uart, "{}\t{}\t0x{start:08X}\t{name}", Cell(write), Cell(read)).unwrap();
+    }
+}
+
+/// Streams through a region and reads one cache line from every page of it on
+/// the calling core with the region mapped in 2MB blocks and then in 4KB
+/// pages, printing a table of the bandwidths, and maps the region in blocks
+/// again afterwards.
+///
+/// The strided bandwidth counts one cache line per page, and the line read
+/// moves along the pages so that the accesses spread across the cache sets.
+pub fn bench_granule()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    // The translation tables of the pages take the first block of the share.
+    if share.len() < GRANULE_SIZE + BLOCK_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the granule benchmark");
+        return;
+    }
+    let tables = share.start;
+    let region = share.start + BLOCK_SIZE .. share.start + BLOCK_SIZE + GRANULE_SIZE;
+    let pattern = pattern(region.start);
+    fill_stream(region.clone(), pattern, 1);
+    let rate = |len| move |Measurement { iterations, ticks, .. }| (iterations as u128 * len as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+    let run = || {
+        let stream = measure(|iterations| fill_stream(region.clone(), pattern, iterations),
+                             || fill_stream(region.clone(), pattern, 1)).map(rate(GRANULE_SIZE));
+        if VERIFY {
+            verify(region.start as *const u64, GRANULE_SIZE, pattern);
+        }
+        let strided = measure(|iterations| load_strided(region.clone(), iterations),
+                              || load_strided(region.clone(), 1)).map(rate(GRANULE_SIZE / PAGE_SIZE * LINE_SIZE));
+        (stream, strided)
+    };
+    let blocks = run();
+    mmu::map_pages(region.clone(), tables);
+    let pages = run();
+    mmu::map_ram(region);
+    let mut uart = UART.lock();
+    writeln!(uart, "Core #{core} streaming and reading a line per page over {}MB:", GRANULE_SIZE >> 20).unwrap();
+    writeln!(uart, "Stream\tStrided\tMapping").unwrap();
+    for (name, (stream, strided)) in [("2MB blocks", blocks), ("4KB pages", pages)] {
+        writeln!(uart, "{}\t{}\t{name}", Cell(stream), Cell(strided)).unwrap();
+    }
+    writeln!(uart,
+             "4KB pages against 2MB blocks: Stream: {}, Strided: {}",
+             Cell(pages.0.zip(blocks.0).map(|(pages, blocks)| Change(pages, blocks))),
+             Cell(pages.1.zip(blocks.1).map(|(pages, blocks)| Change(pages, blocks)))).unwrap();
+}
+
+/// Reads a DRAM stream on the calling core with plain load pairs and then
+/// with a streaming prefetch [`PREFETCH_DISTANCE`] bytes ahead of every cache
+/// line, reporting both bandwidths and the improvement from prefetching.
+pub fn bench_prefetch()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < PREFETCH_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the prefetch benchmark");
+        return;
+    }
+    let stream = share.start .. share.start + PREFETCH_SIZE;
+    fill_stream(stream.clone(), pattern(stream.start), 1);
+    let rate = |Measurement { iterations, ticks, .. }| (iterations as u128 * PREFETCH_SIZE as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+    let plain = measure(|iterations| load_stream(stream.clone(), iterations),
+                        || load_stream(stream.clone(), 1)).map(rate);
+    let prefetched = measure(|iterations| load_stream_prefetched(stream.clone(), iterations),
+                             || load_stream_prefetched(stream.clone(), 1)).map(rate);
+    debug!("Core #{core} DRAM stream read over {}MB: Plain: {}MB/s, Prefetched {PREFETCH_DISTANCE} bytes ahead: {}MB/s ({})",
+           PREFETCH_SIZE >> 20,
+           Cell(plain),
+           Cell(prefetched),
+           Cell(prefetched.zip(plain).map(|(prefetched, plain)| Change(prefetched, plain))));
+}
+
+/// Measures the DRAM stream read and write bandwidth on the calling core at
+/// every setting of the L2 data prefetch distance of the Cortex-A72, printing
+/// a table of the results, and restores the setting that the core booted with
+/// afterwards.
+///
+/// Changing the setting requires the firmware to allow EL1 to access the CPU
+/// extended control register, which is checked without faulting.
+pub fn bench_l2_prefetch()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < PREFETCH_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the L2 prefetch benchmark");
+        return;
+    }
+    impdef::allow();
+    let Some(boot) = impdef::l2_distance() else {
+        debug!("Core #{core} cannot access the L2 prefetch control from EL1 or is not a Cortex-A72");
+        return;
+    };
+    let stream = share.start .. share.start + PREFETCH_SIZE;
+    let pattern = pattern(stream.start);
+    let rate = |Measurement { iterations, ticks, .. }| (iterations as u128 * PREFETCH_SIZE as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+    fill_stream(stream.clone(), pattern, 1);
+    let rows = array::from_fn::<_, { impdef::L2_DISTANCES as usize }, _>(|setting| {
+                   if !impdef::set_l2_distance(setting as u64) {
+                       return None;
+                   }
+                   let read = measure(|iterations| load_stream(stream.clone(), iterations),
+                                      || load_stream(stream.clone(), 1)).map(rate);
+                   let write = measure(|iterations| fill_stream(stream.clone(), pattern, iterations),
+                                       || fill_stream(stream.clone(), pattern, 1)).map(rate);
+                   Some((read, write))
+               });
+    impdef::set_l2_distance(boot);
+    if VERIFY {
+        verify(stream.start as *const u64, PREFETCH_SIZE, pattern);
+    }
+    let mut uart = UART.lock();
+    writeln!(uart, "Core #{core} DRAM stream over {}MB by L2 data prefetch distance:", PREFETCH_SIZE >> 20).unwrap();
+    writeln!(uart, "Read\tWrite\tSetting").unwrap();
+    writeln!(uart, "MB/s\tMB/s").unwrap();
+    for (setting, row) in rows.iter().enumerate() {
+        let boot = if setting as u64 == boot { " (boot)" } else { "" };
+        match row {
+            Some((read, write)) => writeln!(uart, "{}\t{}\t{setting}{boot}", Cell(*read), Cell(*write)).unwrap(),
+            None => writeln!(uart, "-\t-\t{setting}{boot} (rejected)").unwrap(),
+        }
+    }
+}
+
+/// Measures the bandwidth of incrementing every double-word of a DRAM buffer
+/// in place on the calling core, counting both the read and the write traffic,
+/// and verifies that the buffer went through every pass.
+pub fn bench_rmw()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < RMW_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the read-modify-write benchmark");
+        return;
+    }
+    let range = share.start .. share.start + RMW_SIZE;
+    let pattern = pattern(range.start);
+    fill_stream(range.clone(), pattern, 1);
+    // Calibration and warm-up passes increment the buffer too.
+    let passes = AtomicUsize::new(0);
+    let kernel = |iterations| {
+        increment_stream(range.clone(), iterations);
+        passes.fetch_add(iterations, Ordering::Relaxed);
+    };
+    let Some(Measurement { iterations, ticks, .. }) = measure(kernel, || kernel(1)) else {
+        return;
+    };
+    if VERIFY {
+        let passes = passes.load(Ordering::Relaxed) as u64;
+        let buf = range.start as *const u64;
+        for idx in 0 .. RMW_SIZE / size_of::<u64>() {
+            let addr = unsafe { buf.add(idx) };
+            let expected = fill_value(pattern, idx).wrapping_add(passes);
+            let actual = unsafe { addr.read() };
+            assert!(actual == expected,
+                    "Core #{core} verification failed at 0x{:X} after {passes} passes: Expected: 0x{expected:016X}, Actual: 0x{actual:016X}",
+                    addr as usize);
+        }
+    }
+    let bytes = iterations as u128 * RMW_SIZE as u128 * 2;
+    let rate = (bytes * timer::frequency() as u128 / ticks as u128) >> 20;
+    debug!("Core #{core} read-modify-write over {}MB: {rate}MB/s of reads and writes ({iterations} passes)",
+           RMW_SIZE >> 20);
+}
+
+/// Links and chases a DRAM latency chain, flushing it from the caches before
+/// every pass.
+///
+/// * `chain`: Address of each of the [`DRAM_CHAIN_LOADS`] pointers in the
+///   order in which they are chased.
+///
+/// Returns the average latency in hundredths of a nanosecond.
+fn dram_latency(chain: &impl Fn(usize) -> usize) -> u128
+{
+    for idx in 0 .. DRAM_CHAIN_LOADS {
+        unsafe { (chain(idx) as *mut usize).write(chain((idx + 1) % DRAM_CHAIN_LOADS)) };
+    }
+    watchdog::pet();
+    let mut ticks = 0;
+    for _ in 0 .. DRAM_PASSES {
+        for idx in 0 .. DRAM_CHAIN_LOADS {
+            let addr = chain(idx);
+            cache::clean_invalidate(addr .. addr + size_of::<usize>());
+        }
+        let start = timer::now();
+        chase(chain(0), DRAM_CHAIN_LOADS);
+        ticks += timer::elapsed(start, timer::now());
+    }
+    ticks as u128 * 100_000_000_000 / timer::frequency() as u128 / (DRAM_CHAIN_LOADS * DRAM_PASSES) as u128
+}
+
+/// Reverses the order of the bits of an index.
+///
+/// * `idx`: Index to reverse.
+/// * `count`: Number of possible indices, which must be a power of two.
+///
+/// Returns the index with its significant bits reversed.
+fn reverse_bits(idx: usize, count: usize) -> usize
+{
+    let bits = count.trailing_zeros();
+    if bits == 0 {
+        return 0;
+    }
+    idx.reverse_bits() >> (usize::BITS - bits)
+}
+
+/// Measures the cost of acquiring and releasing a lock that no other core
+/// attempts to acquire.
+fn bench_lock()
+{
+    let lock = Lock::new(());
+    let kernel = |iterations| {
+        for _ in 0 .. iterations {
+            drop(lock.lock());
+        }
+    };
+    let Some(Measurement { iterations, ticks, .. }) = measure(kernel, || kernel(WARMUP_PASSES)) else {
+        return;
+    };
+    let core = core_index();
+    let rate = iterations as u128 * timer::frequency() as u128 / ticks as u128;
+    let centis = timer::nanos(ticks) * 100 / iterations as u128;
+    debug!("Core #{core} uncontended lock: {}ns per acquisition and release ({rate} acquisitions/s)",
+           Fixed(centis, 2));
+}
+
+/// Measures the latency of reading a device register with every read depending
+/// on the result of the previous one.
+fn bench_mmio_read()
+{
+    let kernel = |iterations| {
+        for _ in 0 .. iterations {
+            unsafe {
+                asm!(
+                    "mov {count}, #{burst}",
+                    "0:",
+                    "ldr {val:w}, [{reg}]",
+                    "and {val}, {val}, xzr",
+                    "add {reg}, {reg}, {val}",
+                    "subs {count}, {count}, #1",
+                    "bne 0b",
+                    reg = inout (reg) MMIO_READ_REG => _,
+                    val = out (reg) _,
+                    count = out (reg) _,
+                    burst = const MMIO_BURST,
+                    options (nostack)
+                );
+            }
+        }
+    };
+    report_mmio("read latency", measure(kernel, || kernel(WARMUP_PASSES)));
+}
+
+/// Measures the cost of writing to a device register by timing bursts of
+/// writes that each end with a barrier that waits for them to complete.
+///
+/// The peripherals are mapped as Device-nGnRnE memory, so the writes can
+/// neither be gathered nor acknowledged early by the interconnect.
+fn bench_mmio_write()
+{
+    let kernel = |iterations| {
+        for _ in 0 .. iterations {
+            unsafe {
+                asm!(
+                    "mov {count}, #{burst}",
+                    "0:",
+                    "str wzr, [{reg}]",
+                    "subs {count}, {count}, #1",
+                    "bne 0b",
+                    "dsb sy",
+                    reg = in (reg) MMIO_WRITE_REG,
+                    count = out (reg) _,
+                    burst = const MMIO_BURST,
+                    options (nostack)
+                );
+            }
+        }
+    };
+    report_mmio("write cost", measure(kernel, || kernel(WARMUP_PASSES)));
+}
+
+/// Measures how fast the calling core can toggle [`TOGGLE_PIN`], both with
+/// posted writes and with a barrier after every write, producing a square wave
+/// that can be verified with an oscilloscope.
+pub fn bench_gpio()
+{
+    gpio::select(TOGGLE_PIN, gpio::Function::Output);
+    for fenced in [false, true] {
+        let kernel = |iterations| {
+            for _ in 0 .. iterations {
+                gpio::set(TOGGLE_PIN);
+                if fenced {
+                    unsafe { asm!("dsb sy", options (nostack, preserves_flags)) };
+                }
+                gpio::clear(TOGGLE_PIN);
+                if fenced {
+                    unsafe { asm!("dsb sy", options (nostack, preserves_flags)) };
+                }
+            }
+        };
+        let Some(Measurement { iterations, ticks, .. }) = measure(kernel, || kernel(WARMUP_PASSES)) else {
+            continue;
+        };
+        let freq = iterations as u128 * timer::frequency() as u128 / ticks as u128;
+        debug!("GPIO {TOGGLE_PIN} {} a barrier per write: {} toggles/s ({freq}Hz square wave)",
+               if fenced { "with" } else { "without" },
+               freq * 2);
+    }
+    gpio::select(TOGGLE_PIN, gpio::Function::Input);
+}
+
+/// Measures the round trip latency of a trivial mailbox property request.
+pub fn bench_mailbox()
+{
+    let mut samples = [0; MAILBOX_SAMPLES];
+    for sample in samples.iter_mut() {
+        let start = timer::now();
+        let res = mbox::firmware_revision();
+        *sample = timer::elapsed(start, timer::now());
+        if let Err(err) = res {
+            debug!("Mailbox latency benchmark failed: {err}");
+            return;
+        }
+    }
+    samples.sort_unstable();
+    let usecs = |ticks| Fixed(timer::nanos(ticks), 3);
+    debug!("Mailbox round trip latency over {MAILBOX_SAMPLES} requests: Min: {}us, Median: {}us, Max: {}us",
+           usecs(samples[0]),
+           usecs(samples[MAILBOX_SAMPLES / 2]),
+           usecs(samples[MAILBOX_SAMPLES - 1]));
+}
+
+/// Measures the round trip latency of a supervisor call, from the `svc`
+/// instruction through the exception entry to the `eret` that the vector runs
+/// right away, in CPU cycles and nanoseconds.
+pub fn bench_svc()
+{
+    let Some(Measurement { iterations, ticks, .. }) = measure(svc_calls, || svc_calls(WARMUP_PASSES)) else {
+        return;
+    };
+    // The measured number of iterations runs for about the same time with the
+    // cycle counter instead of the timer.
+    let start = pmu::cycles();
+    svc_calls(iterations);
+    let cycles = pmu::cycles() - start;
+    debug!("Core #{} SVC round trip: {} cycles, {}ns ({iterations} calls)",
+           core_index(),
+           Fixed(cycles as u128 * 100 / iterations as u128, 2),
+           Fixed(timer::nanos(ticks) * 100 / iterations as u128, 2));
+}
+
+/// Runs the DRAM streaming store kernel and a loop of supervisor calls at EL0
+/// on the calling core and then the same number of iterations at EL1,
+/// reporting the write bandwidth and the round trip latency at both levels
+/// along with which cache related instructions EL0 is allowed to use.
+///
+/// Both levels are timed with the generic timer, since the system timer is
+/// not accessible at EL0.
+pub fn bench_user()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < CACHES_STREAM_SIZE + BLOCK_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the EL0 benchmark");
+        return;
+    }
+    debug!("Core #{core} {}", mmu::UserAccess(mmu::sctlr().0));
+    // The arguments and the stack take the block right after the stream.
+    let region = share.start .. share.start + CACHES_STREAM_SIZE + BLOCK_SIZE;
+    let stream = region.start .. region.start + CACHES_STREAM_SIZE;
+    let pattern = pattern(stream.start);
+    mmu::map_user(region.clone());
+    let (Some(Measurement { iterations: passes, .. }), Some(Measurement { iterations: calls, .. })) =
+        (measure(|iterations| fill_stream(stream.clone(), pattern, iterations),
+                 || fill_stream(stream.clone(), pattern, 1)),
+         measure(svc_calls, || svc_calls(WARMUP_PASSES)))
+    else {
+        mmu::map_ram(region);
+        return;
+    };
+    let args = stream.end as *mut UserArgs;
+    unsafe {
+        args.write(UserArgs { stream: stream.clone(),
+                              pattern,
+                              passes,
+                              calls,
+                              stream_ticks: 0,
+                              call_ticks: 0,
+                              call_cycles: 0 })
+    };
+    watchdog::pet();
+    user::call(user_kernels, args as usize, region.end);
+    let user = unsafe { args.read() };
+    let start = timer::generic_now();
+    fill_stream(stream.clone(), pattern, passes);
+    let stream_ticks = timer::generic_now() - start;
+    let (start, cycles) = (timer::generic_now(), pmu::cycles());
+    svc_calls(calls);
+    let call_cycles = pmu::cycles() - cycles;
+    let call_ticks = timer::generic_now() - start;
+    if VERIFY {
+        verify(stream.start as *const u64, stream.len(), pattern);
+    }
+    mmu::map_ram(region);
+    let freq = timer::generic_frequency() as u128;
+    let rate = |ticks: usize| (passes as u128 * stream.len() as u128 * freq / ticks.max(1) as u128) >> 20;
+    let (low, high) = (rate(user.stream_ticks), rate(stream_ticks));
+    debug!("Core #{core} DRAM stream write at EL0: {low}MB/s, at EL1: {high}MB/s, EL0 is {} EL1 ({passes} passes)",
+           Ratio(low, high));
+    let nanos = |ticks: usize| Fixed(ticks as u128 * 100_000_000_000 / freq / calls as u128, 2);
+    let cycles = |cycles: usize| Fixed(cycles as u128 * 100 / calls as u128, 2);
+    debug!("Core #{core} SVC round trip from EL0: {} cycles, {}ns, from EL1: {} cycles, {}ns ({calls} calls)",
+           cycles(user.call_cycles),
+           nanos(user.call_ticks),
+           cycles(call_cycles),
+           nanos(call_ticks));
+}
+
+/// Runs the DRAM streaming store kernel and then the supervisor calls at EL0
+/// without accessing anything but the arguments.
+///
+/// * `arg`: Address of the [`UserArgs`].
+///
+/// Returns zero.
+extern "C" fn user_kernels(arg: usize) -> usize
+{
+    let args = unsafe { &mut *(arg as *mut UserArgs) };
+    let start = timer::generic_now();
+    fill_stream(args.stream.clone(), args.pattern, args.passes);
+    args.stream_ticks = timer::generic_now() - start;
+    let (start, cycles) = (timer::generic_now(), pmu::cycles());
+    svc_calls(args.calls);
+    args.call_cycles = pmu::cycles() - cycles;
+    args.call_ticks = timer::generic_now() - start;
+    0
+}
+
+/// Issues supervisor calls that the exception vector returns from right away.
+///
+/// * `count`: Number of calls, with zero issuing one call.
+fn svc_calls(count: usize)
+{
+    unsafe {
+        asm!(
+            "0:",
+            "svc #0",
+            "subs {count}, {count}, #1",
+            "bne 0b",
+            count = inout (reg) count.max(1) => _,
+            options (nomem, nostack)
+        );
+    }
+}
+
+/// Measures the one-way latency of software generated interrupts sent from
+/// this core to [`SGI_TARGET`], from right before the write that sends each
+/// one to right when the handler on the target core reads the timer.
+///
+/// The target core must be idle, waiting for jobs with its interrupts
+/// unmasked.
+pub fn bench_sgi()
+{
+    let core = core_index();
+    if core == SGI_TARGET {
+        debug!("Core #{core} cannot send interrupts to itself");
+        return;
+    }
+    watchdog::pet();
+    let timeout = timer::frequency() / 1000 * SGI_TIMEOUT_MSECS;
+    let mut samples = [0; SGI_SAMPLES];
+    for sample in samples.iter_mut() {
+        SGI_STAMP.store(0, Ordering::Relaxed);
+        let start = timer::now();
+        gic::send(SGI, SGI_TARGET);
+        let stamp = loop {
+            let stamp = SGI_STAMP.load(Ordering::Acquire);
+            if stamp != 0 {
+                break stamp;
+            }
+            if timer::elapsed(start, timer::now()) > timeout {
+                debug!("Core #{SGI_TARGET} did not handle the interrupt from core #{core}");
+                return;
+            }
+            spin_loop();
+        };
+        *sample = timer::elapsed(start, stamp);
+    }
+    samples.sort_unstable();
+    debug!("Core #{core} to core #{SGI_TARGET} interrupt latency over {SGI_SAMPLES} interrupts: Min: {}ns, Median: {}ns, Max: {}ns",
+           timer::nanos(samples[0]),
+           timer::nanos(samples[SGI_SAMPLES / 2]),
+           timer::nanos(samples[SGI_SAMPLES - 1]));
+}
+
+/// Handles the software generated interrupt of the inter-core interrupt
+/// latency benchmark by recording when it ran.
+pub fn sgi_interrupt()
+{
+    SGI_STAMP.store(timer::now(), Ordering::Release);
+}
+
+/// Measures the bandwidth of copying a large buffer from DRAM to DRAM with the
+/// DMA controller, with the CPU through the compiler's `memcpy`, and with the
+/// CPU through a kernel of vector load and store pairs.
+pub fn bench_dma()
+{
+    let free = ram::free();
+    if free.len() < DMA_SIZE * 2 {
+        debug!("Not enough free RAM for the DMA benchmark");
+        return;
+    }
+    let src = free.start;
+    let dst = src + DMA_SIZE .. src + DMA_SIZE * 2;
+    let pattern = pattern(src);
+    for (idx, word) in (src .. src + DMA_SIZE).step_by(8).enumerate() {
+        unsafe { (word as *mut u64).write(fill_value(pattern, idx)) };
+    }
+    unsafe { (dst.start as *mut u8).write_bytes(0, DMA_SIZE) };
+    watchdog::pet();
+    let start = timer::now();
+    let res = dma::copy(dst.clone(), src);
+    let ticks = timer::elapsed(start, timer::now());
+    if let Err(err) = res {
+        debug!("DMA benchmark failed: {err}");
+        return;
+    }
+    verify(dst.start as *const u64, DMA_SIZE, pattern);
+    let dma = report_copy("DMA", ticks);
+    unsafe { (dst.start as *mut u8).write_bytes(0, DMA_SIZE) };
+    let start = timer::now();
+    unsafe { (dst.start as *mut u8).copy_from_nonoverlapping(src as *const u8, DMA_SIZE) };
+    let ticks = timer::elapsed(start, timer::now());
+    verify(dst.start as *const u64, DMA_SIZE, pattern);
+    let memcpy = report_copy("CPU memcpy", ticks);
+    unsafe { (dst.start as *mut u8).write_bytes(0, DMA_SIZE) };
+    let ticks = bench_copy(dst.clone(), src);
+    verify(dst.start as *const u64, DMA_SIZE, pattern);
+    let vector = report_copy("CPU vector", ticks);
+    debug!("DMA is {} CPU memcpy and {} CPU vector",
+           Ratio(dma, memcpy),
+           Ratio(dma, vector));
+}
+
+/// Times a single copy with the vector copy kernel.
+///
+/// * `dst`: Destination memory range.
+/// * `src`: Source address.
+///
+/// Returns the elapsed timer ticks.
+fn bench_copy(dst: Range<usize>, src: usize) -> usize
+{
+    watchdog::pet();
+    let start = timer::now();
+    copy_stream(dst, src);
+    timer::elapsed(start, timer::now())
+}
+
+/// Measures how much the CPU streaming stores to DRAM and a DMA copy degrade
+/// each other when running at the same time, which tells whether the DRAM
+/// controller has headroom beyond what a single agent can use.
+pub fn bench_interference()
+{
+    let free = ram::free();
+    let len = ((free.len().saturating_sub(STREAM_SIZE) / 2) & !(LINE_SIZE - 1)).min(dma::MAX_LEN);
+    if len < STREAM_SIZE {
+        debug!("Not enough free RAM for the interference benchmark");
+        return;
+    }
+    let stream = free.start .. free.start + STREAM_SIZE;
+    let src = stream.end;
+    let dst = src + len .. src + len * 2;
+    let pattern = pattern(stream.start);
+    let Some(alone) = measure(|iterations| fill_stream(stream.clone(), pattern, iterations),
+                              || fill_stream(stream.clone(), pattern, 1))
+    else {
+        return;
+    };
+    let cpu_alone = (alone.iterations as u128 * STREAM_SIZE as u128 * timer::frequency() as u128 / alone.ticks as u128) >> 20;
+    watchdog::pet();
+    let start = timer::now();
+    if let Err(err) = dma::copy(dst.clone(), src) {
+        debug!("Interference benchmark failed: {err}");
+        return;
+    }
+    let dma_ticks = timer::elapsed(start, timer::now());
+    let dma_alone = (len as u128 * timer::frequency() as u128 / dma_ticks.max(1) as u128) >> 20;
+    // Keep the CPU busy for half of the time that the DMA copy takes alone, so
+    // the copy outlasts the CPU measurement even if the CPU slows it down.
+    let iterations = (alone.iterations as u128 * dma_ticks as u128 / 2 / alone.ticks as u128).max(1) as usize;
+    watchdog::pet();
+    let transfer = dma::start(dst, src);
+    let start = timer::now();
+    fill_stream(stream.clone(), pattern, iterations);
+    let ticks = timer::elapsed(start, timer::now()).max(1);
+    let copied = transfer.progress();
+    let overlapped = copied < len;
+    if let Err(err) = transfer.wait() {
+        debug!("Interference benchmark failed: {err}");
+        return;
+    }
+    if VERIFY {
+        verify(stream.start as *const u64, STREAM_SIZE, pattern);
+    }
+    let cpu_shared = (iterations as u128 * STREAM_SIZE as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+    let dma_shared = (copied as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+    if !overlapped {
+        debug!("DMA copy finished before the CPU, so the results below are not fully overlapped");
+    }
+    debug!("CPU stores: Alone: {cpu_alone}MB/s, Shared: {cpu_shared}MB/s ({}% of alone)",
+           cpu_shared * 100 / cpu_alone.max(1));
+    debug!("DMA copy: Alone: {dma_alone}MB/s, Shared: {dma_shared}MB/s ({}% of alone)",
+           dma_shared * 100 / dma_alone.max(1));
+    debug!("Aggregate: {}MB/s shared against {}MB/s for the fastest agent alone",
+           cpu_shared + dma_shared,
+           cpu_alone.max(dma_alone));
+}
+
+/// Measures the bandwidth of streaming stores to the framebuffer against the
+/// same stores to ordinary DRAM, then leaves a gradient on the display.
+pub fn bench_framebuffer()
+{
+    let fb = match fb::get() {
+        Ok(fb) => fb,
+        Err(err) => {
+            debug!("Failed to allocate the framebuffer: {err}");
+            return;
+        }
+    };
+    let range = fb.range();
+    let range = range.start .. range.end & !(LINE_SIZE - 1);
+    let free = ram::free();
+    if free.len() < range.len() {
+        debug!("Not enough free RAM for the framebuffer benchmark");
+        return;
+    }
+    let dram = free.start .. free.start + range.len();
+    let mut reference = None;
+    for (name, range) in [("DRAM", dram), ("Framebuffer", range)] {
+        let pattern = pattern(range.start);
+        let Some(measurement) = measure(|iterations| fill_stream(range.clone(), pattern, iterations),
+                                        || fill_stream(range.clone(), pattern, 1))
+        else {
+            continue;
+        };
+        if VERIFY {
+            verify(range.start as *const u64, range.len(), pattern);
+        }
+        let rate = (measurement.iterations as u128 * range.len() as u128 * timer::frequency() as u128
+                    / measurement.ticks as u128)
+                   >> 20;
+        debug!("{name} streaming stores of {}KB: {rate}MB/s", range.len() >> 10);
+        match reference {
+            None => reference = Some(rate),
+            Some(dram) => debug!("{name} is {} DRAM", Ratio(rate, dram)),
+        }
+    }
+    fb.gradient();
+}
+
+/// Measures the sequential read bandwidth of the SD card over [`EMMC_SIZE`]
+/// bytes, both with the data left alone and with every word summed by the CPU
+/// afterwards.
+pub fn bench_emmc()
+{
+    bench_emmc_sized(EMMC_SIZE)
+}
+
+/// Measures the sequential read bandwidth of the SD card over any number of
+/// bytes, both with the data left alone and with every word summed by the CPU
+/// afterwards.
+///
+/// * `size`: Number of bytes to read, which must be a multiple of 1KB so that
+///   it is a whole number of blocks.
+pub fn bench_emmc_sized(size: usize)
+{
+    if size == 0 || size % 1024 != 0 {
+        debug!("SD card read size must be a non-zero multiple of 1KB");
+        return;
+    }
+    let free = ram::free();
+    if free.len() < size {
+        debug!("Not enough free RAM for the SD card benchmark");
+        return;
+    }
+    let mut card = match emmc::Card::new() {
+        Ok(card) => card,
+        Err(err) => {
+            debug!("Skipping the SD card benchmark: {err}");
+            return;
+        }
+    };
+    let buf = unsafe { slice::from_raw_parts_mut(free.start as *mut u32, size / 4) };
+    for touch in [false, true] {
+        watchdog::pet();
+        let start = timer::now();
+        if let Err(err) = card.read(EMMC_LBA, buf) {
+            debug!("SD card benchmark failed: {err}");
+            return;
+        }
+        let sum = if touch { buf.iter().fold(0u32, |sum, word| sum.wrapping_add(*word)) } else { 0 };
+        let ticks = timer::elapsed(start, timer::now()).max(1);
+        let rate = (size as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+        if touch {
+            debug!("SD card read {}KB from block {EMMC_LBA} and summed it in {} ({rate}MB/s, Sum: 0x{sum:08X})",
+                   size >> 10,
+                   Duration(ticks));
+        } else {
+            debug!("SD card read {}KB from block {EMMC_LBA} in {} ({rate}MB/s)",
+                   size >> 10,
+                   Duration(ticks));
+        }
+    }
+}
+
+/// Measures the throughput of the hardware random number generator by
+/// draining it for [`RNG_MSECS`], checking that its output isn't stuck.
+pub fn bench_rng()
+{
+    let variant = rng::variant();
+    rng::enable();
+    let first = match rng::read(variant) {
+        Ok(word) => word,
+        Err(err) => {
+            debug!("{variant:?} RNG benchmark failed: {err}");
+            return;
+        }
+    };
+    let mut words = 0usize;
+    let mut repeats = 0usize;
+    let mut last = first;
+    let start = timer::now();
+    let ticks = loop {
+        match rng::read(variant) {
+            Ok(word) => {
+                repeats += (word == last) as usize;
+                last = word;
+                words += 1;
+            }
+            Err(err) => {
+                debug!("{variant:?} RNG benchmark failed: {err}");
+                return;
+            }
+        }
+        let ticks = timer::elapsed(start, timer::now());
+        if ticks >= timer::frequency() / 1000 * RNG_MSECS {
+            break ticks;
+        }
+    };
+    let rate = words as u128 * 4 * timer::frequency() as u128 / ticks as u128;
+    debug!("{variant:?} RNG produced {words} words in {} ({rate} bytes/s, {repeats} repeated words)",
+           Duration(ticks));
+    if repeats == words {
+        debug!("{variant:?} RNG output is stuck at 0x{first:08X}");
+    }
+}
+
+/// Reports the result of a copy benchmark.
+///
+/// * `name`: Name of the copying agent.
+/// * `ticks`: Duration of the copy in timer ticks.
+///
+/// Returns the bandwidth in megabytes per second.
+fn report_copy(name: &str, ticks: usize) -> u128
+{
+    let rate = (DMA_SIZE as u128 * timer::frequency() as u128 / ticks.max(1) as u128) >> 20;
+    debug!("{name} copied {}MB in {} ({rate}MB/s)",
+           DMA_SIZE >> 20,
+           Duration(ticks));
+    rate
+}
+
+/// Reports the result of an MMIO benchmark.
+///
+/// * `name`: Name of the benchmark.
+/// * `measurement`: Measurement of the benchmark, if any.
+fn report_mmio(name: &str, measurement: Option<Measurement>)
+{
+    let Some(Measurement { iterations, ticks, .. }) = measurement else {
+        return;
+    };
+    let accesses = (iterations * MMIO_BURST) as u128;
+    let centis = timer::nanos(ticks) * 100 / accesses;
+    debug!("Core #{} MMIO {name}: {}ns per access ({accesses} accesses in {})",
+           core_index(),
+           Fixed(centis, 2),
+           Duration(ticks));
+}
+
+/// Measures the write bandwidth to a block of the calling core's share of the
+/// free RAM when mapped in each shareability domain.
+///
+/// Must not be run while any other core is measuring, since every remapping
+/// invalidates the TLBs of all the cores.
+fn bench_shareability()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < BLOCK_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the shareability benchmark");
+        return;
+    }
+    let block = share.start .. share.start + BLOCK_SIZE;
+    let pattern = pattern(block.start);
+    for sh in [Shareability::Inner, Shareability::Outer, Shareability::Non] {
+        cache::clean_invalidate(block.clone());
+        mmu::remap_ram(block.clone(), sh);
+        let Some(Measurement { iterations, ticks, .. }) = measure(|iterations| fill_stream(block.clone(), pattern, iterations),
+                                                              || fill_stream(block.clone(), pattern, 1))
+        else {
+            continue;
+        };
+        let rate = (iterations as u128 * BLOCK_SIZE as u128 * timer::frequency() as u128 / ticks as u128) >> 20;
+        debug!("Core #{core} {sh:?} shareable write bandwidth: {rate}MB/s");
+    }
+    cache::clean_invalidate(block.clone());
+    mmu::remap_ram(block, Shareability::Inner);
+}
+
+/// Measures the write and read bandwidth to a block of the free RAM when
+/// mapped with each memory type, which makes the cost of mapping buffers as
+/// device or non-cacheable memory concrete.
+///
+/// The block is cleaned and invalidated from the data cache before every
+/// remapping, so lines cached through one mapping never satisfy the accesses
+/// made through the next.
+pub fn bench_attributes()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < BLOCK_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the memory attributes benchmark");
+        return;
+    }
+    let block = share.start .. share.start + BLOCK_SIZE;
+    let pattern = pattern(block.start);
+    let mut reference = None;
+    for attrs in [Attributes::Cacheable, Attributes::NonCacheable, Attributes::Device] {
+        cache::clean_invalidate(block.clone());
+        mmu::map(block.clone(), attrs, Shareability::Inner);
+        let rate = |Measurement { iterations, ticks, .. }| {
+            (iterations as u128 * BLOCK_SIZE as u128 * timer::frequency() as u128 / ticks as u128) >> 20
+        };
+        let write = measure(|iterations| fill_stream(block.clone(), pattern, iterations),
+                            || fill_stream(block.clone(), pattern, 1)).map(rate);
+        if VERIFY {
+            verify(block.start as *const u64, BLOCK_SIZE, pattern);
+        }
+        let read = measure(|iterations| load_stream(block.clone(), iterations),
+                           || load_stream(block.clone(), 1)).map(rate);
+        debug!("Core #{core} {attrs:?} memory (MAIR 0x{:02X}): Write: {}MB/s, Read: {}MB/s",
+               mmu::encoding(attrs),
+               Cell(write),
+               Cell(read));
+        match reference {
+            None => reference = Some((write, read)),
+            Some((cached_write, cached_read)) => {
+                debug!("Core #{core} {attrs:?} memory is {} cacheable memory writing and {} reading",
+                       Cell(write.zip(cached_write).map(|(write, cached)| Ratio(write, cached))),
+                       Cell(read.zip(cached_read).map(|(read, cached)| Ratio(read, cached))))
+            }
+        }
+    }
+    cache::clean_invalidate(block.clone());
+    mmu::map_ram(block);
+}
+
+/// Runs the core benchmarks on the calling core first with the caches
+/// disabled and then with them enabled, reporting every result along with the
+/// system control register it was produced with and a table of the speedups,
+/// along with the results with the caches disabled at EL2 through a hypervisor
+/// call if the firmware booted the kernel at EL2.
+///
+/// The uncached results are a baseline for the benefit of the caches.  The
+/// buffers are remapped as normal non-cacheable memory while they are
+/// produced, so that their attributes agree with the disabled caches, and the
+/// uncached measurements touch nothing else but the registers and the stack,
+/// since the other cores keep serving jobs and share the locks, the output
+/// queue, and the rest of the statics through cacheable mappings.
+///
+/// Must not be run while the other cores are accessing memory, since the
+/// whole data cache hierarchy is maintained by set and way.
+pub fn bench_caches()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < CACHES_STREAM_SIZE + BLOCK_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the caches benchmark");
+        return;
+    }
+    // The pointer chain and the buffer take the block right after the stream,
+    // so that all of them can be remapped together.
+    let region = share.start .. share.start + CACHES_STREAM_SIZE + BLOCK_SIZE;
+    let stream = region.start .. region.start + CACHES_STREAM_SIZE;
+    let chain = stream.end .. stream.end + HOT_CHAIN_SIZE;
+    let ptr = chain.end as *mut u8;
+    link(chain.clone());
+    // Cached lines mapped with different attributes lose coherency.
+    cache::clean_invalidate(region.clone());
+    mmu::map(region.clone(), Attributes::NonCacheable, Shareability::Inner);
+    // Nothing can be reported while the caches are disabled, since reporting
+    // takes the UART lock, and nothing can still be queued either, since the
+    // transmission interrupt is masked.
+    uart::settle();
+    // The uncached measurements don't pet the watchdog.
+    watchdog::pet();
+    cache::disable();
+    let off = (mmu::sctlr(), caches_kernels(ptr, stream.clone(), chain.clone(), true));
+    cache::enable();
+    // The output is not drained at EL2 either, since IRQs stay masked there.
+    let mut args = LevelsArgs { buf: ptr,
+                                stream: stream.clone(),
+                                chain: chain.clone(),
+                                results: None };
+    if hyp::available() {
+        uart::settle();
+        watchdog::pet();
+        hyp::call(uncached_kernels, &mut args as *mut LevelsArgs as usize);
+    }
+    let el2 = args.results;
+    mmu::map_ram(region);
+    if hyp::available() {
+        // The EL2 translation regime cached the non-cacheable mapping.
+        hyp::call(mmu::invalidate_el2, 0);
+    }
+    let on = (mmu::sctlr(), caches_kernels(ptr, stream.clone(), chain, false));
+    if VERIFY {
+        verify(ptr.cast(), size_of::<Buffer>(), pattern(ptr as usize));
+        verify(stream.start as *const u64, stream.len(), pattern(stream.start));
+    }
+    for (sctlr, results) in [&off, &on] {
+        for ((name, unit, places, _), result) in CACHES_KERNELS.iter().zip(results) {
+            match result {
+                Ok(result) => debug!("Core #{core} {name}: {}{unit} with {sctlr}", Fixed(*result, *places)),
+                Err(err) => debug!("Core #{core} {name} with {sctlr}: {err}"),
+            }
+        }
+    }
+    report_unmeasured(el2.as_ref(), "with the caches disabled at EL2");
+    let mut uart = UART.lock();
+    writeln!(uart, "Off\tOn\tSpeedup\tEL2 Off\tBenchmark").unwrap();
+    for (idx, (name, unit, places, lower)) in CACHES_KERNELS.iter().enumerate() {
+        let (off, on, el2) = (off.1[idx].ok(), on.1[idx].ok(), el2.and_then(|el2| el2[idx].ok()));
+        let speedup = off.zip(on).map(|(off, on)| {
+                                     let (slow, fast) = if *lower { (on, off) } else { (off, on) };
+                                     Ratio(fast, slow)
+                                 });
+        writeln!(uart,
+                 "{}\t{}\t{}\t{}\t{name} ({unit})",
+                 Cell(off.map(|off| Fixed(off, *places))),
+                 Cell(on.map(|on| Fixed(on, *places))),
+                 Cell(speedup),
+                 Cell(el2.map(|el2| Fixed(el2, *places))))
+        .unwrap();
+    }
+}
+
+/// Runs the core benchmarks on the calling core first at EL2 through a
+/// hypervisor call and then at EL1, and prints a table of both results along
+/// with their ratios, which shows whether anything running at EL1 costs, such
+/// as the stage 2 translation being disabled rather than absent.
+///
+/// Must only be run if the firmware booted the kernel at EL2.
+pub fn bench_levels()
+{
+    let core = core_index();
+    if !hyp::available() {
+        debug!("Core #{core} was booted at EL{}, so nothing can run at EL2", hyp::boot_level());
+        return;
+    }
+    let share = ram::share(core);
+    if share.len() < CACHES_STREAM_SIZE + HOT_CHAIN_SIZE {
+        debug!("Core #{core} does not have enough free RAM for the exception levels benchmark");
+        return;
+    }
+    let stream = share.start .. share.start + CACHES_STREAM_SIZE;
+    let chain = stream.end .. stream.end + HOT_CHAIN_SIZE;
+    link(chain.clone());
+    let mut buf = MaybeUninit::<Buffer>::uninit();
+    let ptr = buf.as_mut_ptr().cast::<u8>();
+    let mut args = LevelsArgs { buf: ptr,
+                                stream: stream.clone(),
+                                chain: chain.clone(),
+                                results: None };
+    // Nothing drains the output at EL2.
+    uart::settle();
+    let level = hyp::call(levels_kernels, &mut args as *mut LevelsArgs as usize);
+    let low = (hyp::current_level(), caches_kernels(ptr, stream.clone(), chain, false));
+    let high = (level, args.results.unwrap());
+    if VERIFY {
+        verify(ptr.cast(), size_of::<Buffer>(), pattern(ptr as usize));
+        verify(stream.start as *const u64, stream.len(), pattern(stream.start));
+    }
+    report_unmeasured(Some(&high.1), "at EL2");
+    report_unmeasured(Some(&low.1), "at EL1");
+    let mut uart = UART.lock();
+    writeln!(uart, "EL{}\tEL{}\tRatio\tBenchmark", high.0, low.0).unwrap();
+    for (idx, (name, unit, places, _)) in CACHES_KERNELS.iter().enumerate() {
+        let (high, low) = (high.1[idx].ok(), low.1[idx].ok());
+        writeln!(uart,
+                 "{}\t{}\t{}\t{name} ({unit})",
+                 Cell(high.map(|high| Fixed(high, *places))),
+                 Cell(low.map(|low| Fixed(low, *places))),
+                 Cell(high.zip(low).map(|(high, low)| Ratio(low, high))))
+        .unwrap();
+    }
+}
+
+/// Runs the core benchmarks at the exception level of the caller, which is
+/// EL2 when called through a hypervisor call.
+///
+/// * `arg`: Address of the arguments, which receive the results.
+///
+/// Returns the exception level that the benchmarks ran at.
+extern "C" fn levels_kernels(arg: usize) -> usize
+{
+    let args = unsafe { &mut *(arg as *mut LevelsArgs) };
+    args.results = Some(caches_kernels(args.buf, args.stream.clone(), args.chain.clone(), false));
+    hyp::current_level()
+}
+
+/// Runs the core benchmarks with the caches disabled at the exception level of
+/// the caller, which is EL2 when called through a hypervisor call, with the
+/// buffers already remapped as normal non-cacheable memory.
+///
+/// * `arg`: Address of the arguments, which receive the results.
+///
+/// Returns the exception level that the benchmarks ran at.
+extern "C" fn uncached_kernels(arg: usize) -> usize
+{
+    let args = unsafe { &mut *(arg as *mut LevelsArgs) };
+    // The EL2 translation regime may still cache the cacheable mapping.
+    mmu::invalidate_el2(0);
+    cache::disable();
+    let results = caches_kernels(args.buf, args.stream.clone(), args.chain.clone(), true);
+    cache::enable();
+    args.results = Some(results);
+    hyp::current_level()
+}
+
+/// Runs the benchmarks compared with the caches disabled and enabled and at EL2
+/// and EL1 without reporting anything.
+///
+/// * `buf`: Benchmark buffer.
+/// * `stream`: DRAM stream, which must be aligned to the cache line size.
+/// * `chain`: Pointer chain, which must already be linked.
+/// * `quiet`: Whether to measure with [`measure_quiet`], which is required
+///   while the caches are disabled.
+///
+/// Returns the results in the order of [`CACHES_KERNELS`], or the reasons why
+/// they could not be measured.
+fn caches_kernels(buf: *mut u8,
+                  stream: Range<usize>,
+                  chain: Range<usize>,
+                  quiet: bool)
+                  -> [Result<u128, Unmeasurable>; CACHES_KERNELS.len()]
+{
+    let freq = if quiet { timer::generic_frequency() } else { timer::frequency() } as u128;
+    let measure = |kernel: &mut dyn FnMut(usize), warm_up: &mut dyn FnMut()| {
+        if quiet {
+            measure_quiet(kernel, warm_up)
+        } else {
+            try_measure(kernel, warm_up)
+        }
+    };
+    let rate = |len: usize| move |Measurement { iterations, ticks, .. }| (iterations as u128 * len as u128 * freq / ticks as u128) >> 20;
+    let buf_pattern = pattern(buf as usize);
+    let stream_pattern = pattern(stream.start);
+    let loads = chain.len() / LINE_SIZE;
+    [measure(&mut |iterations| fill(buf, buf_pattern, iterations),
+             &mut || fill(buf, buf_pattern, WARMUP_PASSES)).map(rate(BUFFER_SIZE)),
+     measure(&mut |iterations| fill_stream(stream.clone(), stream_pattern, iterations),
+             &mut || fill_stream(stream.clone(), stream_pattern, 1)).map(rate(stream.len())),
+     measure(&mut |iterations| load_stream(stream.clone(), iterations),
+             &mut || load_stream(stream.clone(), 1)).map(rate(stream.len())),
+     measure(&mut |iterations| {
+                 chase(chain.start, loads * iterations);
+             },
+             &mut || {
+                 chase(chain.start, loads * WARMUP_PASSES);
+             }).map(|Measurement { iterations, ticks, .. }| {
+                       ticks as u128 * 100_000_000_000 / freq / (loads * iterations) as u128
+                   })]
+}
+
+/// Reports which of the core benchmarks run by [`caches_kernels`] could not be
+/// measured and why.
+///
+/// * `results`: Results in the order of [`CACHES_KERNELS`], if the benchmarks
+///   ran.
+/// * `context`: Description of the conditions that the benchmarks ran under.
+fn report_unmeasured(results: Option<&[Result<u128, Unmeasurable>; CACHES_KERNELS.len()]>, context: &str)
+{
+    let core = core_index();
+    for ((name, ..), result) in CACHES_KERNELS.iter().zip(results.into_iter().flatten()) {
+        if let Err(err) = result {
+            debug!("Core #{core} {name} {context}: {err}");
+        }
+    }
+}
+
+/// Measures the rate at which all the selected cores acquire and release a
+/// single lock that they all fight over, reporting each core's share of the
+/// acquisitions to surface fairness problems along with the bus traffic per
+/// acquisition, then does the same with a plain busy spinning flag for
+/// comparison.
+///
+/// Must be run as a job on all the selected cores simultaneously.
+fn bench_lock_contended()
+{
+    contend("lock", || drop(CONTENDED.lock()));
+    contend("busy spinning flag", || {
+        while SPINNING.swap(true, Ordering::Acquire) {
+            while SPINNING.load(Ordering::Relaxed) {
+                spin_loop()
+            }
+        }
+        SPINNING.store(false, Ordering::Release);
+    });
+}
+
+/// Runs a contended acquisition benchmark on all the selected cores.
+///
+/// Must be run as a job on all the selected cores simultaneously.
+///
+/// * `name`: Name of the contended primitive.
+/// * `acquire`: Acquires and releases the contended primitive.
+fn contend(name: &str, acquire: impl Fn())
+{
+    let core = core_index();
+    let freq = timer::frequency();
+    pmu::select(pmu::BUS_ACCESS);
+    smp::sync();
+    let mut count = 0usize;
+    let events = pmu::events();
+    let start = timer::now();
+    let ticks = loop {
+        acquire();
+        count += 1;
+        let ticks = timer::elapsed(start, timer::now());
+        if ticks >= freq / 1000 * CONTENTION_MSECS {
+            break ticks;
+        }
+    };
+    let events = pmu::events() - events;
+    ACQUISITIONS[core].store(count, Ordering::Relaxed);
+    smp::sync();
+    let mask = smp::mask();
+    let total = ACQUISITIONS.iter()
+                            .enumerate()
+                            .filter(|(core, _)| mask & 1 << core != 0)
+                            .map(|(_, count)| count.load(Ordering::Relaxed))
+                            .sum::<usize>();
+    let rate = count as u128 * freq as u128 / ticks as u128;
+    let share = count as u128 * 10000 / total.max(1) as u128;
+    let traffic = events as u128 * 100 / count.max(1) as u128;
+    debug!("Core #{core} contended {name}: {count} acquisitions in {} ({rate} acquisitions/s, {}% of {total}, {} bus accesses per acquisition)",
+           Duration(ticks),
+           Fixed(share, 2),
+           Fixed(traffic, 2));
+}
+
+/// Samples the latency of individual loads from a pointer chain into the
+/// histogram of the calling core and prints it.
+///
+/// * `state`: State of the caches.
+/// * `start`: Address of the first pointer in the chain.
+fn histogram(state: CacheState, start: usize)
+{
+    let core = core_index();
+    let histogram = &HISTOGRAMS[core];
+    histogram.iter().for_each(|bucket| bucket.store(0, Ordering::Relaxed));
+    let mut overheads = [0; 64];
+    for overhead in overheads.iter_mut() {
+        let start = pmu::cycles();
+        unsafe { asm!("dsb nsh", options (nostack, preserves_flags)) };
+        *overhead = pmu::cycles() - start;
+    }
+    overheads.sort_unstable();
+    let overhead = overheads[overheads.len() / 2];
+    let mut ptr = start;
+    for _ in 0 .. HISTOGRAM_SAMPLES {
+        ptr = chase(ptr, HISTOGRAM_INTERVAL - 1);
+        let start = pmu::cycles();
+        unsafe {
+            asm!(
+                "ldr {ptr}, [{ptr}]",
+                "dsb nsh",
+                ptr = inout (reg) ptr,
+                options (nostack, preserves_flags)
+            );
+        }
+        let cycles = pmu::cycles() - start;
+        let bucket = (usize::BITS - cycles.leading_zeros()) as usize;
+        histogram[bucket.min(HISTOGRAM_BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+    }
+    let counts: [usize; HISTOGRAM_BUCKETS] = array::from_fn(|idx| histogram[idx].load(Ordering::Relaxed));
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    let mut uart = UART.lock();
+    writeln!(uart, "Core #{core} {state:?} latency histogram of {HISTOGRAM_SAMPLES} loads sampled 1 in {HISTOGRAM_INTERVAL}, each including {overhead} cycles of timestamping overhead:").unwrap();
+    for (idx, count) in counts.iter().enumerate() {
+        write!(uart, "{} cycles: {count:>6} ", BucketRange(idx)).unwrap();
+        for _ in 0 .. (count * HISTOGRAM_WIDTH + max - 1) / max {
+            uart.write_char('#').unwrap();
+        }
+        uart.write_char('\n').unwrap();
+    }
+    for percent in [50, 90, 99] {
+        let target = (HISTOGRAM_SAMPLES * percent + 99) / 100;
+        let mut total = 0;
+        let idx = counts.iter()
+                        .position(|count| {
+                            total += count;
+                            total >= target
+                        })
+                        .unwrap_or(HISTOGRAM_BUCKETS - 1);
+        writeln!(uart, "p{percent}: {} cycles", BucketRange(idx)).unwrap();
+    }
+}
+
+impl<T: Display> Display for Cell<T>
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        match &self.0 {
+            Some(val) => write!(fmt, "{val}"),
+            None => write!(fmt, "-"),
+        }
+    }
+}
+
+impl Display for Fixed
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let Self(val, places) = *self;
+        if places == 0 {
+            return write!(fmt, "{val}");
+        }
+        let scale = 10u128.pow(places);
+        write!(fmt, "{}.{:0width$}", val / scale, val % scale, width = places as usize)
+    }
+}
+
+impl Display for Ratio
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let Self(val, baseline) = *self;
+        write!(fmt, "{}x", Fixed(val * 100 / baseline.max(1), 2))
+    }
+}
+
+impl Display for Change
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let Self(val, baseline) = *self;
+        let sign = if val < baseline { '-' } else { '+' };
+        write!(fmt, "{sign}{}%", Fixed(val.abs_diff(baseline) * 10000 / baseline.max(1), 2))
+    }
+}
+
+impl Display for Bytes
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let bytes = self.0;
+        match [(30, "GB"), (20, "MB"), (10, "KB")].into_iter().find(|&(shift, _)| bytes >> shift != 0) {
+            Some((shift, unit)) => write!(fmt, "{}{unit}", Fixed((bytes * 100) >> shift, 2)),
+            None => write!(fmt, "{bytes}B"),
+        }
+    }
+}
+
+impl Display for Unmeasurable
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        match self {
+            Self::SlowTimer(freq) => write!(fmt, "cannot measure the benchmark with a timer frequency of {freq}Hz"),
+            Self::ShortInterval => write!(fmt, "benchmark interval too short to measure"),
+        }
+    }
+}
+
+impl Display for Access
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let Self { instruction, width, order, range } = self;
+        let attrs = match mmu::attributes_at(range.start) {
+            Some(encoding) => mmu::attribute_name(encoding),
+            None => "unmapped",
+        };
+        write!(fmt,
+               "{instruction}, {}-bit, {order}, {} at 0x{:X}, {attrs}",
+               width * 8,
+               Bytes(range.len() as u128),
+               range.start)
+    }
+}
+
+impl Display for BucketRange
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let idx = self.0;
+        let low = if idx == 0 { 0 } else { 1usize << (idx - 1) };
+        if idx == HISTOGRAM_BUCKETS - 1 {
+            write!(fmt, "{low:>6}+      ")
+        } else {
+            write!(fmt, "{low:>6}-{:<6}", (1usize << idx) - 1)
+        }
+    }
+}
+
+impl Progress
+{
+    /// Creates and initializes a new progress report.
+    ///
+    /// * `desc`: Description of the sequence of benchmarks.
+    /// * `total`: Total number of benchmarks.
+    ///
+    /// Returns the newly created progress report.
+    fn new(desc: &'static str, total: usize) -> Self
+    {
+        Self { desc,
+               started: 0,
+               total,
+               start: timer::now(),
+               last: None }
+    }
+
+    /// Records the start of the next benchmark, reporting it unless progress
+    /// was already reported less than [`PROGRESS_MSECS`] ago.
+    ///
+    /// Must not be called while a benchmark is being timed, since reporting
+    /// takes the UART lock and writes to it.
+    fn advance(&mut self)
+    {
+        self.started += 1;
+        let now = timer::now();
+        if let Some(last) = self.last {
+            if timer::elapsed(last, now) < timer::frequency() / 1000 * PROGRESS_MSECS {
+                return;
+            }
+        }
+        self.last = Some(now);
+        debug!("Core #{} {} progress: trial {}/{}, {} elapsed",
+               core_index(),
+               self.desc,
+               self.started,
+               self.total,
+               Duration(timer::elapsed(self.start, now)));
+    }
+}
+
+/// Calibrates and measures a benchmark kernel.
+///
+/// * `kernel`: Benchmark kernel taking the number of iterations to run.
+/// * `warm_up`: Warm-up to perform between the calibration and the
+///   measurement.
+///
+/// Returns the measurement, or `None` if the kernel could not be measured, in
+/// which case the reason has already been reported.
+pub fn measure(kernel: impl FnMut(usize), warm_up: impl FnMut()) -> Option<Measurement>
+{
+    try_measure(kernel, warm_up).inspect_err(|err| debug!("Core #{} {err}", core_index()))
+                                .ok()
+}
+
+/// Calibrates and measures a benchmark kernel like [`measure`] without
+/// reporting why it could not be measured.
+///
+/// * `kernel`: Benchmark kernel taking the number of iterations to run.
+/// * `warm_up`: Warm-up to perform between the calibration and the
+///   measurement.
+///
+/// Returns the measurement, or the reason why the kernel could not be
+/// measured.
+fn try_measure(mut kernel: impl FnMut(usize), mut warm_up: impl FnMut()) -> Result<Measurement, Unmeasurable>
+{
+    // Every measurement takes about a second, so a suite easily outlasts the
+    // watchdog timeout.
+    watchdog::pet();
+    // Output still being sent would interrupt the measurement.
+    uart::settle();
+    measure_with(timer::frequency(), timer::now, timer::elapsed, &mut kernel, &mut warm_up)
+}
+
+/// Calibrates and measures a benchmark kernel like [`measure`] on the generic
+/// timer, without petting the watchdog, waiting for the output to drain, or
+/// reporting anything, so that nothing but the registers, the stack, and the
+/// memory accessed by the kernel is touched.
+///
+/// This is how the caches benchmark measures while the caches are disabled,
+/// since the watchdog timeout, the timing source, the output queue, and the
+/// UART lock are shared through cacheable mappings, and the exclusive
+/// accesses of the locks may never succeed.  The overhead of reading the timer
+/// is not subtracted for the same reason, which makes no difference over the
+/// duration of a measurement.
+///
+/// * `kernel`: Benchmark kernel taking the number of iterations to run.
+/// * `warm_up`: Warm-up to perform between the calibration and the
+///   measurement.
+///
+/// Returns the measurement in generic timer ticks, or the reason why the
+/// kernel could not be measured, to be reported once the caches are enabled
+/// again.
+fn measure_quiet(mut kernel: impl FnMut(usize), mut warm_up: impl FnMut()) -> Result<Measurement, Unmeasurable>
+{
+    measure_with(timer::generic_frequency(),
+                 timer::generic_now,
+                 |start, end| end - start,
+                 &mut kernel,
+                 &mut warm_up)
+}
+
+/// Calibrates and measures a benchmark kernel with a timing source.
+///
+/// * `freq`: Frequency of the timing source in hertz.
+/// * `now`: Reads the count of the timing source.
+/// * `elapsed`: Computes the ticks elapsed between two counts.
+/// * `kernel`: Benchmark kernel taking the number of iterations to run.
+/// * `warm_up`: Warm-up to perform between the calibration and the
+///   measurement.
+///
+/// Returns the measurement, or the reason why the kernel could not be
+/// measured.
+fn measure_with(freq: usize,
+                now: fn() -> usize,
+                elapsed: fn(usize, usize) -> usize,
+                kernel: &mut dyn FnMut(usize),
+                warm_up: &mut dyn FnMut())
+                -> Result<Measurement, Unmeasurable>
+{
+    if freq < 1000 {
+        return Err(Unmeasurable::SlowTimer(freq));
+    }
+    let mut iterations = 1;
+    let ticks = loop {
+        let (ticks, _) = time_with(now, elapsed, kernel, iterations);
+        if ticks >= freq / 1000 * CALIBRATION_MSECS {
+            break ticks;
+        }
+        if iterations >= MAX_ITERATIONS {
+            return Err(Unmeasurable::ShortInterval);
+        }
+        iterations *= 2;
+    };
+    let target = freq / 1000 * TARGET_MSECS;
+    let iterations = (iterations as u128 * target as u128 / ticks as u128).max(1) as usize;
+    warm_up();
+    let (ticks, cycles) = time_with(now, elapsed, kernel, iterations);
+    if ticks == 0 {
+        return Err(Unmeasurable::ShortInterval);
+    }
+    Ok(Measurement { iterations, ticks, cycles })
+}
+
+/// Times a number of iterations of a benchmark kernel with both the timer
+/// and the cycle counter.
+///
+/// The counters are read back to back on either side of the kernel, so both
+/// durations cover the same run.
+///
+/// * `kernel`: Benchmark kernel taking the number of iterations to run.
+/// * `iterations`: Number of iterations to run.
+///
+/// Returns the elapsed timer ticks and CPU cycles.
+fn time(kernel: &mut impl FnMut(usize), iterations: usize) -> (usize, usize)
+{
+    time_with(timer::now, timer::elapsed, kernel, iterations)
+}
+
+/// Times a number of iterations of a benchmark kernel like [`time`] with a
+/// timing source.
+///
+/// * `now`: Reads the count of the timing source.
+/// * `elapsed`: Computes the ticks elapsed between two counts.
+/// * `kernel`: Benchmark kernel taking the number of iterations to run.
+/// * `iterations`: Number of iterations to run.
+///
+/// Returns the elapsed timer ticks and CPU cycles.
+fn time_with(now: fn() -> usize,
+             elapsed: fn(usize, usize) -> usize,
+             kernel: &mut dyn FnMut(usize),
+             iterations: usize)
+             -> (usize, usize)
+{
+    let start = now();
+    let start_cycles = pmu::cycles();
+    kernel(iterations);
+    let end_cycles = pmu::cycles();
+    let end = now();
+    (elapsed(start, end), end_cycles - start_cycles)
+}
+
+/// Fills the buffer with the pattern repeatedly.
+///
+/// * `buf`: Buffer to fill.
+/// * `pattern`: Pattern to fill the buffer with, laid out as described for
+///   [`fill_value`].
+/// * `iterations`: Number of times to fill the buffer.
+fn fill(buf: *mut u8, pattern: u64, iterations: usize)
+{
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                "add {eaddr}, {addr}, #0x1000",
+                "ins {data0}.d[0], {lo}",
+                "ins {data0}.d[1], {hi}",
+                "dup {step}.2d, {inc}",
+                "add {data1}.2d, {data0}.2d, {step}.2d",
+                "add {step}.2d, {step}.2d, {step}.2d",
+                "0:",
+                "cmp {addr}, {eaddr}",
+                "beq 0f",
+                "stp {data0:q}, {data1:q}, [{addr}], #32",
+                "add {data0}.2d, {data0}.2d, {step}.2d",
+                "add {data1}.2d, {data1}.2d, {step}.2d",
+                "b 0b",
+                "0:",
+                addr = inout (reg) buf => _,
+                eaddr = out (reg) _,
+                lo = in (reg) pattern,
+                hi = in (reg) !pattern,
+                inc = in (reg) PATTERN_STEP,
+                data0 = out (vreg) _,
+                data1 = out (vreg) _,
+                step = out (vreg) _
+            );
+        }
+    }
+}
+
+/// Writes whole cache lines of a buffer in the order of a table of line
+/// indices repeatedly.
+///
+/// * `buf`: Base address of the buffer, which must be aligned to the cache
+///   line size.
+/// * `table`: Line indices, whose length must be a multiple of
+///   [`RANDOM_UNROLL`].
+/// * `pattern`: Pattern to fill the buffer with, laid out as described for
+///   [`fill_value`].
+/// * `iterations`: Number of passes over the table.
+fn write_indexed(buf: usize, table: &[u32], pattern: u64, iterations: usize)
+{
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                "ins {base0}.d[0], {lo}",
+                "ins {base0}.d[1], {hi}",
+                "dup {step}.2d, {inc}",
+                "add {base1}.2d, {base0}.2d, {step}.2d",
+                "add {base2}.2d, {base1}.2d, {step}.2d",
+                "add {base3}.2d, {base2}.2d, {step}.2d",
+                "0:",
+                ".rept {unroll}",
+                "ldr {idx:w}, [{entry}], #4",
+                "add {addr}, {buf}, {idx}, lsl #{shift}",
+                "mul {idx}, {idx}, {line}",
+                "dup {step}.2d, {idx}",
+                "add {data0}.2d, {base0}.2d, {step}.2d",
+                "add {data1}.2d, {base1}.2d, {step}.2d",
+                "add {data2}.2d, {base2}.2d, {step}.2d",
+                "add {data3}.2d, {base3}.2d, {step}.2d",
+                "stp {data0:q}, {data1:q}, [{addr}]",
+                "stp {data2:q}, {data3:q}, [{addr}, #32]",
+                ".endr",
+                "cmp {entry}, {end}",
+                "bne 0b",
+                entry = inout (reg) table.as_ptr() => _,
+                end = in (reg) table.as_ptr_range().end,
+                buf = in (reg) buf,
+                idx = out (reg) _,
+                addr = out (reg) _,
+                lo = in (reg) pattern,
+                hi = in (reg) !pattern,
+                inc = in (reg) PATTERN_STEP,
+                line = in (reg) PATTERN_STEP.wrapping_mul((LINE_SIZE / 16) as u64),
+                base0 = out (vreg) _,
+                base1 = out (vreg) _,
+                base2 = out (vreg) _,
+                base3 = out (vreg) _,
+                data0 = out (vreg) _,
+                data1 = out (vreg) _,
+                data2 = out (vreg) _,
+                data3 = out (vreg) _,
+                step = out (vreg) _,
+                unroll = const RANDOM_UNROLL,
+                shift = const LINE_SIZE.trailing_zeros(),
+                options (nostack)
+            );
+        }
+    }
+}
+
+/// Writes every cache line of the buffer of the random write benchmark once
+/// per pass in the order generated by hashing a counter kept in a register.
+///
+/// The hash is two rounds of a multiplication by [`RANDOM_MULTIPLIER`] and an
+/// exclusive or with the product shifted right by [`RANDOM_FOLD`] bits, all
+/// modulo [`RANDOM_LINES`].  Each step is invertible modulo a power of two,
+/// so the hash permutes the line indices, and unlike the low bits of a linear
+/// congruential generator modulo a power of two, which cycle with short
+/// periods, every bit of the result mixes all the bits of the counter.
+///
+/// * `buf`: Base address of the buffer, which must be aligned to the cache
+///   line size and [`RANDOM_SIZE`] long.
+/// * `pattern`: Pattern to fill the buffer with, laid out as described for
+///   [`fill_value`].
+/// * `iterations`: Number of passes over the buffer.
+fn write_generated(buf: usize, pattern: u64, iterations: usize)
+{
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                "ins {base0}.d[0], {lo}",
+                "ins {base0}.d[1], {hi}",
+                "dup {step}.2d, {inc}",
+                "add {base1}.2d, {base0}.2d, {step}.2d",
+                "add {base2}.2d, {base1}.2d, {step}.2d",
+                "add {base3}.2d, {base2}.2d, {step}.2d",
+                "0:",
+                ".rept {unroll}",
+                "add {counter}, {counter}, #1",
+                "mul {idx}, {counter}, {mul}",
+                "and {idx}, {idx}, {mask}",
+                "eor {idx}, {idx}, {idx}, lsr #{fold}",
+                "mul {idx}, {idx}, {mul}",
+                "and {idx}, {idx}, {mask}",
+                "eor {idx}, {idx}, {idx}, lsr #{fold}",
+                "add {addr}, {buf}, {idx}, lsl #{shift}",
+                "mul {idx}, {idx}, {line}",
+                "dup {step}.2d, {idx}",
+                "add {data0}.2d, {base0}.2d, {step}.2d",
+                "add {data1}.2d, {base1}.2d, {step}.2d",
+                "add {data2}.2d, {base2}.2d, {step}.2d",
+                "add {data3}.2d, {base3}.2d, {step}.2d",
+                "stp {data0:q}, {data1:q}, [{addr}]",
+                "stp {data2:q}, {data3:q}, [{addr}, #32]",
+                ".endr",
+                "subs {count}, {count}, #1",
+                "bne 0b",
+                count = inout (reg) RANDOM_LINES / RANDOM_UNROLL => _,
+                counter = inout (reg) 0u64 => _,
+                mul = in (reg) RANDOM_MULTIPLIER,
+                mask = in (reg) RANDOM_LINES - 1,
+                buf = in (reg) buf,
+                idx = out (reg) _,
+                addr = out (reg) _,
+                lo = in (reg) pattern,
+                hi = in (reg) !pattern,
+                inc = in (reg) PATTERN_STEP,
+                line = in (reg) PATTERN_STEP.wrapping_mul((LINE_SIZE / 16) as u64),
+                base0 = out (vreg) _,
+                base1 = out (vreg) _,
+                base2 = out (vreg) _,
+                base3 = out (vreg) _,
+                data0 = out (vreg) _,
+                data1 = out (vreg) _,
+                data2 = out (vreg) _,
+                data3 = out (vreg) _,
+                step = out (vreg) _,
+                unroll = const RANDOM_UNROLL,
+                fold = const RANDOM_FOLD,
+                shift = const LINE_SIZE.trailing_zeros(),
+                options (nostack)
+            );
+        }
+    }
+}
+
+/// Fills a memory range with the pattern repeatedly.
+///
+/// * `range`: Memory range to fill, which must not be empty, must be a
+///   multiple of 32 bytes long, and must be aligned to 32 bytes unless the
+///   alignment check is disabled.
+/// * `pattern`: Pattern to fill the range with, laid out as described for
+///   [`fill_value`].
+/// * `iterations`: Number of times to fill the range.
+fn fill_stream(range: Range<usize>, pattern: u64, iterations: usize)
+{
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                "ins {data0}.d[0], {lo}",
+                "ins {data0}.d[1], {hi}",
+                "dup {step}.2d, {inc}",
+                "add {data1}.2d, {data0}.2d, {step}.2d",
+                "add {step}.2d, {step}.2d, {step}.2d",
+                "0:",
+                "stp {data0:q}, {data1:q}, [{addr}], #32",
+                "add {data0}.2d, {data0}.2d, {step}.2d",
+                "add {data1}.2d, {data1}.2d, {step}.2d",
+                "cmp {addr}, {eaddr}",
+                "bne 0b",
+                addr = inout (reg) range.start => _,
+                eaddr = in (reg) range.end,
+                lo = in (reg) pattern,
+                hi = in (reg) !pattern,
+                inc = in (reg) PATTERN_STEP,
+                data0 = out (vreg) _,
+                data1 = out (vreg) _,
+                step = out (vreg) _
+            );
+        }
+    }
+}
+
+/// Copies memory with 32 byte vector load and store pairs.
+///
+/// * `dst`: Destination memory range, which must not be empty, must be a
+///   multiple of 32 bytes long, and must be aligned to 32 bytes.
+/// * `src`: Source address, which must be aligned to 32 bytes.
+fn copy_stream(dst: Range<usize>, src: usize)
+{
+    unsafe {
+        asm!(
+            "0:",
+            "ldp {lo:q}, {hi:q}, [{src}], #32",
+            "stp {lo:q}, {hi:q}, [{dst}], #32",
+            "cmp {dst}, {edst}",
+            "bne 0b",
+            dst = inout (reg) dst.start => _,
+            edst = in (reg) dst.end,
+            src = inout (reg) src => _,
+            lo = out (vreg) _,
+            hi = out (vreg) _,
+            options (nostack)
+        );
+    }
+}
+
+/// Reads a memory range repeatedly with 32 byte load pairs.
+///
+/// * `range`: Memory range to read, which must not be empty, must be a
+///   multiple of 32 bytes long, and must be aligned to 32 bytes.
+/// * `iterations`: Number of times to read the range.
+fn load_stream(range: Range<usize>, iterations: usize)
+{
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                "0:",
+                "ldp {lo:q}, {hi:q}, [{addr}], #32",
+                "cmp {addr}, {eaddr}",
+                "bne 0b",
+                addr = inout (reg) range.start => _,
+                eaddr = in (reg) range.end,
+                lo = out (vreg) _,
+                hi = out (vreg) _,
+                options (nostack, readonly)
+            );
+        }
+    }
+}
+
+/// Reads one double-word from every page of a memory range repeatedly, moving
+/// one cache line further into the page from one page to the next.
+///
+/// * `range`: Memory range to read, which must be aligned to [`PAGE_SIZE`].
+/// * `iterations`: Number of times to read the range.
+fn load_strided(range: Range<usize>, iterations: usize)
+{
+    for _ in 0 .. iterations {
+        for (idx, page) in range.clone().step_by(PAGE_SIZE).enumerate() {
+            let addr = page + idx % (PAGE_SIZE / LINE_SIZE) * LINE_SIZE;
+            unsafe { (addr as *const u64).read_volatile() };
+        }
+    }
+}
+
+/// Reads a memory range repeatedly with 32 byte load pairs, prefetching every
+/// cache line [`PREFETCH_DISTANCE`] bytes ahead of the loads for a one-time
+/// access.
+///
+/// * `range`: Memory range to read, which must not be empty, must be a
+///   multiple of the cache line size long, and must be aligned to the cache
+///   line size.
+/// * `iterations`: Number of times to read the range.
+fn load_stream_prefetched(range: Range<usize>, iterations: usize)
+{
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                "0:",
+                "prfm pldl1strm, [{addr}, #{distance}]",
+                ".rept {pairs}",
+                "ldp {lo:q}, {hi:q}, [{addr}], #32",
+                ".endr",
+                "cmp {addr}, {eaddr}",
+                "bne 0b",
+                addr = inout (reg) range.start => _,
+                eaddr = in (reg) range.end,
+                lo = out (vreg) _,
+                hi = out (vreg) _,
+                distance = const PREFETCH_DISTANCE,
+                pairs = const LINE_SIZE / 32,
+                options (nostack, readonly)
+            );
+        }
+    }
+}
+
+/// Increments every double-word of a memory range in place repeatedly, with
+/// the additions done in vector registers.
+///
+/// * `range`: Memory range to increment, which must not be empty, must be a
+///   multiple of 32 bytes long, and must be aligned to 32 bytes.
+/// * `iterations`: Number of times to increment the range.
+fn increment_stream(range: Range<usize>, iterations: usize)
+{
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                "dup {one}.2d, {inc}",
+                "0:",
+                "ldp {lo:q}, {hi:q}, [{addr}]",
+                "add {lo}.2d, {lo}.2d, {one}.2d",
+                "add {hi}.2d, {hi}.2d, {one}.2d",
+                "stp {lo:q}, {hi:q}, [{addr}], #32",
+                "cmp {addr}, {eaddr}",
+                "bne 0b",
+                addr = inout (reg) range.start => _,
+                eaddr = in (reg) range.end,
+                inc = in (reg) 1u64,
+                one = out (vreg) _,
+                lo = out (vreg) _,
+                hi = out (vreg) _,
+                options (nostack)
+            );
+        }
+    }
+}
+
+/// Fills the buffer with the pattern repeatedly with SVE stores of whole
+/// vectors, predicated so that vector lengths that don't divide the buffer
+/// size never store past its end.
+///
+/// Must only be called once SVE is enabled, and is never inlined so that its
+/// SVE instructions stay out of the paths taken on cores without SVE.  The
+/// predicate register that it clobbers can't be declared to the compiler, which
+/// never uses SVE registers itself.
+///
+/// * `buf`: Buffer to fill.
+/// * `pattern`: Pattern to fill the buffer with, laid out as described for
+///   [`fill_value`].
+/// * `iterations`: Number of times to fill the buffer.
+#[inline(never)]
+fn fill_sve(buf: *mut u8, pattern: u64, iterations: usize)
+{
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                ".arch_extension sve",
+                "mov {idx}, xzr",
+                "dup z0.d, {lo}",
+                "dup z1.d, {hi}",
+                "zip1 z0.d, z0.d, z1.d",
+                "index z1.d, #0, {inc}",
+                "zip1 z1.d, z1.d, z1.d",
+                "add z0.d, z0.d, z1.d",
+                "cntb {step}",
+                "lsr {step}, {step}, #4",
+                "mul {step}, {step}, {inc}",
+                "dup z1.d, {step}",
+                "whilelo p0.b, {idx}, {len}",
+                "0:",
+                "st1b {{z0.b}}, p0, [{addr}, {idx}]",
+                "add z0.d, z0.d, z1.d",
+                "incb {idx}",
+                "whilelo p0.b, {idx}, {len}",
+                "b.first 0b",
+                addr = in (reg) buf,
+                len = in (reg) BUFFER_SIZE,
+                idx = out (reg) _,
+                lo = in (reg) pattern,
+                hi = in (reg) !pattern,
+                inc = in (reg) PATTERN_STEP,
+                step = out (reg) _,
+                out ("v0") _,
+                out ("v1") _,
+                options (nostack)
+            );
+        }
+    }
+}
+
+/// Generates a kernel that fills the first [`STORE_SPAN`] bytes of the buffer
+/// with the pattern repeatedly using a single vector store form, repeated to
+/// store [`STORE_UNROLL`] bytes per loop iteration.
+///
+/// The store takes the address from `{addr}`, may use `{tmp}`, which starts
+/// at zero, as a scratch register, and stores `$width` bytes from `v0` onwards,
+/// which hold the pattern for consecutive 16 byte chunks and are advanced past
+/// the stored bytes after every store.  The optional fixup runs once per loop
+/// iteration after the repeated stores, and must leave `{addr}` pointing past
+/// them.
+macro_rules! fill_form {
+    ($name:ident, $store:literal, $width:literal) => {
+        fill_form!($name, $store, $width, "");
+    };
+    ($name:ident, $store:literal, $width:literal, $fixup:literal) => {
+        /// Fills the buffer with the pattern repeatedly with the store form
+        /// in the name of the function.
+        ///
+        /// * `buf`: Buffer to fill.
+        /// * `pattern`: Pattern to fill the buffer with, laid out as described
+        ///   for [`fill_value`].
+        /// * `iterations`: Number of times to fill the buffer.
+        fn $name(buf: *mut u8, pattern: u64, iterations: usize)
+        {
+            for _ in 0 .. iterations {
+                unsafe {
+                    asm!(
+                        "add {eaddr}, {addr}, {len}",
+                        "ins v0.d[0], {lo}",
+                        "ins v0.d[1], {hi}",
+                        "dup v4.2d, {inc}",
+                        "add v1.2d, v0.2d, v4.2d",
+                        "add v2.2d, v1.2d, v4.2d",
+                        "add v3.2d, v2.2d, v4.2d",
+                        "dup v4.2d, {step}",
+                        "mov {tmp}, xzr",
+                        "0:",
+                        ".rept {count}",
+                        $store,
+                        "add v0.2d, v0.2d, v4.2d",
+                        ".if {regs} > 1",
+                        "add v1.2d, v1.2d, v4.2d",
+                        ".endif",
+                        ".if {regs} > 2",
+                        "add v2.2d, v2.2d, v4.2d",
+                        ".endif",
+                        ".if {regs} > 3",
+                        "add v3.2d, v3.2d, v4.2d",
+                        ".endif",
+                        ".endr",
+                        $fixup,
+                        "cmp {addr}, {eaddr}",
+                        "bne 0b",
+                        addr = inout (reg) buf => _,
+                        eaddr = out (reg) _,
+                        tmp = out (reg) _,
+                        len = in (reg) STORE_SPAN,
+                        lo = in (reg) pattern,
+                        hi = in (reg) !pattern,
+                        inc = in (reg) PATTERN_STEP,
+                        step = in (reg) PATTERN_STEP.wrapping_mul($width / 16),
+                        count = const STORE_UNROLL / $width,
+                        regs = const $width / 16,
+                        out ("v0") _,
+                        out ("v1") _,
+                        out ("v2") _,
+                        out ("v3") _,
+                        out ("v4") _
+                    );
+                }
+            }
+        }
+    };
+}
+
+fill_form!(fill_str, "str q0, [{addr}], #16", 16);
+fill_form!(fill_stp, "stp q0, q1, [{addr}], #32", 32);
+fill_form!(fill_st1x1, "st1 {{v0.16b}}, [{addr}], #16", 16);
+fill_form!(fill_st1x2, "st1 {{v0.16b, v1.16b}}, [{addr}], #32", 32);
+fill_form!(fill_st1x3, "st1 {{v0.16b, v1.16b, v2.16b}}, [{addr}], #48", 48);
+fill_form!(fill_st1x4, "st1 {{v0.16b, v1.16b, v2.16b, v3.16b}}, [{addr}], #64", 64);
+fill_form!(fill_str_offset,
+           "str q0, [{addr}, {tmp}]\nadd {tmp}, {tmp}, #16",
+           16,
+           "add {addr}, {addr}, {tmp}\nmov {tmp}, xzr");
+fill_form!(fill_stp_add, "stp q0, q1, [{addr}]\nadd {addr}, {addr}, #32", 32);
+
+/// Generates a kernel that zeroes a range repeatedly with a store form.
+///
+/// The store form must zero the block at the address and advance it past the
+/// block, and is given four zeroed vector registers, `v0` through `v3`.
+macro_rules! zero_form {
+    ($name:ident, $store:literal) => {
+        /// Zeroes the range repeatedly with the store form in the name of the
+        /// function, waiting for the stores to complete after every pass.
+        ///
+        /// * `range`: Range to zero, whose length must be a multiple of the
+        ///   width of the store form.
+        /// * `iterations`: Number of times to zero the range.
+        fn $name(range: Range<usize>, iterations: usize)
+        {
+            for _ in 0 .. iterations {
+                unsafe {
+                    asm!(
+                        "movi v0.16b, #0",
+                        "movi v1.16b, #0",
+                        "movi v2.16b, #0",
+                        "movi v3.16b, #0",
+                        "0:",
+                        $store,
+                        "cmp {addr}, {eaddr}",
+                        "bne 0b",
+                        "dsb sy",
+                        addr = inout (reg) range.start => _,
+                        eaddr = in (reg) range.end,
+                        out ("v0") _,
+                        out ("v1") _,
+                        out ("v2") _,
+                        out ("v3") _,
+                        options (nostack)
+                    );
+                }
+            }
+        }
+    };
+}
+
+zero_form!(zero_bytes, "strb wzr, [{addr}], #1");
+zero_form!(zero_stp_x, "stp xzr, xzr, [{addr}], #16");
+zero_form!(zero_stp_q, "stp q0, q1, [{addr}], #32");
+zero_form!(zero_st1x4, "st1 {{v0.16b, v1.16b, v2.16b, v3.16b}}, [{addr}], #64");
+
+/// Zeroes the range repeatedly with `dc zva`, waiting for the stores to
+/// complete after every pass.
+///
+/// Must only be called if [`zva_block`] returns a block size.
+///
+/// * `range`: Range to zero, which must be aligned to the block size.
+/// * `iterations`: Number of times to zero the range.
+fn zero_zva(range: Range<usize>, iterations: usize)
+{
+    let block = zva_block().expect("DC ZVA is prohibited");
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                "0:",
+                "dc zva, {addr}",
+                "add {addr}, {addr}, {block}",
+                "cmp {addr}, {eaddr}",
+                "bne 0b",
+                "dsb sy",
+                addr = inout (reg) range.start => _,
+                eaddr = in (reg) range.end,
+                block = in (reg) block,
+                options (nostack)
+            );
+        }
+    }
+}
+
+/// Generates a throughput and a latency kernel for a bit manipulation
+/// instruction that takes a register to operate on followed by any other
+/// operands.
+///
+/// The results are left in the registers, which the compiler cannot eliminate
+/// since the inline assembly is never pure.
+macro_rules! bits_form {
+    ($throughput:ident, $latency:ident, $op:literal, $operands:literal) => {
+        /// Runs [`BITS_CHAINS`] independent chains of the instruction in the
+        /// name of the function.
+        ///
+        /// * `iterations`: Number of loop iterations, each running
+        ///   [`BITS_OPS`] instructions.
+        fn $throughput(iterations: usize)
+        {
+            unsafe {
+                asm!(
+                    "0:",
+                    ".rept {count}",
+                    concat!($op, " x9, x9", $operands),
+                    concat!($op, " x10, x10", $operands),
+                    concat!($op, " x11, x11", $operands),
+                    concat!($op, " x12, x12", $operands),
+                    concat!($op, " x13, x13", $operands),
+                    concat!($op, " x14, x14", $operands),
+                    concat!($op, " x15, x15", $operands),
+                    concat!($op, " x16, x16", $operands),
+                    ".endr",
+                    "subs {iterations}, {iterations}, #1",
+                    "bne 0b",
+                    iterations = inout (reg) iterations.max(1) => _,
+                    count = const BITS_OPS / BITS_CHAINS,
+                    inout ("x9") BITS_SEED => _,
+                    inout ("x10") BITS_SEED => _,
+                    inout ("x11") BITS_SEED => _,
+                    inout ("x12") BITS_SEED => _,
+                    inout ("x13") BITS_SEED => _,
+                    inout ("x14") BITS_SEED => _,
+                    inout ("x15") BITS_SEED => _,
+                    inout ("x16") BITS_SEED => _,
+                    in ("x17") !BITS_SEED,
+                    options (nomem, nostack)
+                );
+            }
+        }
+
+        /// Runs a single dependent chain of the instruction in the name of
+        /// the function.
+        ///
+        /// * `iterations`: Number of loop iterations, each running
+        ///   [`BITS_OPS`] instructions.
+        fn $latency(iterations: usize)
+        {
+            unsafe {
+                asm!(
+                    "0:",
+                    ".rept {count}",
+                    concat!($op, " x9, x9", $operands),
+                    ".endr",
+                    "subs {iterations}, {iterations}, #1",
+                    "bne 0b",
+                    iterations = inout (reg) iterations.max(1) => _,
+                    count = const BITS_OPS,
+                    inout ("x9") BITS_SEED => _,
+                    in ("x17") !BITS_SEED,
+                    options (nomem, nostack)
+                );
+            }
+        }
+    };
+}
+
+bits_form!(clz_throughput, clz_latency, "clz", "");
+bits_form!(rbit_throughput, rbit_latency, "rbit", "");
+bits_form!(rev_throughput, rev_latency, "rev", "");
+bits_form!(rev16_throughput, rev16_latency, "rev16", "");
+bits_form!(extr_throughput, extr_latency, "extr", ", x17, #13");
+
+/// Generates a throughput and a latency kernel for a multiplication
+/// instruction.
+///
+/// The multiplier changes after every loop iteration but always stays odd and
+/// between 2^62 and 2^63, so that neither the low half products of the chain
+/// nor, with the `orr` fixup that sets bit 62, the high half products ever
+/// decay to zero.
+macro_rules! mul_form {
+    ($throughput:ident, $latency:ident, $op:literal, $sources:literal, $chain:literal) => {
+        /// Runs [`BITS_CHAINS`] independent multiplications at a time with the
+        /// instruction in the name of the function.
+        ///
+        /// * `iterations`: Number of loop iterations, each running
+        ///   [`MUL_OPS`] multiplications.
+        /// * `seed`: Initial multiplier, which must be odd and lie between
+        ///   2^62 and 2^63.
+        fn $throughput(iterations: usize, seed: u64)
+        {
+            unsafe {
+                asm!(
+                    "0:",
+                    ".rept {count}",
+                    concat!($op, " x9, ", $sources),
+                    concat!($op, " x10, ", $sources),
+                    concat!($op, " x11, ", $sources),
+                    concat!($op, " x12, ", $sources),
+                    concat!($op, " x13, ", $sources),
+                    concat!($op, " x14, ", $sources),
+                    concat!($op, " x15, ", $sources),
+                    concat!($op, " x16, ", $sources),
+                    ".endr",
+                    "add {m}, {m}, {step}",
+                    "orr {m}, {m}, #0x4000000000000000",
+                    "and {m}, {m}, #0x7FFFFFFFFFFFFFFF",
+                    "subs {iterations}, {iterations}, #1",
+                    "bne 0b",
+                    iterations = inout (reg) iterations.max(1) => _,
+                    count = const MUL_OPS / BITS_CHAINS,
+                    m = inout (reg) seed => _,
+                    a = in (reg) !seed,
+                    step = in (reg) MUL_STEP,
+                    out ("x9") _,
+                    out ("x10") _,
+                    out ("x11") _,
+                    out ("x12") _,
+                    out ("x13") _,
+                    out ("x14") _,
+                    out ("x15") _,
+                    out ("x16") _,
+                    options (nomem, nostack)
+                );
+            }
+        }
+
+        /// Runs a single dependent chain of multiplications with the
+        /// instruction in the name of the function.
+        ///
+        /// * `iterations`: Number of loop iterations, each running
+        ///   [`MUL_OPS`] multiplications.
+        /// * `seed`: Odd initial multiplier, which also starts the chain and
+        ///   is moved between 2^62 and 2^63.
+        fn $latency(iterations: usize, seed: u64)
+        {
+            unsafe {
+                asm!(
+                    "0:",
+                    ".rept {count}",
+                    $chain,
+                    ".endr",
+                    "add {m}, {m}, {step}",
+                    "orr {m}, {m}, #0x4000000000000000",
+                    "and {m}, {m}, #0x7FFFFFFFFFFFFFFF",
+                    "subs {iterations}, {iterations}, #1",
+                    "bne 0b",
+                    iterations = inout (reg) iterations.max(1) => _,
+                    count = const MUL_OPS,
+                    m = inout (reg) seed & !(1 << 63) | 1 << 62 => _,
+                    step = in (reg) MUL_STEP,
+                    inout ("x9") seed & !(1 << 63) | 1 << 62 => _,
+                    options (nomem, nostack)
+                );
+            }
+        }
+    };
+}
+
+mul_form!(mul_throughput, mul_latency, "mul", "{m}, {a}", "mul x9, x9, {m}");
+mul_form!(madd_throughput, madd_latency, "madd", "{m}, {a}, {a}", "madd x9, {m}, {m}, x9");
+mul_form!(umulh_throughput,
+          umulh_latency,
+          "umulh",
+          "{m}, {a}",
+          "umulh x9, x9, {m}\norr x9, x9, #0x4000000000000000");
+mul_form!(smulh_throughput,
+          smulh_latency,
+          "smulh",
+          "{m}, {a}",
+          "smulh x9, x9, {m}\norr x9, x9, #0x4000000000000000");
+
+/// Reads the size of the blocks zeroed by `dc zva`.
+///
+/// Returns the block size in bytes, or `None` if `dc zva` is prohibited.
+fn zva_block() -> Option<usize>
+{
+    let dczid: usize;
+    unsafe { asm!("mrs {dczid}, dczid_el0", dczid = out (reg) dczid, options (nomem, nostack, preserves_flags)) };
+    (dczid & DCZID_DZP == 0).then_some(4 << (dczid & 0xF))
+}
+
+/// Fills the buffer with the pattern repeatedly, storing 64 bytes from four
+/// vector registers per loop iteration.
+///
+/// * `buf`: Buffer to fill.
+/// * `pattern`: Pattern to fill the buffer with, laid out as described for
+///   [`fill_value`].
+/// * `iterations`: Number of times to fill the buffer.
+fn fill_wide(buf: *mut u8, pattern: u64, iterations: usize)
+{
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                "add {eaddr}, {addr}, #0x1000",
+                "ins {data0}.d[0], {lo}",
+                "ins {data0}.d[1], {hi}",
+                "dup {step}.2d, {inc}",
+                "add {data1}.2d, {data0}.2d, {step}.2d",
+                "add {data2}.2d, {data1}.2d, {step}.2d",
+                "add {data3}.2d, {data2}.2d, {step}.2d",
+                "shl {step}.2d, {step}.2d, #2",
+                "0:",
+                "cmp {addr}, {eaddr}",
+                "beq 0f",
+                "stp {data0:q}, {data1:q}, [{addr}]",
+                "stp {data2:q}, {data3:q}, [{addr}, #32]",
+                "add {addr}, {addr}, #64",
+                "add {data0}.2d, {data0}.2d, {step}.2d",
+                "add {data1}.2d, {data1}.2d, {step}.2d",
+                "add {data2}.2d, {data2}.2d, {step}.2d",
+                "add {data3}.2d, {data3}.2d, {step}.2d",
+                "b 0b",
+                "0:",
+                addr = inout (reg) buf => _,
+                eaddr = out (reg) _,
+                lo = in (reg) pattern,
+                hi = in (reg) !pattern,
+                inc = in (reg) PATTERN_STEP,
+                data0 = out (vreg) _,
+                data1 = out (vreg) _,
+                data2 = out (vreg) _,
+                data3 = out (vreg) _,
+                step = out (vreg) _
+            );
+        }
+    }
+}
+
+/// Fills the buffer with the pattern repeatedly, waiting for the stores to
+/// drain with a barrier after every fixed number of store pairs.
+///
+/// * `buf`: Buffer to fill.
+/// * `pattern`: Pattern to fill the buffer with, laid out as described for
+///   [`fill_value`].
+/// * `iterations`: Number of times to fill the buffer.
+/// * `stores`: Number of store pairs between barriers, which must not be zero.
+fn fill_fenced(buf: *mut u8, pattern: u64, iterations: usize, stores: usize)
+{
+    for _ in 0 .. iterations {
+        unsafe {
+            asm!(
+                "add {eaddr}, {addr}, #0x1000",
+                "ins {data0}.d[0], {lo}",
+                "ins {data0}.d[1], {hi}",
+                "dup {step}.2d, {inc}",
+                "add {data1}.2d, {data0}.2d, {step}.2d",
+                "add {step}.2d, {step}.2d, {step}.2d",
+                "mov {count}, {stores}",
+                "0:",
+                "stp {data0:q}, {data1:q}, [{addr}], #32",
+                "add {data0}.2d, {data0}.2d, {step}.2d",
+                "add {data1}.2d, {data1}.2d, {step}.2d",
+                "subs {count}, {count}, #1",
+                "bne 1f",
+                "dsb ishst",
+                "mov {count}, {stores}",
+                "1:",
+                "cmp {addr}, {eaddr}",
+                "bne 0b",
+                addr = inout (reg) buf => _,
+                eaddr = out (reg) _,
+                count = out (reg) _,
+                stores = in (reg) stores,
+                lo = in (reg) pattern,
+                hi = in (reg) !pattern,
+                inc = in (reg) PATTERN_STEP,
+                data0 = out (vreg) _,
+                data1 = out (vreg) _,
+                step = out (vreg) _
+            );
+        }
+    }
+}
+
+/// Follows a chain of pointers.
+///
+/// * `start`: Address of the first pointer in the chain.
+/// * `loads`: Number of pointers to follow, which must not be zero.
+///
+/// Returns the last pointer loaded.
+fn chase(start: usize, loads: usize) -> usize
+{
+    let end;
+    unsafe {
+        asm!(
+            "0:",
+            "ldr {ptr}, [{ptr}]",
+            "subs {loads}, {loads}, #1",
+            "bne 0b",
+            ptr = inout (reg) start => end,
+            loads = inout (reg) loads => _,
+            options (nostack)
+        );
+    }
+    end
+}
+
+/// Links the cache lines in a memory range into a single cyclic chain of
+/// pointers in random order using Sattolo's algorithm.
+///
+/// * `range`: Memory range to link, which must be aligned to the cache line
+///   size.
+fn link(range: Range<usize>)
+{
+    let lines = range.len() / LINE_SIZE;
+    let line = |idx: usize| (range.start + idx * LINE_SIZE) as *mut usize;
+    for idx in 0 .. lines {
+        unsafe { line(idx).write(idx) };
+    }
+    let mut state = 0x2545F4914F6CDD1Du64;
+    for idx in (1 .. lines).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let other = (state % idx as u64) as usize;
+        unsafe { line(idx).swap(line(other)) };
+    }
+    for idx in 0 .. lines {
+        unsafe { line(idx).write(line(line(idx).read()) as usize) };
+    }
+}
+
+/// Computes the fill pattern for a buffer from its address.
+///
+/// Being derived from the address means that buffers belonging to different
+/// cores never hold the same contents, so aliasing bugs are caught too.
+///
+/// * `addr`: Base address of the buffer.
+///
+/// Returns the computed pattern.
+fn pattern(addr: usize) -> u64
+{
+    (addr as u64).wrapping_mul(0x9E3779B97F4A7C15) ^ 0x5555555555555555
+}
+
+/// Computes the value of a double-word of a buffer filled with a pattern.
+///
+/// The pattern goes to the even double-words and its complement to the odd
+/// double-words, and both are offset by [`PATTERN_STEP`] times the index of
+/// the 16 byte chunk that they belong to, so that every bit position is checked
+/// in both states and data that lands at the wrong offset in the buffer, such
+/// as through a faulty address line, is caught as well.
+///
+/// * `pattern`: Pattern that the buffer was filled with.
+/// * `idx`: Index of the double-word in the buffer.
+///
+/// Returns the expected value.
+fn fill_value(pattern: u64, idx: usize) -> u64
+{
+    let base = if idx & 0x1 == 0 { pattern } else { !pattern };
+    base.wrapping_add((idx / 2) as u64 * PATTERN_STEP)
+}
+
+/// Verifies that a buffer was filled with the expected pattern, panicking with
+/// the offending address and values on the first mismatch.
+///
+/// * `buf`: Base address of the buffer, which must be aligned to 8 bytes
+///   unless the alignment check is disabled.
+/// * `len`: Length of the buffer in bytes, which must be a multiple of 16.
+/// * `pattern`: Pattern that the buffer is expected to be filled with.
+fn verify(buf: *const u64, len: usize, pattern: u64)
+{
+    let core = core_index();
+    for idx in 0 .. len / size_of::<u64>() {
+        let addr = unsafe { buf.add(idx) };
+        let expected = fill_value(pattern, idx);
+        let actual = unsafe { addr.read_unaligned() };
+        assert!(actual == expected,
+                "Core #{core} verification failed at 0x{:X}: Expected: 0x{expected:016X}, Actual: 0x{actual:016X}",
+                addr as usize);
+    }
+}
+
+/// Verifies that a range was zeroed, panicking with the offending address and
+/// value on the first mismatch.
+///
+/// * `range`: Range to check, which must be aligned to 8 bytes.
+fn verify_zeroed(range: Range<usize>)
+{
+    let core = core_index();
+    for addr in range.step_by(size_of::<u64>()) {
+        let actual = unsafe { (addr as *const u64).read() };
+        assert!(actual == 0,
+                "Core #{core} zeroing verification failed at 0x{addr:X}: Actual: 0x{actual:016X}");
+    }
+}
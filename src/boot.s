@@ -1,18 +1,32 @@
+// Physical address of the first EL0 stack, followed by the EL0 stacks of the other cores, one 2MB
+// block each.
+.globl el0_stacks
+.set el0_stacks, 0x200000
+// End of the EL0 stacks.
+.globl stacks_end
+.set stacks_end, el0_stacks + (2 << 20) * {CPU_COUNT}
+
 .bss
 
-.balign 0x1000
-// Stacks for both EL1 and EL2, one per core.
-eln_stack_x4:
-.zero 0x4000
+.balign {GUARD_SIZE} + {ELN_STACK_SIZE}
+// Stacks for both EL1 and EL2, one per core, each above an unmapped guard of the same size, so that
+// overflows fault before they reach the stack of the previous core or the image, and so that the
+// addresses in the guards have the bit of the stack size clear and those in the stacks have it
+// set.
+.globl eln_stacks
+eln_stacks:
+.zero ({GUARD_SIZE} + {ELN_STACK_SIZE}) * {CPU_COUNT}
 // Translation tables initialized with invalid records.
 root_tt:
 .zero 0x1000
+.globl static_tt
 static_tt:
 .zero 0x1000
 perry_tt:
 .zero 0x1000
 stacks_tt:
 .zero 0x1000
+.globl static_detail_tt
 static_detail_tt:
 .zero 0x1000
 
@@ -23,14 +37,39 @@ static_detail_tt:
 // Boot code.
 .globl boot
 boot:
-    // Set up the ELN stack.
+    // Keep the device tree address passed by the firmware to core 0 in x20 to
+    // be passed to the Rust code.
+    mov x20, x0
+    // Compute the logical index of the core as cluster * CORES_PER_CLUSTER +
+    // core, with the core in Aff0 and the cluster in Aff1, or the core in Aff1
+    // and the cluster in Aff2 if the core reports multi-threading, and keep it
+    // in x21 to select the stacks and in TPIDRRO_EL0 for the Rust code.  Cores
+    // without a logical index have no stacks and just sleep.
     mrs x0, mpidr_el1
-    and x0, x0, #0x3
-    adrp fp, eln_stack_x4
-    add fp, fp, x0, lsl 12
-    add fp, fp, #1 << 12
+    ubfx x1, x0, #0, #8
+    ubfx x2, x0, #8, #8
+    tbz x0, #24, 0f
+    ubfx x1, x0, #8, #8
+    ubfx x2, x0, #16, #8
+0:
+    mov x3, #{CORES_PER_CLUSTER}
+    madd x21, x2, x3, x1
+    cmp x21, #{CPU_COUNT}
+    blo 0f
+1:
+    wfe
+    b 1b
+0:
+    msr tpidrro_el0, x21
+    // Set up the ELN stack.
+    adrp fp, eln_stacks
+    mov x1, #{GUARD_SIZE} + {ELN_STACK_SIZE}
+    madd fp, x21, x1, fp
+    add fp, fp, x1
     mov sp, fp
-    // Execute boot code depending on the current exception level.
+    // Execute boot code depending on the current exception level, which is
+    // kept in x19 to be passed to the Rust code.
+    mrs x19, currentel
     mrs x0, currentel
     cmp x0, #0x8 // Booted in EL2.
     beq 0f
@@ -53,6 +92,14 @@ boot:
     msr vpidr_el2, x0
     mrs x0, mpidr_el1
     msr vmpidr_el2, x0
+    mrs x0, pmcr_el0
+    ubfx x0, x0, #11, #5 // Give EL1 access to all the performance counters.
+    msr mdcr_el2, x0
+    // Give EL1 access to the physical timer and counter, and make the virtual
+    // counter match the physical one.
+    mov x0, #0x3
+    msr cnthctl_el2, x0
+    msr cntvoff_el2, xzr
     mov x0, #0xc4
     msr spsr_el2, x0
     adr x0, start
@@ -67,14 +114,15 @@ boot:
     msr fpsr, xzr
     adr x0, ivec
     msr vbar_el1, x0
+    // Clear the recovery address of the fault probes, whose reset value is
+    // UNKNOWN, so that genuine faults reach the Rust handler.
+    msr tpidr_el1, xzr
     mov x0, #0xc4
     msr spsr_el1, x0
     adr x0, start
     msr elr_el1, x0
     // Core 0 tasks.
-    mrs x0, mpidr_el1
-    and x0, x0, #0x3
-    cbnz x0, 0f
+    cbnz x21, 0f
     // Clean up the BSS.
     adrp x0, bss_start
     adrp x1, bss_end
@@ -104,7 +152,7 @@ boot:
     mov x1, x0
     adrp x2, boot_end
     sub x2, x2, x1
-    mov x3, #0x4a3
+    mov x3, #0x4e3 // Read-only and accessible at EL0.
     adrp x4, static_detail_tt
     mov x5, #1 << 12
     bl map
@@ -125,45 +173,69 @@ boot:
     sub x2, x2, x1
     movk x3, #0x723
     bl map
+    // Map the BSS without the guards of the exception stacks.
     adrp x0, bss_start
     mov x1, x0
+    adrp x2, eln_stacks
+    sub x2, x2, x1
+    bl map
+    adrp x7, eln_stacks
+    mov x8, #{CPU_COUNT}
+1:
+    add x0, x7, #{GUARD_SIZE}
+    mov x1, x0
+    mov x2, #{ELN_STACK_SIZE}
+    bl map
+    add x7, x7, #{GUARD_SIZE} + {ELN_STACK_SIZE}
+    subs x8, x8, #1
+    bne 1b
+    mov x0, x7
+    mov x1, x0
     adrp x2, bss_end
     sub x2, x2, x1
     bl map
     mov x0, xzr
-    mov x1, #0xfc << 24
-    mov x2, #64 << 20
+    mov x1, #{PERRY_BASE}
+    mov x2, #{PERRY_SIZE}
     mov x3, #0x30 << 48
     movk x3, #0x429
     adrp x4, perry_tt
     mov x5, #2 << 20
     bl map
-    // Map the EL0 stacks.
+    // Map the EL0 stacks at the top of the address space with a 2MB gap below
+    // each of them.
     adrp x0, stacks_tt
-    add x0, x0, #0xfc8
-    adrp x1, stack_x4
+    add x0, x0, #0x1000 - 8 * (2 * {CPU_COUNT} - 1)
+    adrp x1, el0_stacks
     mov x2, #0x20 << 48
     movk x2, #0x421
+    mov x4, #{CPU_COUNT}
+1:
     orr x3, x1, x2
     str x3, [x0], #0x10
     add x1, x1, #2 << 20
-    orr x3, x1, x2
-    str x3, [x0], #0x10
-    add x1, x1, #2 << 20
-    orr x3, x1, x2
-    str x3, [x0], #0x10
-    add x1, x1, #2 << 20
-    orr x3, x1, x2
-    str x3, [x0]
-    // Unpark the secondary cores.
-    adr x0, boot
-    mov x1, #0xd8
-    str x0, [x1, #0x8] // Core 1.
-    str x0, [x1, #0x10] // Core 2.
-    str x0, [x1, #0x18] // Core 3.
-    dsb sy
-    sev
+    subs x4, x4, #1
+    bne 1b
 0:
+    // Configure and enable the MMU at EL2 with the same translation tables and
+    // attributes as EL1 if booted in EL2, so that the code called through
+    // hypervisor calls runs in the same environment.
+    cmp x19, #0x8
+    bne 1f
+    adrp x0, root_tt
+    msr ttbr0_el2, x0
+    mov x0, #0x8081 << 16
+    movk x0, #0x3520
+    msr tcr_el2, x0
+    mov x0, #0x44ff
+    movk x0, #0x400, lsl #16
+    msr mair_el2, x0
+    isb
+    mov x0, #0x30c5 << 16
+    movk x0, #0x183f
+    msr sctlr_el2, x0
+    isb
+1:
     // Configure and enable the MMu.
     adrp x0, root_tt
     msr ttbr0_el1, x0
@@ -172,18 +244,19 @@ boot:
     movk x0, #0x3520
     msr tcr_el1, x0
     mov x0, #0x44ff
+    movk x0, #0x400, lsl #16
     msr mair_el1, x0
     mov x0, #0x30d0 << 16
     movk x0, #0x1b9f
     msr sctlr_el1, x0
     isb
     // Jump to Rust code at EL1 with SP_EL0.
-    mrs x0, mpidr_el1
-    and x0, x0, #0x3
-    mov fp, #1 << 32
-    sub fp, fp, x0, lsl #22 // 2MB gap between stacks.
+    mov fp, #{STACKS_TOP}
+    sub fp, fp, x21, lsl #22 // 2MB gap between stacks.
     msr sp_el0, fp
     mov fp, xzr
+    lsr x0, x19, #2
+    mov x1, x20
     eret
 
 // Map function.
@@ -215,43 +288,283 @@ map:
 
 // Interrupt vector.
 //
-// Panics on any EL2 interrupts, any Sync or SError EL1 interrupts, and does nothing for FIQs and IRQs
-// since those are handled synchronously.
+// Panics on any EL2 interrupts other than hypervisor calls from EL1, which run a function at EL2,
+// and any Sync or SError EL1 interrupts other than supervisor calls, which return right away so
+// that their round trip can be timed unless they come from EL0 with immediate 1 to return from a
+// user call, and other Sync EL1 interrupts taken while TPIDR_EL1 holds a recovery address, which
+// resume there, hands EL1 IRQs over to the Rust handler, and does nothing for FIQs since those are
+// handled synchronously.  Sync interrupts taken on the exception stack check that saving x0 and fp
+// doesn't overflow it first, since the saving would fault again on the guard forever.
 .balign 0x800
 ivec:
 .irp kind,0,4,8,c
+.ifc \kind,4
+    // Swap x0 with the stack pointer that x0 and fp are to be saved at without a scratch register
+    // to test the bit of the stack size.
+    sub sp, sp, #0x10
+    add sp, sp, x0
+    sub x0, sp, x0
+    tbz x0, #{ELN_STACK_SHIFT}, eln_overflow
+    sub x0, sp, x0
+    sub sp, sp, x0
+    stp x0, fp, [sp]
+.else
+    stp x0, fp, [sp, #-0x10]!
+.endif
+    mrs x0, currentel
+    cmp x0, #0x4
+.ifc \kind,8
+    bne 1f
+.else
+    bne 0f
+.endif
+    mrs x0, esr_el1
+    lsr x0, x0, #26
+    cmp x0, #0x15 // SVC from AArch64.
+    bne 2f
+.ifc \kind,8
+    mrs x0, esr_el1
+    and x0, x0, #0xffff
+    cmp x0, #0x1 // Return from a user call.
+    beq user_exit
+.endif
+    ldp x0, fp, [sp], #0x10
+    eret
+2:
+    // Resume at the recovery address of a probe.
+    mrs x0, tpidr_el1
+    cbz x0, 0f
+    msr elr_el1, x0
+    msr tpidr_el1, xzr
+    ldp x0, fp, [sp], #0x10
+    eret
+.ifc \kind,8
+1:
+    mrs x0, esr_el2
+    lsr x0, x0, #26
+    cmp x0, #0x16 // HVC from AArch64.
+    beq hvc_entry
+.endif
+0:
     mov x0, #0x\kind
-    mov fp, sp
-    b fault
+    b fault_entry
 .balign 0x80
     stp x0, fp, [sp, #-0x10]!
     mov fp, sp
     mrs x0, currentel
     cmp x0, #0x4
     mov x0, #0x\kind + 1
-    bne fault
-    mrs x0, spsr_el1
-    orr x0, x0, #0xc0
-    msr spsr_el1, x0
-    ldp x0, fp, [sp], #0x10
-    eret
+    bne fault_entry
+    b irq_entry
 .balign 0x80
     stp x0, fp, [sp, #-0x10]!
     mov fp, sp
     mrs x0, currentel
     cmp x0, #0x4
     mov x0, #0x\kind + 2
-    bne fault
+    bne fault_entry
     mrs x0, spsr_el1
     orr x0, x0, #0xc0
     msr spsr_el1, x0
     ldp x0, fp, [sp], #0x10
     eret
 .balign 0x80
+    stp x0, fp, [sp, #-0x10]!
     mov x0, #0x\kind + 3
-    mov fp, sp
-    b fault
+    b fault_entry
 .balign 0x80
 .endr
 
+// Fault entry.
+//
+// x0: Kind of the vector, a single hexadecimal digit whose upper two bits are the origin of the
+// exception and whose lower two bits are its type.
+//
+// Saves all the general purpose registers in a frame on the stack, with x0 and fp already saved by
+// the vector, followed by the stack pointer before the vector saved them, and calls the Rust
+// handler with the kind and the frame, whose x29 and x30 slots form a frame record that chains the
+// faulting code into the backtrace.
+fault_entry:
+    sub sp, sp, #{FRAME_SIZE} - 0x10
+    stp x1, x2, [sp, #0x8]
+    stp x3, x4, [sp, #0x18]
+    stp x5, x6, [sp, #0x28]
+    stp x7, x8, [sp, #0x38]
+    stp x9, x10, [sp, #0x48]
+    stp x11, x12, [sp, #0x58]
+    stp x13, x14, [sp, #0x68]
+    stp x15, x16, [sp, #0x78]
+    stp x17, x18, [sp, #0x88]
+    stp x19, x20, [sp, #0x98]
+    stp x21, x22, [sp, #0xa8]
+    stp x23, x24, [sp, #0xb8]
+    stp x25, x26, [sp, #0xc8]
+    stp x27, x28, [sp, #0xd8]
+    ldp x1, x2, [sp, #{FRAME_SIZE} - 0x10]
+    str x1, [sp]
+    stp x2, lr, [sp, #{FRAME_FP}]
+    add x1, sp, #{FRAME_SIZE}
+    str x1, [sp, #{FRAME_SP}]
+    mov x1, sp
+    add fp, sp, #{FRAME_FP}
+    b fault
+
+// Exception stack overflow entry.
+//
+// x0: Stack pointer that the vector was to save x0 and fp at, with the stack pointer holding its
+// sum with the original x0.
+//
+// Restores x0 and switches to the stack selected by SP_EL0, since the exception stack has reached
+// its guard, to save x0 and fp there and enter the fault handler as the vector would have.
+eln_overflow:
+    sub x0, sp, x0
+    sub sp, sp, x0
+    msr spsel, #0
+    stp x0, fp, [sp, #-0x10]!
+    mov x0, #0x4
+    b fault_entry
+
+// Hypervisor call entry.
+//
+// Calls the function whose address is in x0 at EL2 with the argument in x1 on the stack of the
+// calling EL1 code, with x0 and fp already saved by the vector, and returns its result in x0.
+// The calling code must treat all the registers that the function may clobber as clobbered.
+hvc_entry:
+    ldp x0, fp, [sp], #0x10
+    msr spsel, #0
+    mrs x9, elr_el2
+    mrs x10, spsr_el2
+    stp x9, x10, [sp, #-0x10]!
+    stp fp, lr, [sp, #-0x10]!
+    mov fp, sp
+    mov x9, x0
+    mov x0, x1
+    blr x9
+    ldp fp, lr, [sp], #0x10
+    ldp x9, x10, [sp], #0x10
+    msr elr_el2, x9
+    msr spsr_el2, x10
+    msr spsel, #1
+    eret
+
+// User call.
+//
+// x0: Address of the function to call at EL0.
+// x1: Argument to pass to the function.
+// x2: Top of the EL0 stack, which must be 16 byte aligned.
+//
+// Calls the function at EL0 with the interrupt mask of the caller and returns its result in x0
+// once it returns to user_return, saving the stack pointer of the caller on the EL1h stack for
+// user_exit to restore.  The function must preserve the callee saved registers as required by the
+// procedure call standard.
+.globl user_call
+user_call:
+    stp fp, lr, [sp, #-0x10]!
+    mrs x10, daif
+    msr daifset, #0xf
+    stp x10, xzr, [sp, #-0x10]!
+    mov x9, sp
+    msr spsel, #1
+    stp x9, xzr, [sp, #-0x10]!
+    msr sp_el0, x2
+    msr elr_el1, x0
+    msr spsr_el1, x10 // EL0 with the interrupt mask of the caller.
+    mov x0, x1
+    adr lr, user_return
+    mov fp, xzr
+    eret
+
+// User return.
+//
+// Returns from a user call at EL0 with the result of the function in x0.
+user_return:
+    svc #1
+
+// User exit.
+//
+// Returns from a user call at EL1 with the result of the function in x0, with x0 and fp already
+// saved by the vector.
+user_exit:
+    ldp x0, fp, [sp], #0x10
+    ldp x9, xzr, [sp], #0x10
+    msr spsel, #0
+    mov sp, x9
+    ldp x10, xzr, [sp], #0x10
+    ldp fp, lr, [sp], #0x10
+    msr daif, x10
+    ret
+
+// IRQ entry.
+//
+// Saves all the registers that the Rust handler may clobber along with the exception return state,
+// with x0 and fp already saved by the vector, calls the handler, and restores them.
+irq_entry:
+    stp x1, x2, [sp, #-0x10]!
+    stp x3, x4, [sp, #-0x10]!
+    stp x5, x6, [sp, #-0x10]!
+    stp x7, x8, [sp, #-0x10]!
+    stp x9, x10, [sp, #-0x10]!
+    stp x11, x12, [sp, #-0x10]!
+    stp x13, x14, [sp, #-0x10]!
+    stp x15, x16, [sp, #-0x10]!
+    stp x17, x18, [sp, #-0x10]!
+    mrs x0, elr_el1
+    mrs x1, spsr_el1
+    stp x0, x1, [sp, #-0x10]!
+    stp lr, xzr, [sp, #-0x10]!
+    stp q0, q1, [sp, #-0x20]!
+    stp q2, q3, [sp, #-0x20]!
+    stp q4, q5, [sp, #-0x20]!
+    stp q6, q7, [sp, #-0x20]!
+    stp q8, q9, [sp, #-0x20]!
+    stp q10, q11, [sp, #-0x20]!
+    stp q12, q13, [sp, #-0x20]!
+    stp q14, q15, [sp, #-0x20]!
+    stp q16, q17, [sp, #-0x20]!
+    stp q18, q19, [sp, #-0x20]!
+    stp q20, q21, [sp, #-0x20]!
+    stp q22, q23, [sp, #-0x20]!
+    stp q24, q25, [sp, #-0x20]!
+    stp q26, q27, [sp, #-0x20]!
+    stp q28, q29, [sp, #-0x20]!
+    stp q30, q31, [sp, #-0x20]!
+    mrs x0, fpcr
+    mrs x1, fpsr
+    stp x0, x1, [sp, #-0x10]!
+    bl irq
+    ldp x0, x1, [sp], #0x10
+    msr fpcr, x0
+    msr fpsr, x1
+    ldp q30, q31, [sp], #0x20
+    ldp q28, q29, [sp], #0x20
+    ldp q26, q27, [sp], #0x20
+    ldp q24, q25, [sp], #0x20
+    ldp q22, q23, [sp], #0x20
+    ldp q20, q21, [sp], #0x20
+    ldp q18, q19, [sp], #0x20
+    ldp q16, q17, [sp], #0x20
+    ldp q14, q15, [sp], #0x20
+    ldp q12, q13, [sp], #0x20
+    ldp q10, q11, [sp], #0x20
+    ldp q8, q9, [sp], #0x20
+    ldp q6, q7, [sp], #0x20
+    ldp q4, q5, [sp], #0x20
+    ldp q2, q3, [sp], #0x20
+    ldp q0, q1, [sp], #0x20
+    ldp lr, xzr, [sp], #0x10
+    ldp x0, x1, [sp], #0x10
+    msr elr_el1, x0
+    msr spsr_el1, x1
+    ldp x17, x18, [sp], #0x10
+    ldp x15, x16, [sp], #0x10
+    ldp x13, x14, [sp], #0x10
+    ldp x11, x12, [sp], #0x10
+    ldp x9, x10, [sp], #0x10
+    ldp x7, x8, [sp], #0x10
+    ldp x5, x6, [sp], #0x10
+    ldp x3, x4, [sp], #0x10
+    ldp x1, x2, [sp], #0x10
+    ldp x0, fp, [sp], #0x10
+    eret
+
 .section .text
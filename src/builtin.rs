@@ -77,3 +77,69 @@ pub unsafe extern "C" fn memset(buf: *mut c_void, val: c_int, len: c_size_t) ->
     }
     ret
 }
+
+#[no_mangle]
+pub unsafe extern "C" fn memcmp(lhs: *const c_void, rhs: *const c_void, len: c_size_t) -> c_int
+{
+    let lhs = lhs as *const u8;
+    let rhs = rhs as *const u8;
+    for idx in 0 .. len as usize {
+        let (lhs, rhs) = (*lhs.add(idx), *rhs.add(idx));
+        if lhs != rhs {
+            return lhs as c_int - rhs as c_int;
+        }
+    }
+    0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn bcmp(lhs: *const c_void, rhs: *const c_void, len: c_size_t) -> c_int
+{
+    memcmp(lhs, rhs, len)
+}
+
+#[no_mangle]
+pub extern "C" fn __udivti3(num: u128, den: u128) -> u128
+{
+    udivmod(num, den).0
+}
+
+#[no_mangle]
+pub extern "C" fn __umodti3(num: u128, den: u128) -> u128
+{
+    udivmod(num, den).1
+}
+
+#[no_mangle]
+pub extern "C" fn __divti3(num: i128, den: i128) -> i128
+{
+    let quot = udivmod(num.unsigned_abs(), den.unsigned_abs()).0 as i128;
+    if (num < 0) != (den < 0) { quot.wrapping_neg() } else { quot }
+}
+
+// Divides with 64-bit divisions when both operands fit and by shifting and
+// subtracting otherwise, since 128-bit divisions would call back in here.
+fn udivmod(mut num: u128, mut den: u128) -> (u128, u128)
+{
+    assert!(den != 0, "Division by zero");
+    if num >> 64 == 0 && den >> 64 == 0 {
+        let (num, den) = (num as u64, den as u64);
+        let quot = num / den;
+        return (quot as u128, (num - quot * den) as u128);
+    }
+    if den > num {
+        return (0, num);
+    }
+    let shift = den.leading_zeros() - num.leading_zeros();
+    den <<= shift;
+    let mut quot = 0;
+    for _ in 0 ..= shift {
+        quot <<= 1;
+        if num >= den {
+            num -= den;
+            quot |= 1;
+        }
+        den >>= 1;
+    }
+    (quot, num)
+}
@@ -0,0 +1,180 @@
+//! Cache maintenance.
+//!
+//! Disabling the caches with [`disable`] at EL1 or at EL2 leaves the MMU of
+//! that level enabled, so all the data accesses of the calling core at that
+//! level become non-cacheable, including those to RAM that is still mapped as
+//! normal cacheable memory.  Accessing memory through a cacheable mapping with
+//! the caches disabled is an unsupported and inconsistent combination, since
+//! the other cores keep running and accessing the same memory, including the
+//! locks, the output queue, and the rest of the statics, through their
+//! cacheable mappings.  Code running with the caches disabled must therefore
+//! touch nothing but the registers, its own stack, and memory remapped as
+//! normal non-cacheable, which only the caches benchmark does.
+//!
+//! Documentation:
+//!
+//! * [Arm Architecture Reference Manual for A-profile architecture](https://developer.arm.com/documentation/ddi0487/latest)
+//!   D7.4
+
+use core::arch::asm;
+use core::ops::Range;
+
+/// Assumed size of a data cache line, which is the size on the Cortex-A72 and
+/// the Cortex-A76, and which sizes and aligns the buffers that need to be
+/// known at compile time.  [`line_sizes`] reads the actual sizes.
+pub const LINE_SIZE: usize = 64;
+/// Shift of the smallest data cache line size field of the cache type
+/// register.
+const CTR_DMINLINE_SHIFT: usize = 16;
+/// Mask of the line size fields of the cache type register, which hold the
+/// base 2 logarithm of the number of words in a line.
+const CTR_LINE_MASK: usize = 0xF;
+/// Data cache enable flag of the system control register.
+const SCTLR_C: u64 = 0x4;
+/// Instruction cache enable flag of the system control register.
+const SCTLR_I: u64 = 0x1000;
+
+/// Reads the smallest cache line sizes of the calling core from the cache
+/// type register.
+///
+/// Returns the smallest data and instruction cache line sizes in bytes.
+pub fn line_sizes() -> (usize, usize)
+{
+    let ctr: usize;
+    unsafe { asm!("mrs {ctr}, ctr_el0", ctr = out (reg) ctr, options (nomem, nostack, preserves_flags)) };
+    (4 << (ctr >> CTR_DMINLINE_SHIFT & CTR_LINE_MASK), 4 << (ctr & CTR_LINE_MASK))
+}
+
+/// Cleans and invalidates all the data cache lines overlapping a range of
+/// virtual addresses to the point of coherency.
+///
+/// * `range`: Range of virtual addresses to clean and invalidate.
+pub fn clean_invalidate(range: Range<usize>)
+{
+    let (line, _) = line_sizes();
+    for addr in (range.start & !(line - 1) .. range.end).step_by(line) {
+        unsafe { asm!("dc civac, {addr}", addr = in (reg) addr, options (nostack, preserves_flags)) };
+    }
+    unsafe { asm!("dsb sy", options (nostack, preserves_flags)) };
+}
+
+/// Cleans and invalidates the whole data cache hierarchy by set and way, then
+/// disables data and instruction caching on the calling core at the current
+/// exception level, which is EL1 or EL2.
+///
+/// The maintenance and the change of the system control register happen in a
+/// single block of code that doesn't touch memory, since any line dirtied in
+/// between would hide the latest data from the uncached accesses that follow.
+/// Maintaining the caches by set and way is only safe while the other cores
+/// are not accessing memory.
+///
+/// While caching is disabled, all memory accesses are non-cacheable, so
+/// exclusive accesses may never succeed and locks must not be taken.  IRQs are
+/// masked on the calling core for the same reason, until [`enable`].
+pub fn disable()
+{
+    unsafe {
+        asm!(
+            "msr daifset, #0x2",
+            "mrs {clidr}, clidr_el1",
+            "ubfx {loc}, {clidr}, #24, #3",
+            "lsl {loc}, {loc}, #1",
+            "mov {level}, xzr",
+            "0:",
+            "cmp {level}, {loc}",
+            "beq 0f",
+            // Skip the levels without a data cache.
+            "add {tmp}, {level}, {level}, lsr #1",
+            "lsr {tmp}, {clidr}, {tmp}",
+            "and {tmp}, {tmp}, #0x7",
+            "cmp {tmp}, #2",
+            "blt 3f",
+            "msr csselr_el1, {level}",
+            "isb",
+            "mrs {ccsidr}, ccsidr_el1",
+            "and {shift}, {ccsidr}, #0x7",
+            "add {shift}, {shift}, #4",
+            "ubfx {ways}, {ccsidr}, #3, #10",
+            "clz {wshift:w}, {ways:w}",
+            "ubfx {set}, {ccsidr}, #13, #15",
+            "1:",
+            "mov {way}, {ways}",
+            "2:",
+            "lsl {tmp}, {way}, {wshift}",
+            "orr {tmp}, {tmp}, {level}",
+            "lsl {op}, {set}, {shift}",
+            "orr {tmp}, {tmp}, {op}",
+            "dc cisw, {tmp}",
+            "subs {way}, {way}, #1",
+            "bge 2b",
+            "subs {set}, {set}, #1",
+            "bge 1b",
+            "3:",
+            "add {level}, {level}, #2",
+            "b 0b",
+            "0:",
+            "dsb sy",
+            "mrs {tmp}, currentel",
+            "cmp {tmp}, #0x8",
+            "beq 4f",
+            "mrs {tmp}, sctlr_el1",
+            "bic {tmp}, {tmp}, {flags}",
+            "msr sctlr_el1, {tmp}",
+            "b 5f",
+            "4:",
+            "mrs {tmp}, sctlr_el2",
+            "bic {tmp}, {tmp}, {flags}",
+            "msr sctlr_el2, {tmp}",
+            "5:",
+            "isb",
+            clidr = out (reg) _,
+            loc = out (reg) _,
+            level = out (reg) _,
+            ccsidr = out (reg) _,
+            shift = out (reg) _,
+            ways = out (reg) _,
+            wshift = out (reg) _,
+            set = out (reg) _,
+            way = out (reg) _,
+            op = out (reg) _,
+            tmp = out (reg) _,
+            flags = in (reg) SCTLR_C | SCTLR_I,
+            options (nostack)
+        );
+    }
+}
+
+/// Enables data and instruction caching on the calling core at the current
+/// exception level, then unmasks IRQs if that level is EL1, since IRQs must
+/// stay masked at EL2 until the hypervisor call returns.
+///
+/// Nothing is allocated in the caches while caching is disabled, so only the
+/// instruction cache needs to be invalidated to discard whatever it held
+/// before.
+pub fn enable()
+{
+    unsafe {
+        asm!(
+            "ic iallu",
+            "dsb nsh",
+            "mrs {tmp}, currentel",
+            "cmp {tmp}, #0x8",
+            "beq 0f",
+            "mrs {tmp}, sctlr_el1",
+            "orr {tmp}, {tmp}, {flags}",
+            "msr sctlr_el1, {tmp}",
+            "isb",
+            "msr daifclr, #0x2",
+            "b 1f",
+            "0:",
+            "mrs {tmp}, sctlr_el2",
+            "orr {tmp}, {tmp}, {flags}",
+            "msr sctlr_el2, {tmp}",
+            "isb",
+            "1:",
+            tmp = out (reg) _,
+            flags = in (reg) SCTLR_C | SCTLR_I,
+            options (nostack)
+        );
+    }
+}
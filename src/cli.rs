@@ -0,0 +1,734 @@
+//! Command line over the UART.
+//!
+//! Lines are split into whitespace separated tokens, the first of which names
+//! the command and the rest of which are its arguments.  Numeric arguments
+//! accept the `k`, `m`, and `g` binary suffixes, also spelled `ki` or `kib` and
+//! so on, as well as the `kb`, `mb`, and `gb` decimal suffixes.  Commands that
+//! can run on several cores take an optional trailing `mask=<mask>` argument
+//! that selects the cores by logical index, in decimal or with the `0b` or `0x`
+//! prefixes.
+//!
+//! Right after booting, a single configuration line such as `run=write,copy
+//! sizes=4k,1m trials=10` runs the listed benchmarks at every listed working
+//! set size the given number of times instead of waiting for input, so that
+//! scripts don't have to drive the menu.  An `addr=<address>` argument pins
+//! the working set of the write benchmark to a physical address in both cases.
+//! Across the trials of the configuration line, a write bandwidth dropping
+//! along with the SoC temperature rising is reported as thermal throttling.
+
+use core::fmt::{Display, Formatter, Result as FormatResult, Write};
+use core::hint::spin_loop;
+use core::str::{self, SplitWhitespace};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::bench::{self, CacheState};
+use crate::uart::UART;
+use crate::timer::{self, Source};
+use crate::{core_index, debug, mbox, menu, mmu, smp, watchdog};
+
+/// Maximum length of a line in bytes.
+pub const LINE_LEN: usize = 80;
+/// Commands.
+const COMMANDS: [Command; 11] = [Command { name: "help",
+                                          usage: "help",
+                                          desc: "List the commands",
+                                          action: help },
+                                Command { name: "bench",
+                                          usage: "bench [mask=<mask>]",
+                                          desc: "Run the benchmark on the selected cores or all of them",
+                                          action: bench },
+                                Command { name: "write",
+                                          usage: "write <size> <iterations|total=<bytes>> [addr=<address>|mask=<mask>]",
+                                          desc: "Fill a working set on this core at an address or the selected cores a number of times or until a total is written",
+                                          action: write },
+                                Command { name: "plateau",
+                                          usage: "plateau <size>",
+                                          desc: "Fill a working set on this core doubling the iterations until the bandwidth converges",
+                                          action: plateau },
+                                Command { name: "latency",
+                                          usage: "latency <size> [hot|cold] [histogram]",
+                                          desc: "Chase a chain of pointers on this core, optionally timing sampled loads one by one",
+                                          action: latency },
+                                Command { name: "emmc",
+                                          usage: "emmc <size>",
+                                          desc: "Read a number of bytes from the SD card on this core",
+                                          action: emmc },
+                                Command { name: "unaligned",
+                                          usage: "unaligned <offset>",
+                                          desc: "Compare misaligned and aligned stores on this core",
+                                          action: unaligned },
+                                Command { name: "clock",
+                                          usage: "clock <generic|system>",
+                                          desc: "Select the timer used to time the benchmarks",
+                                          action: clock },
+                                Command { name: "faults",
+                                          usage: "faults <system|core>",
+                                          desc: "Select whether a panic on a secondary core stalls the jobs or only halts that core",
+                                          action: faults },
+                                Command { name: "crash",
+                                          usage: "crash",
+                                          desc: "Panic on this core while holding the UART lock to check that the panic is still reported",
+                                          action: crash },
+                                Command { name: "dump",
+                                          usage: "dump <address> <length>",
+                                          desc: "Print a range of mapped RAM in hexadecimal and ASCII",
+                                          action: dump }];
+
+/// Maximum number of bytes printed by the dump command.
+const DUMP_MAX: usize = 0x1000;
+/// Usage of the boot configuration line.
+const CONFIG_USAGE: &str = "run=<benchmark>[,<benchmark>...] sizes=<size>[,<size>...] [trials=<trials>] [addr=<address>]";
+/// Benchmarks that the boot configuration line can run along with their
+/// names, each taking the working set size.
+const CONFIG_BENCHMARKS: [ConfigBenchmark; 4] = [("write", config_write),
+                                                 ("copy", bench::bench_copy_sized),
+                                                 ("latency", config_latency),
+                                                 ("plateau", bench::bench_plateau)];
+/// Maximum number of entries in a list of the boot configuration line, which
+/// no line can exceed since every entry takes at least one character and a
+/// separator.
+const CONFIG_LIST_MAX: usize = LINE_LEN / 2;
+/// Number of bytes written at every size by the write benchmark run by the
+/// boot configuration line.
+const CONFIG_WRITE_TOTAL: usize = 1 << 30;
+/// Time given to the boot configuration line to start arriving in
+/// milliseconds.
+const CONFIG_TIMEOUT_MSECS: usize = 2000;
+/// Drop in percent of the write bandwidth of a trial of the boot
+/// configuration line from the first trial beyond which it reports thermal
+/// throttling if the SoC temperature rose in between.
+const THROTTLE_DROP: u128 = 5;
+
+/// Working set size of the sized write benchmark run as a job.
+static WRITE_SIZE: AtomicUsize = AtomicUsize::new(0);
+/// Number of iterations of the sized write benchmark run as a job.
+static WRITE_ITERATIONS: AtomicUsize = AtomicUsize::new(0);
+/// Physical address of the working set of the write benchmark run by the boot
+/// configuration line, or zero to use the share of the free RAM of this core,
+/// which never starts at zero.
+static CONFIG_ADDR: AtomicUsize = AtomicUsize::new(0);
+
+/// Benchmark that the boot configuration line can run along with its name,
+/// taking the working set size.
+type ConfigBenchmark = (&'static str, fn(usize));
+
+/// Boot configuration line.
+struct Config
+{
+    /// Benchmarks to run along with their names, in order.
+    run: [Option<ConfigBenchmark>; CONFIG_LIST_MAX],
+    /// Working set sizes to run every benchmark at, in order.
+    sizes: [Option<usize>; CONFIG_LIST_MAX],
+    /// Number of times to run the lists.
+    trials: usize,
+    /// Physical address of the working set of the write benchmark, or zero
+    /// to use the share of the free RAM of this core.
+    addr: usize,
+}
+
+/// Command.
+struct Command
+{
+    /// Name that selects the command.
+    name: &'static str,
+    /// Usage summary.
+    usage: &'static str,
+    /// Description.
+    desc: &'static str,
+    /// Action performed with the arguments when the command is run.
+    action: for<'a> fn(&mut SplitWhitespace<'a>) -> Result<(), Error<'a>>,
+}
+
+/// Command line errors.
+#[derive(Clone, Copy, Debug)]
+enum Error<'a>
+{
+    /// A required argument is missing.
+    Missing(&'static str),
+    /// An argument is not valid.
+    Invalid(&'static str, &'a str),
+    /// There are more arguments than the command takes.
+    Extra(&'a str),
+}
+
+/// Reads a line from the UART, echoing it back and handling backspaces, and
+/// petting the watchdog while waiting since waiting for the user is not a
+/// stall.
+///
+/// * `buf`: Buffer to read the line into.
+///
+/// Returns the line without the terminator.
+pub fn read_line(buf: &mut [u8; LINE_LEN]) -> &str
+{
+    read_line_within(buf, None).unwrap()
+}
+
+/// Waits for a configuration line over the UART for a little while after
+/// booting and runs it, reporting any errors in it.
+///
+/// Must only be called from core #0 with the UART interrupts enabled.
+pub fn boot_config()
+{
+    let mut buf = [0; LINE_LEN];
+    debug!("Send a configuration line within {}s to run it: {CONFIG_USAGE}",
+           CONFIG_TIMEOUT_MSECS / 1000);
+    let Some(line) = read_line_within(&mut buf, Some(CONFIG_TIMEOUT_MSECS)) else {
+        debug!("No configuration line received");
+        return;
+    };
+    let config = match parse_config(line.trim()) {
+        Ok(config) => config,
+        Err(err) => {
+            debug!("{err}, usage: {CONFIG_USAGE}");
+            return;
+        }
+    };
+    debug!("{}", menu::START_MARKER);
+    CONFIG_ADDR.store(config.addr, Ordering::Relaxed);
+    let core = core_index();
+    let mut first = None;
+    for trial in 0 .. config.trials {
+        debug!("Configuration trial {} of {}", trial + 1, config.trials);
+        // The temperature is sampled between trials, outside of any timed
+        // benchmark.
+        let temp = mbox::temperature().ok();
+        let mut write = 0;
+        for (name, benchmark) in config.run.iter().flatten() {
+            for &size in config.sizes.iter().flatten() {
+                smp::record(0);
+                benchmark(size);
+                if *name == "write" {
+                    write += smp::results()[core] as u128;
+                }
+            }
+        }
+        let Some(temp) = temp else {
+            continue;
+        };
+        let Some((first_write, first_temp)) = first else {
+            first = Some((write, temp));
+            continue;
+        };
+        let drop = first_write.saturating_sub(write) * 100 / first_write.max(1);
+        if drop > THROTTLE_DROP && temp > first_temp {
+            let rise = temp - first_temp;
+            debug!("THROTTLING DETECTED: Trial {} write bandwidth is {drop}% below the first trial while the SoC temperature rose by {}.{}C",
+                   trial + 1,
+                   rise / 1000,
+                   rise % 1000 / 100);
+        }
+    }
+    bench::control_state();
+    debug!("{}", menu::COMPLETE_MARKER);
+}
+
+/// Reads a line from the UART, echoing it back and handling backspaces, and
+/// petting the watchdog while waiting.
+///
+/// * `buf`: Buffer to read the line into.
+/// * `timeout`: Time given to the first byte of the line to arrive in
+///   milliseconds, or `None` to wait forever.
+///
+/// Returns the line without the terminator, or `None` if nothing arrived in
+/// time.
+fn read_line_within(buf: &mut [u8; LINE_LEN], timeout: Option<usize>) -> Option<&str>
+{
+    let start = timer::now();
+    let ticks = timeout.map(|msecs| timer::frequency() / 1000 * msecs);
+    let mut len = 0;
+    loop {
+        let Some(byte) = UART.lock().read() else {
+            watchdog::pet();
+            // The FIFO is too small to poll any less often.
+            if len == 0 && ticks.is_some_and(|ticks| timer::elapsed(start, timer::now()) >= ticks) {
+                return None;
+            }
+            spin_loop();
+            continue;
+        };
+        let mut uart = UART.lock();
+        match byte {
+            b'\r' | b'\n' => {
+                uart.write_char('\n').unwrap();
+                break;
+            }
+            0x8 | 0x7F if len > 0 => {
+                len -= 1;
+                uart.write_str("\x08 \x08").unwrap();
+            }
+            0x20 .. 0x7F if len < LINE_LEN => {
+                buf[len] = byte;
+                len += 1;
+                uart.write_char(byte as char).unwrap();
+            }
+            _ => (),
+        }
+    }
+    // Only printable ASCII is ever stored.
+    Some(str::from_utf8(&buf[.. len]).unwrap())
+}
+
+/// Parses and runs a command line, reporting the errors over the UART.
+///
+/// * `line`: Command line.
+pub fn execute(line: &str)
+{
+    let mut args = line.split_whitespace();
+    let Some(name) = args.next() else {
+        return;
+    };
+    let Some(cmd) = COMMANDS.iter().find(|cmd| cmd.name == name) else {
+        debug!("Unknown command: {name:?} (type help for the list of commands)");
+        return;
+    };
+    if let Err(err) = (cmd.action)(&mut args) {
+        debug!("{err}, usage: {}", cmd.usage);
+    }
+}
+
+/// Lists the commands.
+///
+/// * `args`: Arguments, of which there must be none.
+///
+/// Returns an error if there are any arguments.
+fn help<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    finish(args)?;
+    let mut uart = UART.lock();
+    writeln!(uart, "Commands:").unwrap();
+    for cmd in COMMANDS.iter() {
+        writeln!(uart, "{}: {}", cmd.usage, cmd.desc).unwrap();
+    }
+    Ok(())
+}
+
+/// Runs the benchmark on a subset of the cores.
+///
+/// * `args`: Optionally the mask of the cores to run the benchmark on.
+///
+/// Returns an error if the arguments are not valid.
+fn bench<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    let mask = core_mask(args)?.unwrap_or(smp::ALL_CORES);
+    finish(args)?;
+    menu::run_on(mask, bench::run);
+    Ok(())
+}
+
+/// Runs the sized write benchmark on this core or a subset of the cores.
+///
+/// * `args`: Working set size, either the number of iterations or the total
+///   number of bytes to write, which is rounded up to whole iterations, and
+///   optionally either the physical address of the working set, in decimal or
+///   with the `0x` prefix, or the mask of the cores to run the benchmark on.
+///
+/// Returns an error if the arguments are not valid.
+fn write<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    let size = number(args, "size")?;
+    let arg = args.next().ok_or(Error::Missing("iterations"))?;
+    let iterations = match arg.strip_prefix("total=") {
+        Some(total) => {
+            let total = parse_size(total).ok_or(Error::Invalid("total", arg))?;
+            total.div_ceil(size.max(1))
+        }
+        None => parse_size(arg).ok_or(Error::Invalid("iterations", arg))?,
+    };
+    if let Some(arg) = args.clone().next().filter(|arg| arg.starts_with("addr=")) {
+        args.next();
+        let addr = address(&arg["addr=".len() ..]).ok_or(Error::Invalid("address", arg))?;
+        finish(args)?;
+        bench::bench_write_at(addr, size, iterations);
+        return Ok(());
+    }
+    let mask = core_mask(args)?;
+    finish(args)?;
+    match mask {
+        Some(mask) => {
+            WRITE_SIZE.store(size, Ordering::Relaxed);
+            WRITE_ITERATIONS.store(iterations, Ordering::Relaxed);
+            // Posting the job releases the arguments to the selected cores.
+            menu::run_on(mask, || {
+                bench::bench_write_sized(WRITE_SIZE.load(Ordering::Relaxed), WRITE_ITERATIONS.load(Ordering::Relaxed))
+            });
+        }
+        None => bench::bench_write_sized(size, iterations),
+    }
+    Ok(())
+}
+
+/// Runs the plateau benchmark.
+///
+/// * `args`: Working set size.
+///
+/// Returns an error if the arguments are not valid.
+fn plateau<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    let size = number(args, "size")?;
+    finish(args)?;
+    bench::bench_plateau(size);
+    Ok(())
+}
+
+/// Runs the sized latency benchmark.
+///
+/// * `args`: Pointer chain size and optionally the state of the caches and
+///   whether to sample a latency histogram.
+///
+/// Returns an error if the arguments are not valid.
+fn latency<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    let size = number(args, "size")?;
+    let mut state = CacheState::Hot;
+    let mut histogram = false;
+    for arg in args {
+        match arg {
+            "hot" => state = CacheState::Hot,
+            "cold" => state = CacheState::Cold,
+            "histogram" => histogram = true,
+            arg => return Err(Error::Invalid("latency option", arg)),
+        }
+    }
+    bench::bench_latency_sized(state, size, histogram);
+    Ok(())
+}
+
+/// Runs the sized SD card benchmark.
+///
+/// * `args`: Number of bytes to read.
+///
+/// Returns an error if the arguments are not valid.
+fn emmc<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    let size = number(args, "size")?;
+    finish(args)?;
+    bench::bench_emmc_sized(size);
+    Ok(())
+}
+
+/// Runs the misaligned write benchmark.
+///
+/// * `args`: Misalignment in bytes.
+///
+/// Returns an error if the arguments are not valid.
+fn unaligned<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    let offset = number(args, "offset")?;
+    finish(args)?;
+    bench::bench_unaligned(offset);
+    Ok(())
+}
+
+/// Selects the timing source.
+///
+/// * `args`: Timing source.
+///
+/// Returns an error if the arguments are not valid.
+fn clock<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    let source = match args.next() {
+        Some("generic") => Source::Generic,
+        Some("system") => Source::System,
+        Some(arg) => return Err(Error::Invalid("timer", arg)),
+        None => return Err(Error::Missing("timer")),
+    };
+    finish(args)?;
+    timer::select(source);
+    debug!("Timing with the {source:?} timer at {}Hz, Read overhead: {} ticks",
+           timer::frequency(),
+           timer::overhead());
+    Ok(())
+}
+
+/// Selects what a panic on a secondary core halts.
+///
+/// * `args`: Whether the whole system or only the core halts.
+///
+/// Returns an error if the arguments are not valid.
+fn faults<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    let isolate = match args.next() {
+        Some("system") => false,
+        Some("core") => true,
+        Some(arg) => return Err(Error::Invalid("fault scope", arg)),
+        None => return Err(Error::Missing("fault scope")),
+    };
+    finish(args)?;
+    smp::isolate(isolate);
+    debug!("A panic on a secondary core halts {}, Failed cores: 0b{:04b}",
+           if isolate { "only that core" } else { "the whole system" },
+           !smp::alive() & smp::ALL_CORES);
+    Ok(())
+}
+
+/// Panics deliberately while holding the UART lock, which the panic handler
+/// must reclaim to report the panic.
+///
+/// * `args`: Arguments, of which there must be none.
+///
+/// Returns an error if there are any arguments, and does not return
+/// otherwise.
+fn crash<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    finish(args)?;
+    let _uart = UART.lock();
+    panic!("Deliberate panic on core #{} while holding the UART lock", core_index());
+}
+
+/// Prints a range of memory in hexadecimal and ASCII.
+///
+/// * `args`: Address of the first byte, in decimal or with the `0x` prefix,
+///   and number of bytes, up to [`DUMP_MAX`].
+///
+/// Returns an error if the arguments are not valid or the range is not mapped
+/// as normal memory.
+fn dump<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    let arg = args.next().ok_or(Error::Missing("address"))?;
+    let addr = address(arg).ok_or(Error::Invalid("address", arg))?;
+    let arg = args.next().ok_or(Error::Missing("length"))?;
+    let len = parse_size(arg).filter(|&len| len != 0 && len <= DUMP_MAX)
+                             .ok_or(Error::Invalid("length", arg))?;
+    finish(args)?;
+    let end = addr.checked_add(len).ok_or(Error::Invalid("length", arg))?;
+    if !mmu::readable(addr .. end) {
+        debug!("Memory range 0x{addr:X} .. 0x{end:X} is not entirely mapped as normal memory");
+        return Ok(());
+    }
+    UART.lock().hex_dump(addr, len);
+    Ok(())
+}
+
+/// Parses a boot configuration line, which must name only known benchmarks
+/// and valid sizes, numbers of trials, and addresses.
+///
+/// * `line`: Configuration line.
+///
+/// Returns the configuration, in which the number of trials defaults to one
+/// and the address of the working set of the write benchmark defaults to
+/// zero, or an error if the configuration line is not valid.
+fn parse_config(line: &str) -> Result<Config, Error<'_>>
+{
+    let mut config = Config { run: [None; CONFIG_LIST_MAX],
+                              sizes: [None; CONFIG_LIST_MAX],
+                              trials: 1,
+                              addr: 0 };
+    let (mut run, mut sizes) = (false, false);
+    for arg in line.split_whitespace() {
+        match arg.split_once('=') {
+            Some(("run", list)) => {
+                config.run = [None; CONFIG_LIST_MAX];
+                // No line is long enough to overflow the list.
+                for (entry, name) in config.run.iter_mut().zip(list.split(',')) {
+                    let benchmark = CONFIG_BENCHMARKS.iter()
+                                                     .find(|(other, _)| *other == name)
+                                                     .ok_or(Error::Invalid("benchmark", name))?;
+                    *entry = Some(*benchmark);
+                }
+                run = true;
+            }
+            Some(("sizes", list)) => {
+                config.sizes = [None; CONFIG_LIST_MAX];
+                for (entry, size) in config.sizes.iter_mut().zip(list.split(',')) {
+                    *entry = Some(parse_size(size).ok_or(Error::Invalid("size", size))?);
+                }
+                sizes = true;
+            }
+            Some(("trials", trials)) => {
+                config.trials = trials.parse()
+                                      .ok()
+                                      .filter(|&trials| trials != 0)
+                                      .ok_or(Error::Invalid("trials", trials))?;
+            }
+            Some(("addr", addr)) => {
+                config.addr = address(addr).filter(|&addr| addr != 0).ok_or(Error::Invalid("address", addr))?;
+            }
+            _ => return Err(Error::Extra(arg)),
+        }
+    }
+    if !run {
+        return Err(Error::Missing("benchmarks"));
+    }
+    if !sizes {
+        return Err(Error::Missing("sizes"));
+    }
+    Ok(config)
+}
+
+/// Runs the sized write benchmark on this core for a boot configuration line,
+/// with enough iterations to write [`CONFIG_WRITE_TOTAL`] bytes, at
+/// [`CONFIG_ADDR`] if set.
+///
+/// * `size`: Working set size in bytes.
+fn config_write(size: usize)
+{
+    let iterations = CONFIG_WRITE_TOTAL.div_ceil(size.max(1));
+    match CONFIG_ADDR.load(Ordering::Relaxed) {
+        0 => bench::bench_write_sized(size, iterations),
+        addr => bench::bench_write_at(addr, size, iterations),
+    }
+}
+
+/// Runs the sized latency benchmark on this core with the chain warmed up for
+/// a boot configuration line.
+///
+/// * `size`: Pointer chain size in bytes.
+fn config_latency(size: usize)
+{
+    bench::bench_latency_sized(CacheState::Hot, size, false)
+}
+
+/// Parses the next argument as an optional core mask.
+///
+/// * `args`: Arguments.
+///
+/// Returns the mask, `None` if there are no arguments left, or an error if the
+/// argument is not a valid core mask.
+fn core_mask<'a>(args: &mut SplitWhitespace<'a>) -> Result<Option<u8>, Error<'a>>
+{
+    let Some(arg) = args.next() else {
+        return Ok(None);
+    };
+    let val = arg.strip_prefix("mask=").ok_or(Error::Extra(arg))?;
+    let mask = if let Some(digits) = val.strip_prefix("0b") {
+        u8::from_str_radix(digits, 2)
+    } else if let Some(digits) = val.strip_prefix("0x") {
+        u8::from_str_radix(digits, 16)
+    } else {
+        val.parse()
+    };
+    match mask {
+        Ok(mask) if mask != 0 && mask & !smp::ALL_CORES == 0 => Ok(Some(mask)),
+        _ => Err(Error::Invalid("core mask", arg)),
+    }
+}
+
+/// Parses an address.
+///
+/// * `arg`: Text to parse, in decimal or with the `0x` prefix.
+///
+/// Returns the parsed address, or `None` if the text is not valid.
+fn address(arg: &str) -> Option<usize>
+{
+    match arg.strip_prefix("0x") {
+        Some(digits) => usize::from_str_radix(digits, 16).ok(),
+        None => parse_size(arg),
+    }
+}
+
+/// Parses the next argument as a size.
+///
+/// * `args`: Arguments.
+/// * `name`: Name of the argument.
+///
+/// Returns the size, or an error if the argument is missing or not valid.
+fn number<'a>(args: &mut SplitWhitespace<'a>, name: &'static str) -> Result<usize, Error<'a>>
+{
+    let arg = args.next().ok_or(Error::Missing(name))?;
+    parse_size(arg).ok_or(Error::Invalid(name, arg))
+}
+
+/// Parses a decimal number with an optional case insensitive multiplier
+/// suffix, which is `k`, `m`, or `g` for powers of 1024, optionally followed
+/// by `i` or `ib`, and `kb`, `mb`, or `gb` for powers of 1000.
+///
+/// * `arg`: Text to parse.
+///
+/// Returns the parsed number, or `None` if the text is not valid or the number
+/// does not fit.
+pub fn parse_size(arg: &str) -> Option<usize>
+{
+    let split = arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len());
+    let (digits, suffix) = arg.split_at(split);
+    let multiplier: usize = match suffix.as_bytes() {
+        [] => 1,
+        [b'k' | b'K'] | [b'k' | b'K', b'i' | b'I'] | [b'k' | b'K', b'i' | b'I', b'b' | b'B'] => 1 << 10,
+        [b'm' | b'M'] | [b'm' | b'M', b'i' | b'I'] | [b'm' | b'M', b'i' | b'I', b'b' | b'B'] => 1 << 20,
+        [b'g' | b'G'] | [b'g' | b'G', b'i' | b'I'] | [b'g' | b'G', b'i' | b'I', b'b' | b'B'] => 1 << 30,
+        [b'k' | b'K', b'b' | b'B'] => 1000,
+        [b'm' | b'M', b'b' | b'B'] => 1000000,
+        [b'g' | b'G', b'b' | b'B'] => 1000000000,
+        _ => return None,
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    digits.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+/// Makes sure that there are no arguments left.
+///
+/// * `args`: Arguments.
+///
+/// Returns an error if there are any arguments left.
+fn finish<'a>(args: &mut SplitWhitespace<'a>) -> Result<(), Error<'a>>
+{
+    match args.next() {
+        Some(arg) => Err(Error::Extra(arg)),
+        None => Ok(()),
+    }
+}
+
+impl Display for Error<'_>
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        match self {
+            Self::Missing(name) => write!(fmt, "Missing {name}"),
+            Self::Invalid(name, arg) => write!(fmt, "Invalid {name}: {arg:?}"),
+            Self::Extra(arg) => write!(fmt, "Unexpected argument: {arg:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn suffixes()
+    {
+        assert_eq!(parse_size("42"), Some(42));
+        assert_eq!(parse_size("4k"), Some(4 << 10));
+        assert_eq!(parse_size("4Ki"), Some(4 << 10));
+        assert_eq!(parse_size("2m"), Some(2 << 20));
+        assert_eq!(parse_size("2MI"), Some(2 << 20));
+        assert_eq!(parse_size("1g"), Some(1 << 30));
+        assert_eq!(parse_size("1gi"), Some(1 << 30));
+        assert_eq!(parse_size("8KiB"), Some(8 << 10));
+        assert_eq!(parse_size("4kb"), Some(4000));
+        assert_eq!(parse_size("2MB"), Some(2000000));
+        assert_eq!(parse_size("1Gb"), Some(1000000000));
+    }
+
+    #[test]
+    fn zero()
+    {
+        assert_eq!(parse_size("0"), Some(0));
+        assert_eq!(parse_size("0k"), Some(0));
+    }
+
+    #[test]
+    fn overflow()
+    {
+        assert_eq!(parse_size("18446744073709551615"), Some(usize::MAX));
+        assert_eq!(parse_size("18446744073709551616"), None);
+        assert_eq!(parse_size("17179869184g"), None);
+        assert_eq!(parse_size("18446744073709552kb"), None);
+    }
+
+    #[test]
+    fn garbage()
+    {
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("k"), None);
+        assert_eq!(parse_size("-1"), None);
+        assert_eq!(parse_size("+1"), None);
+        assert_eq!(parse_size("1.5k"), None);
+        assert_eq!(parse_size("4kk"), None);
+        assert_eq!(parse_size("4kbi"), None);
+        assert_eq!(parse_size("4 k"), None);
+        assert_eq!(parse_size("0x10"), None);
+    }
+}
@@ -0,0 +1,227 @@
+//! DMA controller driver.
+//!
+//! Only one of the full featured legacy channels is used, which can transfer
+//! up to a gigabyte per control block.  Transfers are started in the background
+//! and then polled for completion with a timeout, so a wedged channel is
+//! reported instead of hanging the benchmark.
+//!
+//! Documentation:
+//!
+//! * [BCM2711 ARM Peripherals](https://datasheets.raspberrypi.com/bcm2711/bcm2711-peripherals.pdf)
+//!   4
+
+use core::fmt::{Display, Formatter, Result as FormatResult};
+use core::hint::spin_loop;
+use core::ops::Range;
+use core::ptr::addr_of;
+
+use crate::sync::{Lock, LockGuard};
+use crate::{cache, timer, PERRY_RANGE};
+
+/// Base address of the DMA controller registers.
+const DMA_BASE: usize = 0x2007000 + PERRY_RANGE.start;
+/// Channel used for all the transfers, which is not used by the firmware.
+const CHANNEL: usize = 5;
+/// Base address of the registers of the channel.
+const CHANNEL_BASE: usize = DMA_BASE + CHANNEL * 0x100;
+/// Control and status register of the channel.
+const DMA_CS: *mut u32 = CHANNEL_BASE as _;
+/// Control block address register of the channel.
+const DMA_CONBLK_AD: *mut u32 = (CHANNEL_BASE + 0x4) as _;
+/// Current destination address register of the channel.
+const DMA_DEST_AD: *const u32 = (CHANNEL_BASE + 0x10) as _;
+/// Global enable register.
+const DMA_ENABLE: *mut u32 = (DMA_BASE + 0xFF0) as _;
+/// Channel active flag.
+const CS_ACTIVE: u32 = 0x1;
+/// Transfer complete flag, cleared by writing it.
+const CS_END: u32 = 0x2;
+/// Error flag.
+const CS_ERROR: u32 = 0x100;
+/// Wait for outstanding writes flag.
+const CS_WAIT_FOR_OUTSTANDING_WRITES: u32 = 0x10000000;
+/// Channel reset flag.
+const CS_RESET: u32 = 0x80000000;
+/// Transfer information for a memory to memory copy with 128-bit reads and
+/// writes in bursts of 4 that waits for the write responses.
+const TI_MEMCPY: u32 = 0x4 << 12 | 0x1 << 9 | 0x1 << 8 | 0x1 << 5 | 0x1 << 4 | 0x1 << 3;
+/// Maximum length of a transfer in bytes.
+pub const MAX_LEN: usize = 0x40000000;
+/// Alias through which the DMA controller accesses ARM memory without caching
+/// it.
+const BUS_ALIAS: u32 = 0xC0000000;
+/// Transfer timeout in milliseconds.
+const TIMEOUT_MSECS: usize = 5000;
+
+/// Global DMA control block, locked for the duration of every transfer.
+static CONTROL_BLOCK: Lock<ControlBlock> = Lock::new(ControlBlock::new());
+
+/// Control block describing a transfer.
+///
+/// Takes up an entire cache line so that maintaining it can't affect anything
+/// else.
+#[repr(align(64), C)]
+#[derive(Debug)]
+pub struct ControlBlock
+{
+    /// Transfer information.
+    ti: u32,
+    /// Source bus address.
+    source_ad: u32,
+    /// Destination bus address.
+    dest_ad: u32,
+    /// Transfer length in bytes.
+    txfr_len: u32,
+    /// Two dimensional stride.
+    stride: u32,
+    /// Bus address of the next control block.
+    nextconbk: u32,
+    /// Reserved.
+    _reserved: [u32; 2],
+}
+
+/// Transfer in progress, which holds the control block until waited for.
+#[derive(Debug)]
+pub struct Transfer
+{
+    /// Control block describing the transfer.
+    _cb: LockGuard<'static, ControlBlock>,
+    /// Destination range of physical addresses.
+    dst: Range<usize>,
+    /// Timer count when the transfer started.
+    start: usize,
+}
+
+/// DMA errors.
+#[derive(Clone, Copy, Debug)]
+pub enum Error
+{
+    /// The channel reported an error.
+    Bus,
+    /// The transfer did not complete in time.
+    Timeout,
+}
+
+impl ControlBlock
+{
+    /// Creates and initializes a new empty control block.
+    ///
+    /// Returns the newly created control block.
+    const fn new() -> Self
+    {
+        Self { ti: 0,
+               source_ad: 0,
+               dest_ad: 0,
+               txfr_len: 0,
+               stride: 0,
+               nextconbk: 0,
+               _reserved: [0; 2] }
+    }
+}
+
+impl Display for Error
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        match self {
+            Self::Bus => write!(fmt, "DMA channel {CHANNEL} reported an error"),
+            Self::Timeout => write!(fmt, "DMA channel {CHANNEL} timed out after {TIMEOUT_MSECS} milliseconds"),
+        }
+    }
+}
+
+/// Copies memory with the DMA controller, blocking until the copy completes.
+///
+/// The source and destination are cleaned and invalidated from the data cache
+/// around the transfer, so they can be accessed normally before and after.
+///
+/// * `dst`: Destination range of physical addresses.
+/// * `src`: Source physical address.
+///
+/// Returns an error if the transfer fails or times out.
+pub fn copy(dst: Range<usize>, src: usize) -> Result<(), Error>
+{
+    start(dst, src).wait()
+}
+
+/// Starts copying memory with the DMA controller in the background.
+///
+/// The source and destination are cleaned and invalidated from the data cache
+/// before the transfer starts, and the destination again once it is waited
+/// for, so they must not be accessed in the meantime.
+///
+/// * `dst`: Destination range of physical addresses.
+/// * `src`: Source physical address.
+///
+/// Returns the transfer in progress, which must be waited for.
+pub fn start(dst: Range<usize>, src: usize) -> Transfer
+{
+    let len = dst.len();
+    assert!(len > 0 && len <= MAX_LEN, "DMA transfer of {len} bytes out of range");
+    assert!(src + len <= MAX_LEN && dst.end <= MAX_LEN,
+            "DMA transfer outside the first gigabyte of RAM");
+    let mut cb = CONTROL_BLOCK.lock();
+    *cb = ControlBlock { ti: TI_MEMCPY,
+                         source_ad: src as u32 | BUS_ALIAS,
+                         dest_ad: dst.start as u32 | BUS_ALIAS,
+                         txfr_len: len as u32,
+                         ..ControlBlock::new() };
+    let addr = addr_of!(*cb) as usize;
+    cache::clean_invalidate(addr .. addr + 32);
+    cache::clean_invalidate(src .. src + len);
+    cache::clean_invalidate(dst.clone());
+    unsafe {
+        DMA_ENABLE.write_volatile(DMA_ENABLE.read_volatile() | 1 << CHANNEL);
+        DMA_CS.write_volatile(CS_RESET);
+        while DMA_CS.read_volatile() & CS_RESET != 0 {
+            spin_loop()
+        }
+        DMA_CONBLK_AD.write_volatile(addr as u32 | BUS_ALIAS);
+        DMA_CS.write_volatile(CS_WAIT_FOR_OUTSTANDING_WRITES | CS_END | CS_ACTIVE);
+    }
+    Transfer { _cb: cb,
+               dst,
+               start: timer::now() }
+}
+
+impl Transfer
+{
+    /// Computes how much of the destination has been written so far.
+    ///
+    /// Returns the number of bytes written.
+    pub fn progress(&self) -> usize
+    {
+        if unsafe { DMA_CS.read_volatile() } & CS_ACTIVE == 0 {
+            return self.dst.len();
+        }
+        let addr = unsafe { DMA_DEST_AD.read_volatile() } & !BUS_ALIAS;
+        (addr as usize).saturating_sub(self.dst.start)
+    }
+
+    /// Polls the channel until the transfer completes.
+    ///
+    /// Returns an error if the transfer fails or times out, in which case the
+    /// channel is reset.
+    pub fn wait(self) -> Result<(), Error>
+    {
+        let timeout = timer::frequency() / 1000 * TIMEOUT_MSECS;
+        let res = loop {
+            let cs = unsafe { DMA_CS.read_volatile() };
+            if cs & CS_ERROR != 0 {
+                unsafe { DMA_CS.write_volatile(CS_RESET) };
+                break Err(Error::Bus);
+            }
+            if cs & CS_END != 0 && cs & CS_ACTIVE == 0 {
+                unsafe { DMA_CS.write_volatile(CS_END) };
+                break Ok(());
+            }
+            if timer::elapsed(self.start, timer::now()) >= timeout {
+                unsafe { DMA_CS.write_volatile(CS_RESET) };
+                break Err(Error::Timeout);
+            }
+            spin_loop()
+        };
+        cache::clean_invalidate(self.dst);
+        res
+    }
+}
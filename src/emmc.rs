@@ -0,0 +1,374 @@
+//! SD card driver.
+//!
+//! Drives the card slot through the EMMC2 SDHCI controller with programmed
+//! I/O, and only supports what it takes to initialize an SD card and read
+//! blocks from it.  Every wait has a timeout, and failures are reported as
+//! errors instead of panics, since a missing card is a perfectly normal
+//! situation for a board that boots from the network.
+//!
+//! Documentation:
+//!
+//! * [BCM2711 ARM Peripherals](https://datasheets.raspberrypi.com/bcm2711/bcm2711-peripherals.pdf)
+//!   1.2
+//! * [SD Host Controller Simplified Specification](https://www.sdcard.org/downloads/pls/)
+//! * [SD Physical Layer Simplified Specification](https://www.sdcard.org/downloads/pls/)
+
+use core::fmt::{Display, Formatter, Result as FormatResult};
+use core::hint::spin_loop;
+
+use crate::{mbox, timer, PERRY_RANGE};
+
+/// Base address of the EMMC2 controller registers.
+const EMMC_BASE: usize = 0x2340000 + PERRY_RANGE.start;
+/// Block size and count register.
+const EMMC_BLKSIZECNT: *mut u32 = (EMMC_BASE + 0x4) as _;
+/// Argument register.
+const EMMC_ARG1: *mut u32 = (EMMC_BASE + 0x8) as _;
+/// Command and transfer mode register.
+const EMMC_CMDTM: *mut u32 = (EMMC_BASE + 0xC) as _;
+/// First response register.
+const EMMC_RESP0: *const u32 = (EMMC_BASE + 0x10) as _;
+/// Data register.
+const EMMC_DATA: *const u32 = (EMMC_BASE + 0x20) as _;
+/// Status register.
+const EMMC_STATUS: *const u32 = (EMMC_BASE + 0x24) as _;
+/// Host, power, and block gap control register.
+const EMMC_CONTROL0: *mut u32 = (EMMC_BASE + 0x28) as _;
+/// Clock and reset control register.
+const EMMC_CONTROL1: *mut u32 = (EMMC_BASE + 0x2C) as _;
+/// Interrupt flags register.
+const EMMC_INTERRUPT: *mut u32 = (EMMC_BASE + 0x30) as _;
+/// Interrupt flag enable register.
+const EMMC_IRPT_MASK: *mut u32 = (EMMC_BASE + 0x34) as _;
+/// Interrupt generation enable register.
+const EMMC_IRPT_EN: *mut u32 = (EMMC_BASE + 0x38) as _;
+/// Command line busy status flag.
+const STATUS_CMD_INHIBIT: u32 = 0x1;
+/// Data lines busy status flag.
+const STATUS_DAT_INHIBIT: u32 = 0x2;
+/// Four bit data bus flag.
+const CONTROL0_DWIDTH4: u32 = 0x2;
+/// Bus power on at 3.3V.
+const CONTROL0_POWER_3V3: u32 = 0xF00;
+/// Internal clock enable flag.
+const CONTROL1_CLK_INTLEN: u32 = 0x1;
+/// Internal clock stable flag.
+const CONTROL1_CLK_STABLE: u32 = 0x2;
+/// Card clock enable flag.
+const CONTROL1_CLK_EN: u32 = 0x4;
+/// Maximum data timeout.
+const CONTROL1_DATA_TOUNIT_MAX: u32 = 0xE << 16;
+/// Host controller reset flag.
+const CONTROL1_SRST_HC: u32 = 0x1000000;
+/// Command line reset flag.
+const CONTROL1_SRST_CMD: u32 = 0x2000000;
+/// Command complete interrupt flag.
+const INTERRUPT_CMD_DONE: u32 = 0x1;
+/// Data transfer complete interrupt flag.
+const INTERRUPT_DATA_DONE: u32 = 0x2;
+/// Data ready to be read interrupt flag.
+const INTERRUPT_READ_RDY: u32 = 0x20;
+/// Error interrupt flag.
+const INTERRUPT_ERR: u32 = 0x8000;
+/// Command timeout error interrupt flag.
+const INTERRUPT_CTO_ERR: u32 = 0x10000;
+/// Block count enable transfer mode flag.
+const TM_BLKCNT_EN: u32 = 0x2;
+/// Automatic stop transmission transfer mode flag.
+const TM_AUTO_CMD12: u32 = 0x4;
+/// Card to host transfer mode flag.
+const TM_DAT_DIR_READ: u32 = 0x10;
+/// Multiple block transfer mode flag.
+const TM_MULTI_BLOCK: u32 = 0x20;
+/// 136 bit response command flag.
+const CMD_RSPNS_136: u32 = 0x10000;
+/// 48 bit response command flag.
+const CMD_RSPNS_48: u32 = 0x20000;
+/// 48 bit response with busy signaling command flag.
+const CMD_RSPNS_48_BUSY: u32 = 0x30000;
+/// Response CRC check command flag.
+const CMD_CRCCHK_EN: u32 = 0x80000;
+/// Response index check command flag.
+const CMD_IXCHK_EN: u32 = 0x100000;
+/// Data transfer command flag.
+const CMD_ISDATA: u32 = 0x200000;
+/// Go idle state command.
+const GO_IDLE_STATE: u32 = 0;
+/// All send card identification command.
+const ALL_SEND_CID: u32 = 2 << 24 | CMD_RSPNS_136 | CMD_CRCCHK_EN;
+/// Send relative address command.
+const SEND_RELATIVE_ADDR: u32 = 3 << 24 | CMD_RSPNS_48 | CMD_CRCCHK_EN | CMD_IXCHK_EN;
+/// Select card command.
+const SELECT_CARD: u32 = 7 << 24 | CMD_RSPNS_48_BUSY | CMD_CRCCHK_EN | CMD_IXCHK_EN;
+/// Send interface condition command.
+const SEND_IF_COND: u32 = 8 << 24 | CMD_RSPNS_48 | CMD_CRCCHK_EN | CMD_IXCHK_EN;
+/// Set block length command.
+const SET_BLOCKLEN: u32 = 16 << 24 | CMD_RSPNS_48 | CMD_CRCCHK_EN | CMD_IXCHK_EN;
+/// Read multiple blocks command.
+const READ_MULTIPLE_BLOCK: u32 = 18 << 24
+                                 | CMD_RSPNS_48
+                                 | CMD_CRCCHK_EN
+                                 | CMD_IXCHK_EN
+                                 | CMD_ISDATA
+                                 | TM_MULTI_BLOCK
+                                 | TM_DAT_DIR_READ
+                                 | TM_AUTO_CMD12
+                                 | TM_BLKCNT_EN;
+/// Application specific command prefix.
+const APP_CMD: u32 = 55 << 24 | CMD_RSPNS_48 | CMD_CRCCHK_EN | CMD_IXCHK_EN;
+/// Set bus width application command.
+const SET_BUS_WIDTH: u32 = 6 << 24 | CMD_RSPNS_48 | CMD_CRCCHK_EN | CMD_IXCHK_EN;
+/// Send operating condition application command, whose response carries no
+/// CRC nor index.
+const SD_SEND_OP_COND: u32 = 41 << 24 | CMD_RSPNS_48;
+/// Check pattern and supported voltage range of the interface condition.
+const IF_COND_3V3: u32 = 0x1AA;
+/// Operating condition requesting high capacity support at 3.2-3.4V.
+const OP_COND_HCS_3V3: u32 = 0x40300000;
+/// Card powered up flag of the operating condition.
+const OP_COND_READY: u32 = 0x80000000;
+/// Card capacity status flag of the operating condition.
+const OP_COND_CCS: u32 = 0x40000000;
+/// Size of a block in bytes.
+pub const BLOCK_SIZE: usize = 512;
+/// Maximum number of blocks per read command.
+const MAX_BLOCKS: usize = 0xFFFF;
+/// Clock rate used for identification in hertz.
+const IDENT_RATE: u32 = 400000;
+/// Clock rate used for data transfers in hertz.
+const TRANSFER_RATE: u32 = 25000000;
+/// Timeout of every command and of every block in milliseconds.
+const TIMEOUT_MSECS: usize = 500;
+/// Timeout of the card power up in milliseconds.
+const POWER_UP_MSECS: usize = 1000;
+/// Interval between polls of the card power up status in milliseconds.
+const POWER_UP_POLL_MSECS: u64 = 10;
+/// Time given to the card to see at least 74 cycles of a new clock before the
+/// next command, in microseconds.
+const CLOCK_SETTLE_USECS: u64 = 200;
+
+/// Initialized SD card.
+#[derive(Debug)]
+pub struct Card
+{
+    /// Whether the card is addressed in blocks rather than in bytes.
+    high_capacity: bool,
+}
+
+/// SD card errors.
+#[derive(Clone, Copy, Debug)]
+pub enum Error
+{
+    /// The firmware did not report the clock rate of the controller.
+    Clock,
+    /// The controller did not complete a reset or a clock change.
+    Controller,
+    /// No card answered the identification.
+    NoCard,
+    /// The card does not support the 3.3V interface.
+    Unsupported,
+    /// A command timed out.
+    Timeout(u32),
+    /// A command failed with the interrupt flags.
+    Command(u32, u32),
+}
+
+impl Card
+{
+    /// Initializes the controller and identifies and selects the card.
+    ///
+    /// Returns the initialized card, or an error if initialization fails.
+    pub fn new() -> Result<Self, Error>
+    {
+        let base = mbox::clock_rate(mbox::EMMC2_CLOCK).map_err(|_| Error::Clock)?;
+        unsafe {
+            EMMC_CONTROL1.write_volatile(CONTROL1_SRST_HC);
+            if !wait(EMMC_CONTROL1, CONTROL1_SRST_HC, 0) {
+                return Err(Error::Controller);
+            }
+            EMMC_CONTROL0.write_volatile(CONTROL0_POWER_3V3);
+            EMMC_IRPT_EN.write_volatile(0);
+            EMMC_IRPT_MASK.write_volatile(u32::MAX);
+            EMMC_INTERRUPT.write_volatile(u32::MAX);
+        }
+        set_clock(base, IDENT_RATE)?;
+        command(GO_IDLE_STATE, 0)?;
+        match command(SEND_IF_COND, IF_COND_3V3) {
+            Ok(resp) if resp & 0xFFF == IF_COND_3V3 => (),
+            Ok(_) => return Err(Error::Unsupported),
+            Err(Error::Timeout(_)) => return Err(Error::NoCard),
+            Err(err) => return Err(err),
+        }
+        let start = timer::now();
+        let cond = loop {
+            command(APP_CMD, 0)?;
+            let cond = command(SD_SEND_OP_COND, OP_COND_HCS_3V3)?;
+            if cond & OP_COND_READY != 0 {
+                break cond;
+            }
+            if timer::elapsed(start, timer::now()) >= timer::frequency() / 1000 * POWER_UP_MSECS {
+                return Err(Error::Timeout(SD_SEND_OP_COND));
+            }
+            timer::delay_ms(POWER_UP_POLL_MSECS);
+        };
+        command(ALL_SEND_CID, 0)?;
+        let rca = command(SEND_RELATIVE_ADDR, 0)? & 0xFFFF0000;
+        set_clock(base, TRANSFER_RATE)?;
+        command(SELECT_CARD, rca)?;
+        command(APP_CMD, rca)?;
+        command(SET_BUS_WIDTH, 0x2)?;
+        unsafe { EMMC_CONTROL0.write_volatile(EMMC_CONTROL0.read_volatile() | CONTROL0_DWIDTH4) };
+        let high_capacity = cond & OP_COND_CCS != 0;
+        if !high_capacity {
+            command(SET_BLOCKLEN, BLOCK_SIZE as u32)?;
+        }
+        Ok(Self { high_capacity })
+    }
+
+    /// Reads consecutive blocks from the card.
+    ///
+    /// * `lba`: Address of the first block to read.
+    /// * `buf`: Buffer to read into, whose length must be a multiple of
+    ///   [`BLOCK_SIZE`].
+    ///
+    /// Returns an error if reading fails.
+    pub fn read(&mut self, lba: u32, buf: &mut [u32]) -> Result<(), Error>
+    {
+        let words = BLOCK_SIZE / 4;
+        assert!(buf.len() % words == 0, "SD card read buffer not a whole number of blocks");
+        for (idx, chunk) in buf.chunks_mut(words * MAX_BLOCKS).enumerate() {
+            let lba = lba + (idx * MAX_BLOCKS) as u32;
+            let blocks = chunk.len() / words;
+            let arg = if self.high_capacity { lba } else { lba * BLOCK_SIZE as u32 };
+            unsafe { EMMC_BLKSIZECNT.write_volatile((blocks as u32) << 16 | BLOCK_SIZE as u32) };
+            command(READ_MULTIPLE_BLOCK, arg)?;
+            for block in chunk.chunks_mut(words) {
+                interrupt(READ_MULTIPLE_BLOCK, INTERRUPT_READ_RDY)?;
+                for word in block.iter_mut() {
+                    *word = unsafe { EMMC_DATA.read_volatile() };
+                }
+            }
+            interrupt(READ_MULTIPLE_BLOCK, INTERRUPT_DATA_DONE)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Error
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        match self {
+            Self::Clock => write!(fmt, "Failed to read the EMMC2 clock rate"),
+            Self::Controller => write!(fmt, "EMMC2 controller did not respond"),
+            Self::NoCard => write!(fmt, "No SD card found"),
+            Self::Unsupported => write!(fmt, "SD card does not support 3.3V operation"),
+            Self::Timeout(cmd) => write!(fmt, "SD card command {} timed out", cmd >> 24),
+            Self::Command(cmd, irpt) => write!(fmt, "SD card command {} failed with interrupt flags 0x{irpt:08X}", cmd >> 24),
+        }
+    }
+}
+
+/// Changes the rate of the card clock.
+///
+/// * `base`: Base clock rate of the controller in hertz.
+/// * `rate`: Maximum card clock rate in hertz.
+///
+/// Returns an error if the clock does not stabilize.
+fn set_clock(base: u32, rate: u32) -> Result<(), Error>
+{
+    let div = (base + rate * 2 - 1) / (rate * 2);
+    let div = (div & 0xFF) << 8 | (div >> 8 & 0x3) << 6;
+    unsafe {
+        EMMC_CONTROL1.write_volatile(EMMC_CONTROL1.read_volatile() & !CONTROL1_CLK_EN);
+        EMMC_CONTROL1.write_volatile(CONTROL1_DATA_TOUNIT_MAX | div | CONTROL1_CLK_INTLEN);
+        if !wait(EMMC_CONTROL1, CONTROL1_CLK_STABLE, CONTROL1_CLK_STABLE) {
+            return Err(Error::Controller);
+        }
+        EMMC_CONTROL1.write_volatile(EMMC_CONTROL1.read_volatile() | CONTROL1_CLK_EN);
+    }
+    timer::delay_us(CLOCK_SETTLE_USECS);
+    Ok(())
+}
+
+/// Sends a command to the card and waits for it to complete.
+///
+/// * `cmd`: Command and transfer mode.
+/// * `arg`: Command argument.
+///
+/// Returns the first word of the response, or an error if the command fails.
+fn command(cmd: u32, arg: u32) -> Result<u32, Error>
+{
+    let inhibit = if cmd & CMD_ISDATA != 0 || cmd & CMD_RSPNS_48_BUSY == CMD_RSPNS_48_BUSY {
+        STATUS_CMD_INHIBIT | STATUS_DAT_INHIBIT
+    } else {
+        STATUS_CMD_INHIBIT
+    };
+    unsafe {
+        if !wait(EMMC_STATUS.cast_mut(), inhibit, 0) {
+            return Err(Error::Timeout(cmd));
+        }
+        EMMC_INTERRUPT.write_volatile(u32::MAX);
+        EMMC_ARG1.write_volatile(arg);
+        EMMC_CMDTM.write_volatile(cmd);
+    }
+    interrupt(cmd, INTERRUPT_CMD_DONE)?;
+    Ok(unsafe { EMMC_RESP0.read_volatile() })
+}
+
+/// Waits for an interrupt flag to be raised and acknowledges it.
+///
+/// * `cmd`: Command being waited for.
+/// * `flag`: Interrupt flag to wait for.
+///
+/// Returns an error if the controller reports an error or the wait times out.
+fn interrupt(cmd: u32, flag: u32) -> Result<(), Error>
+{
+    let start = timer::now();
+    let timeout = timer::frequency() / 1000 * TIMEOUT_MSECS;
+    loop {
+        let irpt = unsafe { EMMC_INTERRUPT.read_volatile() };
+        if irpt & INTERRUPT_ERR != 0 {
+            unsafe {
+                EMMC_INTERRUPT.write_volatile(u32::MAX);
+                // A failed command leaves the command line to be reset.
+                EMMC_CONTROL1.write_volatile(EMMC_CONTROL1.read_volatile() | CONTROL1_SRST_CMD);
+                wait(EMMC_CONTROL1, CONTROL1_SRST_CMD, 0);
+            }
+            if irpt & INTERRUPT_CTO_ERR != 0 {
+                return Err(Error::Timeout(cmd));
+            }
+            return Err(Error::Command(cmd, irpt));
+        }
+        if irpt & flag != 0 {
+            unsafe { EMMC_INTERRUPT.write_volatile(flag) };
+            return Ok(());
+        }
+        if timer::elapsed(start, timer::now()) >= timeout {
+            return Err(Error::Timeout(cmd));
+        }
+        spin_loop()
+    }
+}
+
+/// Waits for some bits of a register to take a value.
+///
+/// * `reg`: Register to poll.
+/// * `mask`: Bits to check.
+/// * `val`: Expected value of the bits.
+///
+/// Returns whether the bits took the value before timing out.
+///
+/// The caller must make sure that `reg` is a valid register.
+unsafe fn wait(reg: *mut u32, mask: u32, val: u32) -> bool
+{
+    let start = timer::now();
+    let timeout = timer::frequency() / 1000 * TIMEOUT_MSECS;
+    while reg.read_volatile() & mask != val {
+        if timer::elapsed(start, timer::now()) >= timeout {
+            return false;
+        }
+        spin_loop()
+    }
+    true
+}
@@ -0,0 +1,215 @@
+//! Exception syndrome decoding.
+//!
+//! The exception class of a syndrome register is looked up in [`CLASSES`], and
+//! the fault status code of data and instruction aborts is decoded further
+//! into the kind of fault along with the translation table level that caused
+//! it, so that faults can be read without the reference manual at hand.  A
+//! few known encodings are checked at compile time.
+//!
+//! Documentation:
+//!
+//! * [Arm Architecture Reference Manual for A-profile architecture](https://developer.arm.com/documentation/ddi0487/latest)
+//!   D1.10, D19.2
+
+use core::fmt::{Display, Formatter, Result as FormatResult};
+
+/// Shift of the exception class field of the syndrome registers.
+const EC_SHIFT: usize = 26;
+/// Mask of the exception class field of the syndrome registers, after
+/// shifting.
+const EC_MASK: usize = 0x3F;
+/// Mask of the fault status code field of the syndrome registers for data and
+/// instruction aborts.
+const FSC_MASK: usize = 0x3F;
+/// Write not read flag of the syndrome registers for data aborts.
+const ISS_WNR: usize = 0x40;
+/// Exception class of instruction aborts from a lower exception level.
+const EC_IABT_LOWER: u8 = 0x20;
+/// Exception class of instruction aborts from the same exception level.
+const EC_IABT_SAME: u8 = 0x21;
+/// Exception class of data aborts from a lower exception level.
+const EC_DABT_LOWER: u8 = 0x24;
+/// Exception class of data aborts from the same exception level.
+const EC_DABT_SAME: u8 = 0x25;
+/// Names of the exception classes of exceptions taken from AArch64.
+const CLASSES: [(u8, &str); 24] = [(0x00, "Unknown reason or undefined instruction"),
+                                   (0x01, "Trapped WFI or WFE"),
+                                   (0x07, "FP/SIMD access not enabled"),
+                                   (0x0E, "Illegal execution state"),
+                                   (0x15, "SVC from AArch64"),
+                                   (0x16, "HVC from AArch64"),
+                                   (0x17, "SMC from AArch64"),
+                                   (0x18, "Trapped system register access"),
+                                   (0x19, "SVE access not enabled"),
+                                   (EC_IABT_LOWER, "Instruction Abort from a lower level"),
+                                   (EC_IABT_SAME, "Instruction Abort from the same level"),
+                                   (0x22, "PC alignment fault"),
+                                   (EC_DABT_LOWER, "Data Abort from a lower level"),
+                                   (EC_DABT_SAME, "Data Abort from the same level"),
+                                   (0x26, "SP alignment fault"),
+                                   (0x2C, "Trapped floating-point exception"),
+                                   (0x2F, "SError"),
+                                   (0x30, "Breakpoint from a lower level"),
+                                   (0x31, "Breakpoint from the same level"),
+                                   (0x32, "Software step from a lower level"),
+                                   (0x33, "Software step from the same level"),
+                                   (0x34, "Watchpoint from a lower level"),
+                                   (0x35, "Watchpoint from the same level"),
+                                   (0x3C, "BRK instruction")];
+
+const _: () = assert!(same(class_name(0x25), "Data Abort from the same level"));
+const _: () = assert!(same(class_name(0x07), "FP/SIMD access not enabled"));
+const _: () = assert!(same(class_name(0x3F), "Unknown exception class"));
+const _: () = assert!(matches!(Syndrome(0x96000045).fault(), Some(Fault::Translation(1))));
+const _: () = assert!(matches!(Syndrome(0x96000021).fault(), Some(Fault::Alignment)));
+const _: () = assert!(matches!(Syndrome(0x86000010).fault(), Some(Fault::External)));
+const _: () = assert!(Syndrome(0x96000045).fault().is_some() && Syndrome(0x96000045).is_write());
+const _: () = assert!(Syndrome(0x5E000000).fault().is_none());
+
+/// Value of an exception syndrome register, formatted for display as a
+/// description of the exception.
+#[derive(Clone, Copy, Debug)]
+pub struct Syndrome(pub usize);
+
+/// Decoded fault status code of a data or instruction abort.
+#[derive(Clone, Copy, Debug)]
+enum Fault
+{
+    /// Address size fault at a translation table level.
+    AddressSize(u8),
+    /// Translation fault at a translation table level.
+    Translation(u8),
+    /// Access flag fault at a translation table level.
+    AccessFlag(u8),
+    /// Permission fault at a translation table level.
+    Permission(u8),
+    /// Synchronous external abort not on a translation table walk.
+    External,
+    /// Synchronous external abort on a translation table walk at a level.
+    ExternalWalk(u8),
+    /// Alignment fault.
+    Alignment,
+    /// TLB conflict abort.
+    TlbConflict,
+    /// Fault status code without a description.
+    Other(u8),
+}
+
+impl Syndrome
+{
+    /// Returns the exception class.
+    const fn class(self) -> u8
+    {
+        (self.0 >> EC_SHIFT & EC_MASK) as u8
+    }
+
+    /// Returns whether the exception is a data abort.
+    const fn is_data_abort(self) -> bool
+    {
+        matches!(self.class(), EC_DABT_LOWER | EC_DABT_SAME)
+    }
+
+    /// Returns whether a data abort was caused by a write, which is
+    /// meaningless for other exceptions.
+    const fn is_write(self) -> bool
+    {
+        self.0 & ISS_WNR != 0
+    }
+
+    /// Decodes the fault status code.
+    ///
+    /// Returns the fault, or `None` if the exception is neither a data abort
+    /// nor an instruction abort.
+    const fn fault(self) -> Option<Fault>
+    {
+        if !matches!(self.class(), EC_IABT_LOWER | EC_IABT_SAME | EC_DABT_LOWER | EC_DABT_SAME) {
+            return None;
+        }
+        let fsc = (self.0 & FSC_MASK) as u8;
+        let level = fsc & 0x3;
+        Some(match fsc {
+            0x00 ..= 0x03 => Fault::AddressSize(level),
+            0x04 ..= 0x07 => Fault::Translation(level),
+            0x08 ..= 0x0B => Fault::AccessFlag(level),
+            0x0C ..= 0x0F => Fault::Permission(level),
+            0x10 => Fault::External,
+            0x14 ..= 0x17 => Fault::ExternalWalk(level),
+            0x21 => Fault::Alignment,
+            0x30 => Fault::TlbConflict,
+            _ => Fault::Other(fsc),
+        })
+    }
+}
+
+/// Looks up the name of an exception class.
+///
+/// * `class`: Exception class.
+///
+/// Returns the name of the exception class.
+const fn class_name(class: u8) -> &'static str
+{
+    let mut idx = 0;
+    while idx < CLASSES.len() {
+        if CLASSES[idx].0 == class {
+            return CLASSES[idx].1;
+        }
+        idx += 1;
+    }
+    "Unknown exception class"
+}
+
+/// Compares two strings at compile time.
+///
+/// * `lhs`: First string.
+/// * `rhs`: Second string.
+///
+/// Returns whether the strings are equal.
+const fn same(lhs: &str, rhs: &str) -> bool
+{
+    let (lhs, rhs) = (lhs.as_bytes(), rhs.as_bytes());
+    if lhs.len() != rhs.len() {
+        return false;
+    }
+    let mut idx = 0;
+    while idx < lhs.len() {
+        if lhs[idx] != rhs[idx] {
+            return false;
+        }
+        idx += 1;
+    }
+    true
+}
+
+impl Display for Syndrome
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        write!(fmt, "{}", class_name(self.class()))?;
+        let Some(fault) = self.fault() else {
+            return Ok(());
+        };
+        write!(fmt, ": {fault}")?;
+        if self.is_data_abort() {
+            write!(fmt, " on a {}", if self.is_write() { "write" } else { "read" })?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Fault
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        match self {
+            Self::AddressSize(level) => write!(fmt, "Address size fault level {level}"),
+            Self::Translation(level) => write!(fmt, "Translation fault level {level}"),
+            Self::AccessFlag(level) => write!(fmt, "Access flag fault level {level}"),
+            Self::Permission(level) => write!(fmt, "Permission fault level {level}"),
+            Self::External => write!(fmt, "Synchronous external abort"),
+            Self::ExternalWalk(level) => write!(fmt, "Synchronous external abort on translation table walk level {level}"),
+            Self::Alignment => write!(fmt, "Alignment fault"),
+            Self::TlbConflict => write!(fmt, "TLB conflict abort"),
+            Self::Other(fsc) => write!(fmt, "Fault status 0x{fsc:02X}"),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! Framebuffer.
+//!
+//! The framebuffer is allocated by the firmware out of the memory reserved for
+//! the VideoCore, which scans it out to the display without going through the
+//! ARM caches, so it is mapped as normal non-cacheable memory for the CPU
+//! writes to show up on screen.
+//!
+//! Documentation:
+//!
+//! * [Mailbox property interface](https://github.com/raspberrypi/firmware/wiki/Mailbox-property-interface)
+
+use core::ops::Range;
+
+use crate::mbox::{self, MAILBOX};
+use crate::mmu::{self, Attributes, Shareability, BLOCK_SIZE};
+use crate::sync::Lazy;
+
+/// Width of the framebuffer in pixels.
+const WIDTH: usize = 1024;
+/// Height of the framebuffer in pixels.
+const HEIGHT: usize = 768;
+/// Depth of the framebuffer in bits per pixel.
+const DEPTH: usize = 32;
+/// Required alignment of the framebuffer in bytes.
+const ALIGNMENT: u32 = 0x1000;
+/// Set physical width and height tag.
+const SET_PHYSICAL_SIZE: u32 = 0x00048003;
+/// Set virtual width and height tag.
+const SET_VIRTUAL_SIZE: u32 = 0x00048004;
+/// Set depth tag.
+const SET_DEPTH: u32 = 0x00048005;
+/// Allocate buffer tag.
+const ALLOCATE_BUFFER: u32 = 0x00040001;
+/// Get pitch tag.
+const GET_PITCH: u32 = 0x00040008;
+/// Mask that strips the cache alias from a VideoCore bus address, leaving the
+/// ARM physical address.
+const BUS_MASK: u32 = 0x3FFFFFFF;
+
+/// Framebuffer allocated on first use.
+static FRAMEBUFFER: Lazy<Result<Framebuffer, mbox::Error>> = Lazy::new(Framebuffer::new);
+
+/// Framebuffer allocated by the firmware.
+#[derive(Clone, Debug)]
+pub struct Framebuffer
+{
+    /// Range of physical addresses covered by the pixels.
+    range: Range<usize>,
+    /// Width in pixels.
+    width: usize,
+    /// Height in pixels.
+    height: usize,
+    /// Distance between the starts of consecutive rows in bytes.
+    pitch: usize,
+}
+
+/// Returns the framebuffer, or an error if the firmware failed to allocate
+/// it.
+pub fn get() -> Result<Framebuffer, mbox::Error>
+{
+    FRAMEBUFFER.clone()
+}
+
+impl Framebuffer
+{
+    /// Asks the firmware to allocate a new framebuffer and maps it.
+    ///
+    /// Returns the newly allocated framebuffer, or an error if the firmware
+    /// rejects the configuration.
+    fn new() -> Result<Self, mbox::Error>
+    {
+        let mut physical = [WIDTH as u32, HEIGHT as u32];
+        let mut virt = [WIDTH as u32, HEIGHT as u32];
+        let mut depth = [DEPTH as u32];
+        let mut buffer = [ALIGNMENT, 0];
+        let mut pitch = [0];
+        MAILBOX.lock().call_tags(&mut [(SET_PHYSICAL_SIZE, &mut physical),
+                                       (SET_VIRTUAL_SIZE, &mut virt),
+                                       (SET_DEPTH, &mut depth),
+                                       (ALLOCATE_BUFFER, &mut buffer),
+                                       (GET_PITCH, &mut pitch)])?;
+        if buffer[0] == 0 || depth[0] != DEPTH as u32 {
+            return Err(mbox::Error::Tag);
+        }
+        let start = (buffer[0] & BUS_MASK) as usize;
+        let range = start .. start + buffer[1] as usize;
+        let block = range.start & !(BLOCK_SIZE - 1) .. (range.end + BLOCK_SIZE - 1) & !(BLOCK_SIZE - 1);
+        mmu::map(block, Attributes::NonCacheable, Shareability::Outer);
+        Ok(Self { range,
+                  width: virt[0] as usize,
+                  height: virt[1] as usize,
+                  pitch: pitch[0] as usize })
+    }
+
+    /// Returns the range of physical addresses covered by the pixels.
+    pub fn range(&self) -> Range<usize>
+    {
+        self.range.clone()
+    }
+
+    /// Fills the framebuffer with a gradient that goes from black to red
+    /// horizontally and to green vertically.
+    pub fn gradient(&self)
+    {
+        for y in 0 .. self.height {
+            let row = (self.range.start + y * self.pitch) as *mut u32;
+            let green = (y * 0xFF / self.height) as u32;
+            for x in 0 .. self.width {
+                let red = (x * 0xFF / self.width) as u32;
+                unsafe { row.add(x).write_volatile(red << 16 | green << 8) };
+            }
+        }
+    }
+}
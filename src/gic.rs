@@ -0,0 +1,316 @@
+//! GIC-400 interrupt controller driver, with a backend for the legacy
+//! interrupt controllers of the Pi 3 behind the same interface.
+//!
+//! The firmware places all the interrupts in the non-secure group, so only the
+//! non-secure views of the distributor and the CPU interfaces are programmed,
+//! and all the shared peripheral interrupts are routed to core #0 at the same
+//! priority as the software generated interrupts that the cores send each
+//! other.
+//!
+//! The Pi 3 has no GIC, so its backend routes the interrupts of the
+//! peripherals to core #0 through the ARM local interrupt controller and
+//! implements the software generated interrupts with the mailboxes of the
+//! cores, of which there are only four per core.  The peripheral interrupts
+//! keep the identifiers that they have at the GIC of the Pi 4, so the callers
+//! don't need to tell the boards apart.
+//!
+//! Documentation:
+//!
+//! * [BCM2711 ARM Peripherals](https://datasheets.raspberrypi.com/bcm2711/bcm2711-peripherals.pdf)
+//!   6.3
+//! * [ARM Generic Interrupt Controller Architecture Specification version 2.0](https://developer.arm.com/documentation/ihi0048/latest)
+//!   4
+//! * [BCM2835 ARM Peripherals](https://datasheets.raspberrypi.com/bcm2835/bcm2835-peripherals.pdf)
+//!   7.5
+//! * [BCM2836 ARM-local peripherals](https://datasheets.raspberrypi.com/bcm2836/bcm2836-peripherals.pdf)
+//!   4
+
+use core::arch::asm;
+#[cfg(feature = "pi3")]
+use core::sync::atomic::{AtomicU32, Ordering};
+
+#[cfg(feature = "pi3")]
+use crate::core_index;
+use crate::PERRY_RANGE;
+
+/// Base address of the distributor registers.
+#[cfg(not(feature = "pi3"))]
+const GICD_BASE: usize = 0x3841000 + PERRY_RANGE.start;
+/// Distributor control register.
+#[cfg(not(feature = "pi3"))]
+const GICD_CTLR: *mut u32 = GICD_BASE as _;
+/// First interrupt set-enable register.
+#[cfg(not(feature = "pi3"))]
+const GICD_ISENABLER: *mut u32 = (GICD_BASE + 0x100) as _;
+/// First interrupt priority register, which is byte accessible.
+#[cfg(not(feature = "pi3"))]
+const GICD_IPRIORITYR: *mut u8 = (GICD_BASE + 0x400) as _;
+/// First interrupt processor targets register, which is byte accessible.
+#[cfg(not(feature = "pi3"))]
+const GICD_ITARGETSR: *mut u8 = (GICD_BASE + 0x800) as _;
+/// Software generated interrupt register.
+#[cfg(not(feature = "pi3"))]
+const GICD_SGIR: *mut u32 = (GICD_BASE + 0xF00) as _;
+/// Base address of the CPU interface registers.
+#[cfg(not(feature = "pi3"))]
+const GICC_BASE: usize = 0x3842000 + PERRY_RANGE.start;
+/// CPU interface control register.
+#[cfg(not(feature = "pi3"))]
+const GICC_CTLR: *mut u32 = GICC_BASE as _;
+/// Interrupt priority mask register.
+#[cfg(not(feature = "pi3"))]
+const GICC_PMR: *mut u32 = (GICC_BASE + 0x4) as _;
+/// Interrupt acknowledge register.
+#[cfg(not(feature = "pi3"))]
+const GICC_IAR: *const u32 = (GICC_BASE + 0xC) as _;
+/// End of interrupt register.
+#[cfg(not(feature = "pi3"))]
+const GICC_EOIR: *mut u32 = (GICC_BASE + 0x10) as _;
+/// Priority of all the interrupts.
+#[cfg(not(feature = "pi3"))]
+const PRIORITY: u8 = 0xA0;
+/// Priority mask that lets all the interrupts through.
+#[cfg(not(feature = "pi3"))]
+const PRIORITY_MASK: u32 = 0xF0;
+/// Identifier returned when no interrupt is pending.
+#[cfg(not(feature = "pi3"))]
+const SPURIOUS: u32 = 1023;
+/// Mask of the interrupt identifier field of the acknowledge register.
+#[cfg(not(feature = "pi3"))]
+const IAR_ID: u32 = 0x3FF;
+/// Mask of the requesting core field of the acknowledge register, which is
+/// only set for software generated interrupts.
+#[cfg(not(feature = "pi3"))]
+const IAR_SOURCE: u32 = 0x1C00;
+/// Number of software generated interrupts.
+#[cfg(not(feature = "pi3"))]
+const SGI_COUNT: usize = 16;
+/// Shift of the target list field of the software generated interrupt
+/// register.
+#[cfg(not(feature = "pi3"))]
+const SGIR_TARGETS_SHIFT: usize = 16;
+/// Base address of the ARM local peripherals of the Pi 3.
+#[cfg(feature = "pi3")]
+const LOCAL_BASE: usize = 0x3000000 + PERRY_RANGE.start;
+/// GPU interrupt routing register.
+#[cfg(feature = "pi3")]
+const LOCAL_GPU_ROUTING: *mut u32 = (LOCAL_BASE + 0xC) as _;
+/// Mailbox interrupt control register of core #0, followed by those of the
+/// other cores.
+#[cfg(feature = "pi3")]
+const LOCAL_MAILBOX_CONTROL: *mut u32 = (LOCAL_BASE + 0x50) as _;
+/// Interrupt source register of core #0, followed by those of the other
+/// cores.
+#[cfg(feature = "pi3")]
+const LOCAL_IRQ_SOURCE: *const u32 = (LOCAL_BASE + 0x60) as _;
+/// Write-set register of mailbox #0 of core #0, followed by those of the other
+/// mailboxes and then by those of the other cores.
+#[cfg(feature = "pi3")]
+const LOCAL_MAILBOX_SET: *mut u32 = (LOCAL_BASE + 0x80) as _;
+/// Read and write-clear register of mailbox #0 of core #0, laid out like
+/// [`LOCAL_MAILBOX_SET`].
+#[cfg(feature = "pi3")]
+const LOCAL_MAILBOX_CLEAR: *mut u32 = (LOCAL_BASE + 0xC0) as _;
+/// Number of mailboxes of every core.
+#[cfg(feature = "pi3")]
+const MAILBOX_COUNT: usize = 4;
+/// Shift of the mailbox flags in the interrupt source registers.
+#[cfg(feature = "pi3")]
+const SOURCE_MAILBOX_SHIFT: u32 = 4;
+/// GPU interrupt flag of the interrupt source registers.
+#[cfg(feature = "pi3")]
+const SOURCE_GPU: u32 = 0x100;
+/// Pending register of the first 32 peripheral interrupts at the legacy
+/// interrupt controller, followed by that of the other 32.
+#[cfg(feature = "pi3")]
+const IRQ_PENDING: *const u32 = (0x200B204 + PERRY_RANGE.start) as _;
+/// Enable register of the first 32 peripheral interrupts at the legacy
+/// interrupt controller, followed by that of the other 32.
+#[cfg(feature = "pi3")]
+const IRQ_ENABLE: *mut u32 = (0x200B210 + PERRY_RANGE.start) as _;
+/// Identifier of the first peripheral interrupt at the GIC of the Pi 4.
+#[cfg(feature = "pi3")]
+const PERIPHERAL_BASE: u32 = 96;
+
+/// Peripheral interrupts enabled so far, as the pending registers of the
+/// legacy interrupt controller also report those routed to the VideoCore.
+#[cfg(feature = "pi3")]
+static ENABLED: [AtomicU32; 2] = [const { AtomicU32::new(0) }; 2];
+
+/// Interrupt acknowledged by a CPU interface.
+#[derive(Clone, Copy, Debug)]
+pub struct Interrupt(u32);
+
+/// Enables the distributor and the CPU interface of the calling core.
+///
+/// Must be called from core #0 before any interrupt is enabled.
+#[cfg(not(feature = "pi3"))]
+pub fn init()
+{
+    unsafe { GICD_CTLR.write_volatile(0x1) };
+    init_core();
+}
+
+/// Routes the peripheral interrupts to core #0 and enables the mailbox
+/// interrupts of the calling core.
+///
+/// Must be called from core #0 before any interrupt is enabled.
+#[cfg(feature = "pi3")]
+pub fn init()
+{
+    unsafe { LOCAL_GPU_ROUTING.write_volatile(0) };
+    init_core();
+}
+
+/// Enables the CPU interface of the calling core along with the software
+/// generated interrupts, whose registers are banked per core.
+#[cfg(not(feature = "pi3"))]
+pub fn init_core()
+{
+    unsafe {
+        for idx in 0 .. SGI_COUNT {
+            GICD_IPRIORITYR.add(idx).write_volatile(PRIORITY);
+        }
+        GICD_ISENABLER.write_volatile((1 << SGI_COUNT) - 1);
+        GICC_PMR.write_volatile(PRIORITY_MASK);
+        GICC_CTLR.write_volatile(0x1);
+    }
+}
+
+/// Enables the mailbox interrupts of the calling core, which stand in for
+/// the software generated interrupts.
+#[cfg(feature = "pi3")]
+pub fn init_core()
+{
+    let core = core_index();
+    unsafe { LOCAL_MAILBOX_CONTROL.add(core).write_volatile((1 << MAILBOX_COUNT) - 1) };
+}
+
+/// Enables a shared peripheral interrupt and routes it to core #0.
+///
+/// * `id`: Interrupt identifier.
+#[cfg(not(feature = "pi3"))]
+pub fn enable(id: u32)
+{
+    let idx = id as usize;
+    unsafe {
+        GICD_IPRIORITYR.add(idx).write_volatile(PRIORITY);
+        GICD_ITARGETSR.add(idx).write_volatile(0x1);
+        GICD_ISENABLER.add(idx / 32).write_volatile(1 << (idx % 32));
+    }
+}
+
+/// Enables a peripheral interrupt, which [`init`] routed to core #0.
+///
+/// * `id`: Interrupt identifier at the GIC of the Pi 4.
+#[cfg(feature = "pi3")]
+pub fn enable(id: u32)
+{
+    let idx = (id - PERIPHERAL_BASE) as usize;
+    ENABLED[idx / 32].fetch_or(1 << (idx % 32), Ordering::Relaxed);
+    unsafe { IRQ_ENABLE.add(idx / 32).write_volatile(1 << (idx % 32)) };
+}
+
+/// Sends a software generated interrupt to a core.
+///
+/// * `id`: Software generated interrupt identifier, which must be lower than
+///   16.
+/// * `core`: Logical index of the target core.
+#[cfg(not(feature = "pi3"))]
+pub fn send(id: u32, core: usize)
+{
+    unsafe {
+        asm!("dsb ishst", options (nostack, preserves_flags));
+        GICD_SGIR.write_volatile(1 << (SGIR_TARGETS_SHIFT + core) | id);
+    }
+}
+
+/// Sends a software generated interrupt to a core through its mailbox of the
+/// same index.
+///
+/// * `id`: Software generated interrupt identifier, which must be lower than
+///   4.
+/// * `core`: Logical index of the target core.
+#[cfg(feature = "pi3")]
+pub fn send(id: u32, core: usize)
+{
+    unsafe {
+        asm!("dsb ishst", options (nostack, preserves_flags));
+        LOCAL_MAILBOX_SET.add(core * MAILBOX_COUNT + id as usize).write_volatile(0x1);
+    }
+}
+
+/// Acknowledges the highest priority pending interrupt on the calling core.
+///
+/// Returns the interrupt, which must be passed to [`end`] once handled, or
+/// `None` if no interrupt is pending.
+#[cfg(not(feature = "pi3"))]
+pub fn acknowledge() -> Option<Interrupt>
+{
+    let iar = unsafe { GICC_IAR.read_volatile() } & (IAR_ID | IAR_SOURCE);
+    (iar & IAR_ID != SPURIOUS).then_some(Interrupt(iar))
+}
+
+/// Acknowledges the pending interrupt on the calling core with the lowest
+/// identifier, clearing its mailbox if it's a software generated interrupt.
+///
+/// Returns the interrupt, which must be passed to [`end`] once handled, or
+/// `None` if no interrupt is pending.
+#[cfg(feature = "pi3")]
+pub fn acknowledge() -> Option<Interrupt>
+{
+    let core = core_index();
+    let source = unsafe { LOCAL_IRQ_SOURCE.add(core).read_volatile() };
+    let mailboxes = source >> SOURCE_MAILBOX_SHIFT & ((1 << MAILBOX_COUNT) - 1);
+    if mailboxes != 0 {
+        let id = mailboxes.trailing_zeros();
+        unsafe { LOCAL_MAILBOX_CLEAR.add(core * MAILBOX_COUNT + id as usize).write_volatile(u32::MAX) };
+        return Some(Interrupt(id));
+    }
+    if source & SOURCE_GPU == 0 {
+        return None;
+    }
+    for (bank, enabled) in ENABLED.iter().enumerate() {
+        let pending = unsafe { IRQ_PENDING.add(bank).read_volatile() } & enabled.load(Ordering::Relaxed);
+        if pending != 0 {
+            return Some(Interrupt(PERIPHERAL_BASE + bank as u32 * 32 + pending.trailing_zeros()));
+        }
+    }
+    None
+}
+
+/// Signals the end of the handling of an interrupt on the calling core.
+///
+/// * `irq`: Interrupt returned by [`acknowledge`].
+#[cfg(not(feature = "pi3"))]
+pub fn end(irq: Interrupt)
+{
+    unsafe { GICC_EOIR.write_volatile(irq.0) };
+}
+
+/// Signals the end of the handling of an interrupt on the calling core, which
+/// is a no-op since the mailboxes are cleared when acknowledged and the
+/// peripherals stop requesting their interrupts once handled.
+///
+/// * `_irq`: Interrupt returned by [`acknowledge`].
+#[cfg(feature = "pi3")]
+pub fn end(_irq: Interrupt)
+{
+}
+
+impl Interrupt
+{
+    /// Returns the interrupt identifier.
+    #[cfg(not(feature = "pi3"))]
+    pub fn id(self) -> u32
+    {
+        self.0 & IAR_ID
+    }
+
+    /// Returns the interrupt identifier.
+    #[cfg(feature = "pi3")]
+    pub fn id(self) -> u32
+    {
+        self.0
+    }
+}
@@ -0,0 +1,65 @@
+//! GPIO driver.
+//!
+//! Documentation:
+//!
+//! * [BCM2711 ARM Peripherals](https://datasheets.raspberrypi.com/bcm2711/bcm2711-peripherals.pdf)
+//!   5
+
+use crate::PERRY_RANGE;
+
+/// Base address of the GPIO registers.
+const GPIO_BASE: usize = 0x2200000 + PERRY_RANGE.start;
+/// First function selection register.
+const GPIO_FSEL0: *mut u32 = GPIO_BASE as _;
+/// First output set register.
+const GPIO_SET0: *mut u32 = (GPIO_BASE + 0x1C) as _;
+/// First output clear register.
+const GPIO_CLR0: *mut u32 = (GPIO_BASE + 0x28) as _;
+/// Number of GPIO pins.
+const PIN_COUNT: usize = 58;
+
+/// Pin function.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u32)]
+pub enum Function
+{
+    /// General purpose input.
+    Input = 0,
+    /// General purpose output.
+    Output = 1,
+}
+
+/// Selects the function of a pin.
+///
+/// * `pin`: Pin to configure.
+/// * `function`: Function to select.
+pub fn select(pin: usize, function: Function)
+{
+    assert!(pin < PIN_COUNT, "GPIO pin {pin} out of range");
+    let shift = pin % 10 * 3;
+    unsafe {
+        let reg = GPIO_FSEL0.add(pin / 10);
+        let val = reg.read_volatile();
+        reg.write_volatile(val & !(0x7 << shift) | (function as u32) << shift);
+    }
+}
+
+/// Drives an output pin high.
+///
+/// * `pin`: Pin to drive.
+#[inline(always)]
+pub fn set(pin: usize)
+{
+    assert!(pin < PIN_COUNT, "GPIO pin {pin} out of range");
+    unsafe { GPIO_SET0.add(pin / 32).write_volatile(1 << (pin % 32)) };
+}
+
+/// Drives an output pin low.
+///
+/// * `pin`: Pin to drive.
+#[inline(always)]
+pub fn clear(pin: usize)
+{
+    assert!(pin < PIN_COUNT, "GPIO pin {pin} out of range");
+    unsafe { GPIO_CLR0.add(pin / 32).write_volatile(1 << (pin % 32)) };
+}
@@ -0,0 +1,79 @@
+//! Hypervisor calls.
+//!
+//! When the firmware boots the kernel at EL2, the boot code enables the MMU at
+//! EL2 with the same translation tables and memory attributes as EL1 before
+//! dropping to EL1, along with the same caches, alignment checks, and lack of
+//! implicit execute-never on writable memory in the system control register,
+//! whose remaining EL1 bits only concern EL0.  The exception vector then runs
+//! the functions passed to [`call`] at EL2 on the stack of the caller, so that
+//! code runs the same at either level, except that the EL2 TLB entries must
+//! be invalidated with [`invalidate_el2`](crate::mmu::invalidate_el2) after
+//! remapping memory.  Interrupts are never taken at EL2, however, so the
+//! functions must not wait for anything that an interrupt handler does, such
+//! as output being drained.
+//!
+//! Documentation:
+//!
+//! * [Arm Architecture Reference Manual for A-profile architecture](https://developer.arm.com/documentation/ddi0487/latest)
+//!   D1.3, D1.10
+
+use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Exception level at which the firmware booted the kernel.
+static BOOT_LEVEL: AtomicUsize = AtomicUsize::new(0);
+
+/// Records the exception level at which the firmware booted the kernel.
+///
+/// * `level`: Exception level passed by the boot code.
+pub fn init(level: usize)
+{
+    BOOT_LEVEL.store(level, Ordering::Relaxed);
+}
+
+/// Returns the exception level at which the firmware booted the kernel.
+pub fn boot_level() -> usize
+{
+    BOOT_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Checks whether functions can be called at EL2.
+///
+/// Returns whether the firmware booted the kernel at EL2.
+pub fn available() -> bool
+{
+    boot_level() == 2
+}
+
+/// Calls a function at EL2.
+///
+/// Must only be called if [`available`] returns `true`.
+///
+/// * `func`: Function to call.
+/// * `arg`: Argument to pass to the function.
+///
+/// Returns the result of the function.
+pub fn call(func: extern "C" fn(usize) -> usize, arg: usize) -> usize
+{
+    assert!(available(), "The kernel was not booted at EL2");
+    let res: usize;
+    unsafe {
+        asm!(
+            "hvc #0",
+            inout ("x0") func as usize => res,
+            in ("x1") arg,
+            clobber_abi ("C")
+        );
+    }
+    res
+}
+
+/// Reads the exception level of the calling code.
+///
+/// Returns the current exception level.
+pub fn current_level() -> usize
+{
+    let el: usize;
+    unsafe { asm!("mrs {el}, currentel", el = out (reg) el, options (nomem, nostack, preserves_flags)) };
+    el >> 2
+}
@@ -0,0 +1,144 @@
+//! Implementation defined registers of the Cortex-A72.
+//!
+//! Accessing the implementation defined registers at EL1 is undefined unless
+//! every higher exception level allows it in its auxiliary control register,
+//! and their layouts differ between cores, so they're only accessed on the
+//! Cortex-A72 and every access is probed: the address to resume at is placed
+//! in TPIDR_EL1 right before the access, and the exception vector resumes
+//! there instead of reporting a fault if the access is trapped.
+//!
+//! Documentation:
+//!
+//! * [Arm Cortex-A72 MPCore Processor Technical Reference Manual](https://developer.arm.com/documentation/100095/latest)
+//!   4.3.65, 4.3.67
+
+use core::arch::asm;
+
+use crate::hyp;
+
+/// Number of settings of the L2 data prefetch distance.
+pub const L2_DISTANCES: u64 = 4;
+/// Part number of the Cortex-A72 found in the BCM2711.
+const CORTEX_A72: u64 = 0xD08;
+/// CPU auxiliary and extended control registers access flags of the auxiliary
+/// control register at EL2.
+const ACTLR_CPU: u64 = 0x3;
+/// Shift of the L2 load and store data prefetch distance field of the CPU
+/// extended control register.
+const CPUECTLR_L2_DISTANCE_SHIFT: u64 = 32;
+/// Mask of the L2 load and store data prefetch distance field of the CPU
+/// extended control register.
+const CPUECTLR_L2_DISTANCE_MASK: u64 = 0x3 << CPUECTLR_L2_DISTANCE_SHIFT;
+
+/// Allows EL1 to access the CPU auxiliary and extended control registers of
+/// the calling core if the kernel was booted at EL2, leaving the decision to
+/// the firmware at EL3 otherwise.
+pub fn allow()
+{
+    if hyp::available() {
+        hyp::call(allow_el1, 0);
+    }
+}
+
+/// Reads the setting of the L2 load and store data prefetch distance of the
+/// calling core, where higher settings prefetch further ahead.
+///
+/// Returns the setting, or `None` if the calling core is not a Cortex-A72 or
+/// EL1 is not allowed to read it.
+pub fn l2_distance() -> Option<u64>
+{
+    cpuectlr().map(|cpuectlr| (cpuectlr & CPUECTLR_L2_DISTANCE_MASK) >> CPUECTLR_L2_DISTANCE_SHIFT)
+}
+
+/// Changes the setting of the L2 load and store data prefetch distance of the
+/// calling core.
+///
+/// * `setting`: Setting, which must be lower than [`L2_DISTANCES`].
+///
+/// Returns whether the setting was changed, which fails if the calling core is
+/// not a Cortex-A72 or EL1 is not allowed to change it.
+pub fn set_l2_distance(setting: u64) -> bool
+{
+    assert!(setting < L2_DISTANCES, "Invalid L2 prefetch distance setting: {setting}");
+    let Some(cpuectlr) = cpuectlr() else {
+        return false;
+    };
+    let cpuectlr = cpuectlr & !CPUECTLR_L2_DISTANCE_MASK | setting << CPUECTLR_L2_DISTANCE_SHIFT;
+    let done: u64;
+    unsafe {
+        asm!(
+            "mrs {daif}, daif",
+            "msr daifset, #0x2",
+            "adr {tmp}, 0f",
+            "msr tpidr_el1, {tmp}",
+            "mov {done}, xzr",
+            "msr s3_1_c15_c2_1, {val}", // CPUECTLR_EL1.
+            "isb",
+            "mov {done}, #1",
+            "0:",
+            "msr tpidr_el1, xzr",
+            "msr daif, {daif}",
+            val = in (reg) cpuectlr,
+            tmp = out (reg) _,
+            daif = out (reg) _,
+            done = out (reg) done,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+    done != 0
+}
+
+/// Probes the CPU extended control register of the calling core.
+///
+/// Returns the value of the register, or `None` if the calling core is not a
+/// Cortex-A72 or EL1 is not allowed to read it.
+fn cpuectlr() -> Option<u64>
+{
+    let midr: u64;
+    unsafe { asm!("mrs {midr}, midr_el1", midr = out (reg) midr, options (nomem, nostack, preserves_flags)) };
+    if midr >> 4 & 0xFFF != CORTEX_A72 {
+        return None;
+    }
+    let (val, done): (u64, u64);
+    unsafe {
+        asm!(
+            "mrs {daif}, daif",
+            "msr daifset, #0x2",
+            "adr {tmp}, 0f",
+            "msr tpidr_el1, {tmp}",
+            "mov {done}, xzr",
+            "mov {val}, xzr",
+            "mrs {val}, s3_1_c15_c2_1", // CPUECTLR_EL1.
+            "mov {done}, #1",
+            "0:",
+            "msr tpidr_el1, xzr",
+            "msr daif, {daif}",
+            val = out (reg) val,
+            tmp = out (reg) _,
+            daif = out (reg) _,
+            done = out (reg) done,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+    (done != 0).then_some(val)
+}
+
+/// Sets the CPU auxiliary and extended control registers access flags of the
+/// auxiliary control register at EL2.
+///
+/// Returns nothing meaningful.
+extern "C" fn allow_el1(_: usize) -> usize
+{
+    unsafe {
+        asm!(
+            "mrs {tmp}, actlr_el2",
+            "orr {tmp}, {tmp}, {cpu}",
+            "msr actlr_el2, {tmp}",
+            "isb",
+            tmp = out (reg) _,
+            cpu = in (reg) ACTLR_CPU,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+    0
+}
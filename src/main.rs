@@ -3,104 +3,306 @@
 
 #![feature(panic_info_message)]
 
+mod bench;
+mod cache;
+mod cli;
+mod dma;
+mod emmc;
+mod exception;
+mod fb;
+mod gic;
+mod gpio;
+mod hyp;
+mod impdef;
+mod mbox;
+mod memtest;
+mod menu;
+mod mmu;
+mod pmu;
+mod psci;
+mod ram;
+mod rng;
+mod smp;
+mod stream;
+mod sve;
 mod sync;
+mod systimer;
+mod timer;
 mod uart;
+mod user;
+mod watchdog;
 
 use core::arch::{asm, global_asm};
-use core::fmt::Write;
-use core::mem::MaybeUninit;
+use core::fmt::{Display, Formatter, Result as FormatResult, Write};
 use core::ops::Range;
 use core::panic::PanicInfo;
+use core::mem::{offset_of, size_of};
+use core::ptr::addr_of_mut;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use core::write;
 
-use self::uart::UART;
+use self::exception::Syndrome;
+use self::uart::{Uart, UART};
 
-/// Peripherals range.
+#[cfg(all(feature = "pi3", feature = "pi4"))]
+compile_error!("The pi3 and pi4 features select different boards and cannot be enabled together");
+
+/// Peripherals range, which is where the boot code maps the 64MB of physical
+/// addresses starting at [`PERRY_BASE`].
 const PERRY_RANGE: Range<usize> = 0x80000000 .. 0x84000000;
+/// Physical address of the peripherals of the Pi 4 mapped at [`PERRY_RANGE`],
+/// in the low peripheral mode that the firmware boots in, which puts the main
+/// peripherals at 0xFE000000.
+///
+/// The Pi 4 is the default board, and the `pi4` feature only exists to select
+/// it explicitly.
+#[cfg(not(feature = "pi3"))]
+const PERRY_BASE: usize = 0xFC000000;
+/// Physical address of the peripherals of the Pi 3 mapped at [`PERRY_RANGE`],
+/// which starts 32MB below the main peripherals at 0x3F000000 so that they
+/// line up with those of the Pi 4 and the ARM local peripherals at 0x40000000
+/// are mapped as well.
+///
+/// Selected with the `pi3` feature, for instance with `./build --cfg
+/// 'feature="pi3"'`.  Only the peripherals shared by both SoCs work, besides
+/// the interrupt controllers, since the EMMC2 controller and the Cortex-A72
+/// controls only exist on the Pi 4.
+#[cfg(feature = "pi3")]
+const PERRY_BASE: usize = 0x3D000000;
+/// End of the physical addresses that can be mapped as RAM on the Pi 4, which
+/// is the end of the first gigabyte covered by the static translation table.
+#[cfg(not(feature = "pi3"))]
+const RAM_END: usize = 0x40000000;
+/// End of the physical addresses that can be mapped as RAM on the Pi 3, where
+/// the peripherals take the top of the first gigabyte.
+#[cfg(feature = "pi3")]
+const RAM_END: usize = 0x3F000000;
+/// GPIO pin driving the activity LED of the Pi 4.
+#[cfg(not(feature = "pi3"))]
+const ACT_LED_PIN: usize = 42;
+/// GPIO pin driving the activity LED of the Pi 3 B+, as that of the Pi 3 B is
+/// behind the GPIO expander of the firmware.
+#[cfg(feature = "pi3")]
+const ACT_LED_PIN: usize = 29;
 /// Logical CPU count.
 const CPU_COUNT: usize = 4;
+/// Number of cores in each cluster.
+const CORES_PER_CLUSTER: usize = 4;
+/// Size of the stack used by every core at EL1 and EL2 to handle exceptions
+/// in bytes.
+const ELN_STACK_SIZE: usize = 0x4000;
+/// Size of the guard below every exception stack in bytes, which the boot
+/// code leaves unmapped.
+const STACK_GUARD_SIZE: usize = ELN_STACK_SIZE;
+/// Address just past the top of the stack selected by `SP_EL0` on core #0,
+/// below which the boot code maps those of the other cores with a gap of
+/// [`STACK_SIZE`] bytes below each of them.
+const STACKS_TOP: usize = 1 << 32;
+/// Size of the stack selected by `SP_EL0` on every core, which the kernel runs
+/// on outside of exceptions, in bytes.
+const STACK_SIZE: usize = 0x200000;
+/// Largest number of frames walked by a backtrace.
+const BACKTRACE_DEPTH: usize = 64;
+/// Watchdog timeout in seconds.
+const WATCHDOG_TIMEOUT: u32 = 10;
+/// Largest difference between the ARM clock rate measured with the generic
+/// timer and the rate reported by the firmware in percent.
+const CLOCK_TOLERANCE: usize = 2;
+/// Names, shifts, and widths of the fields of the exception syndrome
+/// registers.
+const ESR_FIELDS: [(&str, u32, u32); 3] = [("EC", 26, 6), ("IL", 25, 1), ("ISS", 0, 25)];
+/// Names, shifts, and widths of the fields of the saved program status
+/// registers for exceptions taken from AArch64.
+const SPSR_FIELDS: [(&str, u32, u32); 11] = [("N", 31, 1),
+                                             ("Z", 30, 1),
+                                             ("C", 29, 1),
+                                             ("V", 28, 1),
+                                             ("SS", 21, 1),
+                                             ("IL", 20, 1),
+                                             ("D", 9, 1),
+                                             ("A", 8, 1),
+                                             ("I", 7, 1),
+                                             ("F", 6, 1),
+                                             ("M", 0, 4)];
+
+/// Whether core #0 has entered the Rust code, which is left to the BSS so that
+/// finding it set at entry reveals that the boot code did not zero the BSS.
+static ENTERED: AtomicBool = AtomicBool::new(false);
+/// Addresses of the registers saved by the boot code when every core faulted,
+/// or zero if the core hasn't faulted.
+static FAULT_FRAMES: [AtomicUsize; CPU_COUNT] = [const { AtomicUsize::new(0) }; CPU_COUNT];
+/// Whether every core has entered the panic handler.
+static PANICKING: [AtomicBool; CPU_COUNT] = [const { AtomicBool::new(false) }; CPU_COUNT];
+
+/// General purpose registers saved by the boot code when a fault is taken,
+/// formatted for display as a table.
+///
+/// The boot code saves the registers at the offsets passed to it from this
+/// layout.
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct Frame
+{
+    /// Registers x0 to x30.
+    pub regs: [usize; 31],
+    /// Stack pointer at the vector, which is the stack pointer of the faulting
+    /// code unless the fault came from a lower exception level.
+    pub sp: usize,
+}
+
+/// Encoding of an instruction, or `None` if it could not be read, formatted for
+/// display.
+#[derive(Clone, Copy, Debug)]
+struct Instruction(Option<u32>);
+
+/// Value of a register along with the names, shifts, and widths of its fields,
+/// formatted for display in hexadecimal followed by every field in binary.
+#[derive(Clone, Copy, Debug)]
+struct Register(usize, &'static [(&'static str, u32, u32)]);
+
+// The boot code compares the logical index of every core with the number of
+// cores as an immediate, and the secondary cores are started by their logical
+// index, which only matches their affinity within the first cluster.
+const _: () = assert!(CPU_COUNT > 1 && CPU_COUNT.is_power_of_two() && CPU_COUNT <= CORES_PER_CLUSTER,
+                      "The boot code cannot select the stacks of this many cores");
 
-global_asm!(include_str!("boot.s"));
+// The exception vectors tell the exception stacks from their guards with the
+// bit of the stack size, and the boot code maps them in pages.
+const _: () = assert!(ELN_STACK_SIZE.is_power_of_two() && STACK_GUARD_SIZE == ELN_STACK_SIZE && ELN_STACK_SIZE >= 0x1000,
+                      "The boot code cannot guard exception stacks of this size");
+
+// The boot code saves x1 to x28 by pairs at offsets from the start of the
+// frame and x0 and fp right below its end, which the saved registers then
+// overwrite.
+const _: () = assert!(offset_of!(Frame, regs) == 0 && size_of::<Frame>() == 32 * 8,
+                      "The boot code cannot save the registers in this frame layout");
+
+// The boot code places the stacks selected by SP_EL0 4MB apart.
+const _: () = assert!(STACK_SIZE * 2 == 1 << 22, "The boot code cannot place stacks of this size");
+
+global_asm!(include_str!("boot.s"),
+            CPU_COUNT = const CPU_COUNT,
+            CORES_PER_CLUSTER = const CORES_PER_CLUSTER,
+            STACKS_TOP = const STACKS_TOP,
+            PERRY_BASE = const PERRY_BASE,
+            PERRY_SIZE = const PERRY_RANGE.end - PERRY_RANGE.start,
+            FRAME_SIZE = const size_of::<Frame>(),
+            FRAME_FP = const offset_of!(Frame, regs) + 29 * 8,
+            FRAME_SP = const offset_of!(Frame, sp),
+            ELN_STACK_SIZE = const ELN_STACK_SIZE,
+            ELN_STACK_SHIFT = const ELN_STACK_SIZE.trailing_zeros(),
+            GUARD_SIZE = const STACK_GUARD_SIZE);
+
+extern "C" {
+    /// Exception stacks of all the cores along with their guards, defined in
+    /// the boot code.
+    static mut eln_stacks: u8;
+}
 
 /// Entry point.
+///
+/// * `level`: Exception level at which the firmware booted the kernel.
+/// * `dtb`: Address of the device tree passed by the firmware, which is only
+///   meaningful on core #0.
 #[no_mangle]
-pub extern "C" fn start() -> !
+pub extern "C" fn start(level: usize, dtb: usize) -> !
 {
-    let cpu = cpu_id();
-    debug!("Booted core #{cpu}");
-    bench();
-    halt()
+    let cpu = core_index();
+    let mpidr = mpidr();
+    // Nothing but the boot code has written to the BSS by now.
+    let dirty = cpu == 0 && ENTERED.swap(true, Ordering::Relaxed);
+    hyp::init(level);
+    sve::enable_fp();
+    debug!("Booted core #{cpu} at EL{level} (Affinity: {}.{}.{}.{}, MT: {})",
+           mpidr >> 32 & 0xFF,
+           mpidr >> 16 & 0xFF,
+           mpidr >> 8 & 0xFF,
+           mpidr & 0xFF,
+           mpidr >> 24 & 0x1);
+    pmu::enable();
+    let (dline, iline) = cache::line_sizes();
+    if dline != cache::LINE_SIZE {
+        debug!("WARNING: Core #{cpu} has {dline} byte data cache lines, but the benchmarks assume {} byte lines!",
+               cache::LINE_SIZE);
+    }
+    if cpu == 0 {
+        if dirty {
+            debug!("WARNING: The BSS was not zeroed before entering the Rust code, so statics may start corrupted!");
+        }
+        debug!("Cache lines: Data: {dline} bytes, Instruction: {iline} bytes");
+        debug!("{}", mmu::translation());
+        bench::control_state();
+        watchdog::arm(WATCHDOG_TIMEOUT);
+        match (mbox::board_revision(), mbox::board_serial()) {
+            (Ok(revision), Ok(serial)) => debug!("Board: {revision}, Serial: {serial:016X}"),
+            (Err(err), _) | (_, Err(err)) => debug!("Failed to identify the board: {err}"),
+        }
+        timer::calibrate();
+        let freq = timer::frequency();
+        let measured = timer::cross_check();
+        debug!("Timer frequency: {freq}Hz, Measured against the system timer: {measured}Hz, Read overhead: {} ticks",
+               timer::overhead());
+        if measured.abs_diff(freq) > freq / 100 {
+            debug!("WARNING: The generic timer frequency is off by more than 1%, so all the timings will be skewed!");
+        }
+        match mbox::clock_rate(mbox::ARM_CLOCK) {
+            Ok(reported) => {
+                let reported = reported as usize;
+                let measured = pmu::clock_rate();
+                debug!("ARM clock: Reported: {}MHz, Measured with the cycle counter and the generic timer: {}MHz",
+                       reported / 1000000,
+                       measured / 1000000);
+                if measured.abs_diff(reported) > reported / 100 * CLOCK_TOLERANCE {
+                    debug!("WARNING: The measured ARM clock is off by more than {CLOCK_TOLERANCE}%, so CNTFRQ_EL0 is probably wrong!");
+                }
+            }
+            Err(err) => debug!("Failed to read the ARM clock rate: {err}"),
+        }
+        psci::init(dtb);
+        smp::start_secondaries();
+        smp::check_in();
+        gic::init();
+        gic::enable(uart::IRQ);
+        uart::use_interrupts();
+        unsafe { asm!("msr daifclr, #0x2", options (nomem, nostack, preserves_flags)) };
+        cli::boot_config();
+        menu::run()
+    }
+    // Only the software generated interrupts sent by the other cores are routed
+    // to the secondary cores.
+    gic::init_core();
+    unsafe { asm!("msr daifclr, #0x2", options (nomem, nostack, preserves_flags)) };
+    smp::serve()
 }
 
-/// Benchmarks.
-fn bench()
+/// Handles the pending interrupts on the calling core.
+///
+/// Must not take any lock, since the interrupted code may be holding it.
+#[no_mangle]
+pub extern "C" fn irq()
 {
-    #[repr(align(64), C)]
-    struct Buffer([u8; 0x1000]);
-    let mut buf = MaybeUninit::<Buffer>::uninit();
-    unsafe {
-        asm!(
-            "add {eaddr}, {addr}, #0x1000",
-            "0:",
-            "cmp {addr}, {eaddr}",
-            "beq 0f",
-            "prfm pstl1keep, [{addr}]",
-            "add {addr}, {addr}, #64",
-            "b 0b",
-            "0:",
-            addr = inout (reg) buf.as_mut_ptr() => _,
-            eaddr = out (reg) _,
-        );
-    }
-    let start: usize;
-    unsafe {
-        asm!(
-            "mrs {now}, cntpct_el0",
-            now = out (reg) start,
-            options (nomem, nostack, preserves_flags)
-        );
-    }
-    for _ in 0 .. 2 << 20 {
-        unsafe {
-            asm!(
-                "add {eaddr}, {addr}, #0x1000",
-                "ins {data}.d[0], xzr",
-                "ins {data}.d[1], xzr",
-                "0:",
-                "cmp {addr}, {eaddr}",
-                "beq 0f",
-                "stp {data:q}, {data:q}, [{addr}], #32",
-                "b 0b",
-                "0:",
-                addr = inout (reg) buf.as_mut_ptr() => _,
-                eaddr = out (reg) _,
-                data = out (vreg) _
-            );
+    while let Some(irq) = gic::acknowledge() {
+        match irq.id() {
+            uart::IRQ => uart::interrupt(),
+            bench::SGI => bench::sgi_interrupt(),
+            _ => (),
         }
+        gic::end(irq);
     }
-    let end: usize;
-    let freq: usize;
-    unsafe {
-        asm!(
-            "mrs {now}, cntpct_el0",
-            "mrs {freq}, cntfrq_el0",
-            now = out (reg) end,
-            freq = out (reg) freq,
-            options (nomem, nostack, preserves_flags)
-        );
-    }
-    let diff = end - start;
-    let secs = diff / freq;
-    let msecs = diff / (freq / 1000) % 1000;
-    let core = cpu_id();
-    debug!("Core #{core} wrote 8GB in {secs}.{msecs:03} secs");
 }
 
 /// Panics with diagnostic information about a fault.
+///
+/// * `kind`: Offset of the vector that caught the fault in multiples of 0x80
+///   bytes, whose upper two bits are the origin of the exception (current
+///   level with `SP_EL0`, current level with `SP_ELx`, lower level in AArch64,
+///   or lower level in AArch32) and whose lower two bits are its type (Sync,
+///   IRQ, FIQ, or SError).
+/// * `frame`: General purpose registers at the time of the fault.
 #[no_mangle]
-pub extern "C" fn fault(kind: usize) -> !
+pub extern "C" fn fault(kind: usize, frame: &Frame) -> !
 {
-    let core = cpu_id();
+    let core = core_index();
     let level: usize;
     let syndrome: usize;
     let addr: usize;
@@ -136,15 +338,37 @@ pub extern "C" fn fault(kind: usize) -> !
             _ => panic!("Exception caught at unsupported level {level}"),
         }
     };
-    panic!("Core #{core} triggered an exception at level {level}: Kind: 0x{kind:x}, Syndrome: 0x{syndrome:x}, Address: 0x{addr:x}, Location: 0x{ret:x}, State: 0x{state:x}");
+    // The mode field of the saved state holds the level the exception was taken
+    // from, which is EL0 for faults in user calls.
+    let origin = state >> 2 & 0x3;
+    FAULT_FRAMES[core].store(frame as *const Frame as usize, Ordering::Relaxed);
+    // Reading the instruction must not fault again, so it's only read if it is
+    // mapped as normal memory.
+    let insn = (ret % 4 == 0 && mmu::readable(ret .. ret + 4)).then(|| unsafe { (ret as *const u32).read_volatile() });
+    // The guards are unmapped, so overflowing the exception stack faults on
+    // its guard.
+    let overflow = if stack_guard(core).contains(&addr) { " by overflowing its exception stack" } else { "" };
+    panic!("{}: Core #{core} triggered an exception at level {level} from EL{origin}{overflow}: Kind: 0x{kind:x}, Syndrome: {}, Address: 0x{addr:x}, Location: 0x{ret:x}, Instruction: {}, State: {}",
+           Syndrome(syndrome),
+           Register(syndrome, &ESR_FIELDS),
+           Instruction(insn),
+           Register(state, &SPSR_FIELDS));
 }
 
 /// Halts the calling core.
 #[no_mangle]
 pub extern "C" fn halt() -> !
 {
-    let core = cpu_id();
+    let core = core_index();
     debug!("Halted core #{core}");
+    // Nothing drains the output once interrupts are masked.
+    UART.lock().flush();
+    park()
+}
+
+/// Halts the calling core without taking any lock or reporting anything.
+fn park() -> !
+{
     unsafe {
         asm!("msr daifset, #0x3",
              "0:",
@@ -158,56 +382,181 @@ pub extern "C" fn halt() -> !
 #[panic_handler]
 fn panic(info: &PanicInfo) -> !
 {
-    let mut uart = UART.lock();
-    let affinity = cpu_id();
+    let affinity = core_index();
+    // Panicking again from the panic handler, for instance by faulting while
+    // walking a corrupted stack, must neither take the UART lock, which may be
+    // held already, nor recurse.
+    if PANICKING[affinity].swap(true, Ordering::Relaxed) {
+        uart::write_raw(b"\nDouble panic on core #");
+        uart::write_raw(&[b'0' + affinity as u8, b'\n']);
+        park();
+    }
+    // The activity LED shows the panic even without anything on the console.
+    gpio::select(ACT_LED_PIN, gpio::Function::Output);
+    gpio::set(ACT_LED_PIN);
+    // Formatting errors are ignored, since they would panic again.  The UART
+    // lock is reclaimed, since this core may have panicked while holding it,
+    // and kept until the backtrace is out.
+    let mut uart = unsafe { UART.reclaim() };
     if let Some(location) = info.location() {
-        write!(uart,
-               "Core #{affinity} panicked at {}:{}: ",
-               location.file(),
-               location.line()).unwrap()
+        let _ = write!(uart,
+                       "Core #{affinity} panicked at {}:{}: ",
+                       location.file(),
+                       location.line());
     } else {
-        write!(uart, "Core #{affinity} panic: ").unwrap()
+        let _ = write!(uart, "Core #{affinity} panic: ");
     }
     if let Some(args) = info.message() {
-        uart.write_fmt(*args).unwrap()
+        let _ = uart.write_fmt(*args);
     } else {
-        uart.write_str("Unknown reason").unwrap()
+        let _ = uart.write_str("Unknown reason");
+    }
+    let _ = uart.write_char('\n');
+    // The registers are dumped before the backtrace, which may go wrong.
+    let frame = FAULT_FRAMES[affinity].swap(0, Ordering::Relaxed);
+    if frame != 0 {
+        let _ = write!(uart, "{}", unsafe { &*(frame as *const Frame) });
     }
-    uart.write_char('\n').unwrap();
+    backtrace(&mut uart);
     drop(uart);
-    backtrace();
+    if smp::fail() {
+        debug!("Core #{affinity} marked as failed, the other cores carry on without it");
+    }
     halt();
 }
 
-/// Returns the ID of the current CPU core.
-fn cpu_id() -> usize
+/// Returns the range of addresses of the guard below the exception stack of a
+/// core.
+///
+/// * `core`: Logical index of the core.
+fn stack_guard(core: usize) -> Range<usize>
+{
+    let stacks = unsafe { addr_of_mut!(eln_stacks) } as usize;
+    let guard = stacks + core * (STACK_GUARD_SIZE + ELN_STACK_SIZE);
+    guard .. guard + STACK_GUARD_SIZE
+}
+
+/// Returns the ranges of addresses of the stack selected by `SP_EL0` and of
+/// the exception stack of a core, without the guard.
+///
+/// * `core`: Logical index of the core.
+fn stacks(core: usize) -> [Range<usize>; 2]
+{
+    let top = STACKS_TOP - core * 2 * STACK_SIZE;
+    let eln = stack_guard(core).end;
+    [top - STACK_SIZE .. top, eln .. eln + ELN_STACK_SIZE]
+}
+
+/// Returns the logical index of the current CPU core.
+///
+/// The boot code derives the index from the affinity fields of `MPIDR_EL1` as
+/// `cluster * CORES_PER_CLUSTER + core`, selects the stacks of the core with
+/// it, and leaves it in `TPIDRRO_EL0`.  Cores that do not report
+/// multi-threading, such as the Cortex-A72 cores in the single cluster of the
+/// Pi 4, identify the core in Aff0 and the cluster in Aff1, whereas cores that
+/// do report it, such as the Cortex-A76, identify the thread in Aff0, the core
+/// in Aff1, and the cluster in Aff2, with the thread ignored since they are
+/// single-threaded.
+fn core_index() -> usize
+{
+    let index: usize;
+    unsafe {
+        asm!(
+            "mrs {index}, tpidrro_el0",
+            index = out (reg) index,
+            options (nomem, nostack, preserves_flags));
+    }
+    index
+}
+
+/// Reads the multiprocessor affinity register of the current CPU core.
+///
+/// Returns the value of `MPIDR_EL1`.
+fn mpidr() -> usize
 {
-    let id: usize;
+    let mpidr: usize;
     unsafe {
         asm!(
-            "mrs {id}, mpidr_el1",
-            "and {id}, {id}, #0xff",
-            id = out (reg) id,
+            "mrs {mpidr}, mpidr_el1",
+            mpidr = out (reg) mpidr,
             options (nomem, nostack, preserves_flags));
     }
-    id
+    mpidr
 }
 
 /// Sends the return addresses of all the function calls from this function all
-/// the way back to the boot code through the UART.
-fn backtrace()
+/// the way back to the boot code through the UART, up to
+/// [`BACKTRACE_DEPTH`] frames.
+///
+/// Every frame pointer is checked before following it, since faulting here
+/// would lose the report: it must be 16 byte aligned, above the previous one,
+/// and within one of the stacks of the calling core, which covers the frames
+/// of exception handlers chained to the frames that they interrupted.
+///
+/// * `uart`: UART driver held by the caller.
+fn backtrace(uart: &mut Uart)
 {
-    let mut uart = UART.lock();
     let mut fp: usize;
     let mut lr: usize;
     unsafe {
         asm!("mov {fp}, fp", "mov {lr}, lr", fp = out (reg) fp, lr = out (reg) lr, options (nomem, nostack, preserves_flags))
     };
-    let mut frame = 0usize;
-    writeln!(uart, "Backtrace:").unwrap();
-    while fp != 0x0 {
-        writeln!(uart, "#{frame}: 0x{lr:X}").unwrap();
+    let stacks = stacks(core_index());
+    let mut prev = 0;
+    let _ = writeln!(uart, "Backtrace:");
+    for frame in 0 .. BACKTRACE_DEPTH {
+        if fp == 0x0 {
+            return;
+        }
+        let _ = writeln!(uart, "#{frame}: 0x{lr:X}");
+        if fp % 16 != 0 || fp <= prev || !stacks.iter().any(|stack| stack.start <= fp && fp + 16 <= stack.end) {
+            let _ = writeln!(uart, "(frame chain corrupt at 0x{fp:X})");
+            return;
+        }
+        prev = fp;
         unsafe { asm!("ldp {fp}, {lr}, [{fp}]", fp = inout (reg) fp, lr = out (reg) lr, options (preserves_flags)) };
-        frame += 1;
+    }
+    if fp != 0x0 {
+        let _ = writeln!(uart, "(frame chain truncated after {BACKTRACE_DEPTH} frames)");
+    }
+}
+
+impl Display for Register
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let Self(val, fields) = *self;
+        write!(fmt, "0x{val:X} (")?;
+        for (idx, &(name, shift, width)) in fields.iter().enumerate() {
+            if idx != 0 {
+                write!(fmt, ", ")?;
+            }
+            let field = val >> shift & ((1 << width) - 1);
+            write!(fmt, "{name}: 0b{field:0width$b}", width = width as usize)?;
+        }
+        write!(fmt, ")")
+    }
+}
+
+impl Display for Frame
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        for (idx, reg) in self.regs.iter().enumerate() {
+            write!(fmt, "x{idx:<2} 0x{reg:016X}")?;
+            fmt.write_str(if idx % 4 == 3 { "\n" } else { "  " })?;
+        }
+        writeln!(fmt, "sp  0x{:016X}", self.sp)
+    }
+}
+
+impl Display for Instruction
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        match self.0 {
+            Some(insn) => write!(fmt, "0x{insn:08X}"),
+            None => write!(fmt, "Unreadable"),
+        }
     }
 }
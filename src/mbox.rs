@@ -0,0 +1,332 @@
+//! VideoCore mailbox property interface.
+//!
+//! Documentation:
+//!
+//! * [BCM2711 ARM Peripherals](https://datasheets.raspberrypi.com/bcm2711/bcm2711-peripherals.pdf)
+//!   1.2
+//! * [Mailbox property interface](https://github.com/raspberrypi/firmware/wiki/Mailbox-property-interface)
+
+use core::fmt::{Display, Formatter, Result as FormatResult};
+use core::hint::spin_loop;
+use core::ops::Range;
+
+use crate::cache;
+use crate::sync::Lock;
+use crate::PERRY_RANGE;
+
+/// Base address of the mailbox registers.
+const MBOX_BASE: usize = 0x200B880 + PERRY_RANGE.start;
+/// Read register of the VideoCore to ARM mailbox.
+const MBOX_READ: *const u32 = MBOX_BASE as _;
+/// Status register of the VideoCore to ARM mailbox.
+const MBOX_STATUS: *const u32 = (MBOX_BASE + 0x18) as _;
+/// Write register of the ARM to VideoCore mailbox.
+const MBOX_WRITE: *mut u32 = (MBOX_BASE + 0x20) as _;
+/// Status register of the ARM to VideoCore mailbox.
+const MBOX_WRITE_STATUS: *const u32 = (MBOX_BASE + 0x38) as _;
+/// Mailbox full status flag.
+const MBOX_FULL: u32 = 0x80000000;
+/// Mailbox empty status flag.
+const MBOX_EMPTY: u32 = 0x40000000;
+/// Property channel from the ARM to the VideoCore.
+const PROPERTY_CHANNEL: u32 = 8;
+/// Alias through which the VideoCore accesses ARM memory without caching it.
+const BUS_ALIAS: u32 = 0xC0000000;
+/// Response code of a successful request.
+const RESPONSE_SUCCESS: u32 = 0x80000000;
+/// Get firmware revision tag.
+const GET_FIRMWARE_REVISION: u32 = 0x00000001;
+/// Get board revision tag.
+const GET_BOARD_REVISION: u32 = 0x00010002;
+/// Get board serial tag.
+const GET_BOARD_SERIAL: u32 = 0x00010004;
+/// Get ARM memory tag.
+const GET_ARM_MEMORY: u32 = 0x00010005;
+/// Get clock rate tag.
+const GET_CLOCK_RATE: u32 = 0x00030002;
+/// Get maximum clock rate tag.
+const GET_MAX_CLOCK_RATE: u32 = 0x00030004;
+/// Set clock rate tag.
+const SET_CLOCK_RATE: u32 = 0x00038002;
+/// Identifier of the ARM clock.
+pub const ARM_CLOCK: u32 = 3;
+/// Identifier of the EMMC2 controller clock.
+pub const EMMC2_CLOCK: u32 = 12;
+/// Get temperature tag.
+const GET_TEMPERATURE: u32 = 0x00030006;
+/// Get maximum temperature tag.
+const GET_MAX_TEMPERATURE: u32 = 0x0003000A;
+/// Identifier of the only temperature sensor.
+const TEMPERATURE_ID: u32 = 0;
+
+/// Global mailbox driver instance.
+pub static MAILBOX: Lock<Mailbox> = Lock::new(Mailbox::new());
+
+/// Property message buffer.
+///
+/// Takes up entire cache lines so that maintaining its cache lines can't
+/// affect anything else.
+#[repr(align(64), C)]
+struct Buffer([u32; 64]);
+
+/// Mailbox driver.
+pub struct Mailbox
+{
+    /// Buffer shared with the VideoCore.
+    buf: Buffer,
+}
+
+/// Board revision code, formatted for display as the model it encodes.
+#[derive(Clone, Copy, Debug)]
+pub struct Revision(pub u32);
+
+/// Mailbox errors.
+#[derive(Clone, Copy, Debug)]
+pub enum Error
+{
+    /// The firmware failed to parse the request.
+    Request,
+    /// The firmware did not process the tag.
+    Tag,
+}
+
+impl Mailbox
+{
+    /// Creates and initializes a new mailbox driver instance.
+    ///
+    /// Returns the newly created mailbox driver instance.
+    const fn new() -> Self
+    {
+        Self { buf: Buffer([0; 64]) }
+    }
+
+    /// Sends a property message with a single tag to the VideoCore and waits
+    /// for the response.
+    ///
+    /// * `tag`: Tag identifier.
+    /// * `data`: Request values, overwritten with the response values.
+    ///
+    /// Returns an error if the firmware rejects the request or the tag.
+    pub fn call(&mut self, tag: u32, data: &mut [u32]) -> Result<(), Error>
+    {
+        self.call_tags(&mut [(tag, data)])
+    }
+
+    /// Sends a property message with several tags to the VideoCore and waits
+    /// for the response, for settings that the firmware only applies together.
+    ///
+    /// * `tags`: Tag identifiers along with their request values, overwritten
+    ///   with the response values.
+    ///
+    /// Returns an error if the firmware rejects the request or any of the
+    /// tags.
+    pub fn call_tags(&mut self, tags: &mut [(u32, &mut [u32])]) -> Result<(), Error>
+    {
+        let len = tags.iter().map(|(_, data)| data.len() + 3).sum::<usize>() + 3;
+        let buf = &mut self.buf.0;
+        assert!(len <= buf.len(), "Mailbox property message too long");
+        let ptr = buf.as_mut_ptr();
+        unsafe {
+            ptr.write_volatile((len * 4) as u32);
+            ptr.add(1).write_volatile(0x0);
+            let mut idx = 2;
+            for (tag, data) in tags.iter() {
+                for word in [*tag, (data.len() * 4) as u32, 0x0].iter().chain(data.iter()) {
+                    ptr.add(idx).write_volatile(*word);
+                    idx += 1;
+                }
+            }
+            ptr.add(idx).write_volatile(0x0);
+        }
+        let range = ptr as usize .. ptr as usize + len * 4;
+        cache::clean_invalidate(range.clone());
+        // The buffer is identity mapped, so its virtual address is also its
+        // physical address.
+        let mail = (ptr as u32 | BUS_ALIAS) | PROPERTY_CHANNEL;
+        unsafe {
+            while MBOX_WRITE_STATUS.read_volatile() & MBOX_FULL != 0 {
+                spin_loop()
+            }
+            MBOX_WRITE.write_volatile(mail);
+            loop {
+                while MBOX_STATUS.read_volatile() & MBOX_EMPTY != 0 {
+                    spin_loop()
+                }
+                if MBOX_READ.read_volatile() == mail {
+                    break;
+                }
+            }
+        }
+        cache::clean_invalidate(range);
+        unsafe {
+            if ptr.add(1).read_volatile() != RESPONSE_SUCCESS {
+                return Err(Error::Request);
+            }
+            let mut idx = 2;
+            for (_, data) in tags.iter_mut() {
+                if ptr.add(idx + 2).read_volatile() & RESPONSE_SUCCESS == 0 {
+                    return Err(Error::Tag);
+                }
+                for (offset, word) in data.iter_mut().enumerate() {
+                    *word = ptr.add(idx + 3 + offset).read_volatile();
+                }
+                idx += data.len() + 3;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Revision
+{
+    /// Decodes the model of the board from a new style revision code.
+    ///
+    /// Returns the name of the model, or `None` if the revision code uses the
+    /// old style or encodes an unknown model.
+    pub fn model(self) -> Option<&'static str>
+    {
+        if self.0 & 0x800000 == 0 {
+            return None;
+        }
+        let model = match self.0 >> 4 & 0xFF {
+            0x08 => "Raspberry Pi 3 Model B",
+            0x0A => "Raspberry Pi Compute Module 3",
+            0x0D => "Raspberry Pi 3 Model B+",
+            0x0E => "Raspberry Pi 3 Model A+",
+            0x10 => "Raspberry Pi Compute Module 3+",
+            0x11 => "Raspberry Pi 4 Model B",
+            0x12 => "Raspberry Pi Zero 2 W",
+            0x13 => "Raspberry Pi 400",
+            0x14 => "Raspberry Pi Compute Module 4",
+            _ => return None,
+        };
+        Some(model)
+    }
+}
+
+impl Display for Revision
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let Some(model) = self.model() else {
+            return write!(fmt, "Unknown board (Revision: 0x{:X})", self.0);
+        };
+        write!(fmt,
+               "{model} rev 1.{} with {}MB of RAM (Revision: 0x{:X})",
+               self.0 & 0xF,
+               256 << (self.0 >> 20 & 0x7),
+               self.0)
+    }
+}
+
+impl Display for Error
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        match self {
+            Self::Request => write!(fmt, "Firmware failed to parse the request"),
+            Self::Tag => write!(fmt, "Firmware did not process the tag"),
+        }
+    }
+}
+
+/// Queries the firmware about its revision.
+///
+/// Returns the revision, or an error if the query fails.
+pub fn firmware_revision() -> Result<u32, Error>
+{
+    let mut data = [0];
+    MAILBOX.lock().call(GET_FIRMWARE_REVISION, &mut data)?;
+    Ok(data[0])
+}
+
+/// Queries the firmware about the revision of the board.
+///
+/// Returns the revision code, or an error if the query fails.
+pub fn board_revision() -> Result<Revision, Error>
+{
+    let mut data = [0];
+    MAILBOX.lock().call(GET_BOARD_REVISION, &mut data)?;
+    Ok(Revision(data[0]))
+}
+
+/// Queries the firmware about the serial number of the board.
+///
+/// Returns the serial number, or an error if the query fails.
+pub fn board_serial() -> Result<u64, Error>
+{
+    let mut data = [0; 2];
+    MAILBOX.lock().call(GET_BOARD_SERIAL, &mut data)?;
+    Ok((data[1] as u64) << 32 | data[0] as u64)
+}
+
+/// Queries the firmware about the memory range assigned to the ARM cores.
+///
+/// Returns the physical memory range, or an error if the query fails.
+pub fn arm_memory() -> Result<Range<usize>, Error>
+{
+    let mut data = [0; 2];
+    MAILBOX.lock().call(GET_ARM_MEMORY, &mut data)?;
+    let base = data[0] as usize;
+    Ok(base .. base + data[1] as usize)
+}
+
+/// Queries the firmware about the current rate of a clock.
+///
+/// * `clock`: Clock identifier.
+///
+/// Returns the rate in hertz, or an error if the query fails.
+pub fn clock_rate(clock: u32) -> Result<u32, Error>
+{
+    let mut data = [clock, 0];
+    MAILBOX.lock().call(GET_CLOCK_RATE, &mut data)?;
+    Ok(data[1])
+}
+
+/// Queries the firmware about the maximum rate of a clock.
+///
+/// * `clock`: Clock identifier.
+///
+/// Returns the rate in hertz, or an error if the query fails.
+pub fn max_clock_rate(clock: u32) -> Result<u32, Error>
+{
+    let mut data = [clock, 0];
+    MAILBOX.lock().call(GET_MAX_CLOCK_RATE, &mut data)?;
+    Ok(data[1])
+}
+
+/// Asks the firmware to change the rate of a clock.
+///
+/// * `clock`: Clock identifier.
+/// * `rate`: Requested rate in hertz.
+///
+/// Returns the rate actually applied in hertz, or an error if the request
+/// fails.
+pub fn set_clock_rate(clock: u32, rate: u32) -> Result<u32, Error>
+{
+    let mut data = [clock, rate, 0];
+    MAILBOX.lock().call(SET_CLOCK_RATE, &mut data)?;
+    Ok(data[1])
+}
+
+/// Queries the firmware about the current SoC temperature.
+///
+/// Returns the temperature in thousandths of a degree Celsius, or an error if
+/// the query fails.
+pub fn temperature() -> Result<u32, Error>
+{
+    let mut data = [TEMPERATURE_ID, 0];
+    MAILBOX.lock().call(GET_TEMPERATURE, &mut data)?;
+    Ok(data[1])
+}
+
+/// Queries the firmware about the SoC temperature beyond which it throttles.
+///
+/// Returns the temperature in thousandths of a degree Celsius, or an error if
+/// the query fails.
+pub fn max_temperature() -> Result<u32, Error>
+{
+    let mut data = [TEMPERATURE_ID, 0];
+    MAILBOX.lock().call(GET_MAX_TEMPERATURE, &mut data)?;
+    Ok(data[1])
+}
@@ -0,0 +1,236 @@
+//! RAM tests.
+//!
+//! Walks all the RAM that is not used by the kernel image or the stacks either
+//! with a March C- test using the address of each double-word as the data
+//! background, which catches stuck bits and coupling faults as well as
+//! addressing faults, or with a quicker verification that just writes and
+//! reads back a couple of patterns.  A stress test additionally walks single
+//! set and cleared bits across all the free RAM and runs moving inversions over
+//! it with the set bit rotating on every pass, which is useful to validate
+//! overclocked memory settings.
+
+use core::fmt::Display;
+use core::ops::Range;
+
+use crate::{debug, ram, watchdog};
+
+/// Interval between progress reports in bytes.
+const PROGRESS_INTERVAL: usize = 64 << 20;
+/// Maximum number of mismatches to report per test.
+const MAX_REPORTS: usize = 16;
+/// Steps of the March C- test.
+const MARCH_STEPS: [Step; 6] = [Step { desc: "Ascending (w0)",
+                                       descending: false,
+                                       read: None,
+                                       write: Some(false) },
+                                Step { desc: "Ascending (r0, w1)",
+                                       descending: false,
+                                       read: Some(false),
+                                       write: Some(true) },
+                                Step { desc: "Ascending (r1, w0)",
+                                       descending: false,
+                                       read: Some(true),
+                                       write: Some(false) },
+                                Step { desc: "Descending (r0, w1)",
+                                       descending: true,
+                                       read: Some(false),
+                                       write: Some(true) },
+                                Step { desc: "Descending (r1, w0)",
+                                       descending: true,
+                                       read: Some(true),
+                                       write: Some(false) },
+                                Step { desc: "Descending (r0)",
+                                       descending: true,
+                                       read: Some(false),
+                                       write: None }];
+/// Steps of the moving inversions test.
+const MOVING_INVERSIONS_STEPS: [Step; 3] = [Step { desc: "Ascending (w0)",
+                                                   descending: false,
+                                                   read: None,
+                                                   write: Some(false) },
+                                            Step { desc: "Ascending (r0, w1)",
+                                                   descending: false,
+                                                   read: Some(false),
+                                                   write: Some(true) },
+                                            Step { desc: "Descending (r1, w0)",
+                                                   descending: true,
+                                                   read: Some(true),
+                                                   write: Some(false) }];
+/// Steps of the walking zeros test, which are the pattern verification steps
+/// with the walking ones pattern inverted.
+const WALKING_ZEROS_STEPS: [Step; 2] = [Step { desc: "Write",
+                                               descending: false,
+                                               read: None,
+                                               write: Some(true) },
+                                        Step { desc: "Read",
+                                               descending: false,
+                                               read: Some(true),
+                                               write: None }];
+/// Number of passes of the moving inversions test, each with the set bit of
+/// every double-word one position further, so that every double-word has its
+/// bit set in every position once.
+const MOVING_INVERSIONS_PASSES: u32 = 64;
+/// Steps of the pattern verification.
+const VERIFY_STEPS: [Step; 2] = [Step { desc: "Write",
+                                        descending: false,
+                                        read: None,
+                                        write: Some(false) },
+                                 Step { desc: "Read",
+                                        descending: false,
+                                        read: Some(false),
+                                        write: None }];
+
+/// Test step.
+struct Step
+{
+    /// Description.
+    desc: &'static str,
+    /// Whether to walk the memory in descending order.
+    descending: bool,
+    /// Data expected to be read, with `true` meaning the inverted pattern.
+    read: Option<bool>,
+    /// Data to write, with `true` meaning the inverted pattern.
+    write: Option<bool>,
+}
+
+/// Data pattern.
+#[derive(Clone, Copy, Debug)]
+enum Pattern
+{
+    /// Address of each double-word.
+    Address,
+    /// Single bit set that walks across the double-words, starting at the
+    /// given position in the first one.
+    WalkingOnes(u32),
+}
+
+/// Runs the March C- test over all the free RAM.
+pub fn run()
+{
+    test("March C-", ram::free(), Pattern::Address, &MARCH_STEPS);
+}
+
+/// Writes and verifies known patterns across all the free RAM.
+pub fn verify()
+{
+    let range = ram::free();
+    let errors = test("Address verification", range.clone(), Pattern::Address, &VERIFY_STEPS)
+                 + test("Walking ones verification", range, Pattern::WalkingOnes(0), &VERIFY_STEPS);
+    debug!("Pattern verification found {errors} mismatches in total");
+}
+
+/// Runs the walking ones, walking zeros, and moving inversions tests over all
+/// the free RAM, with [`MOVING_INVERSIONS_PASSES`] passes of the latter.
+pub fn stress()
+{
+    let range = ram::free();
+    let tests = [("Walking ones", &VERIFY_STEPS[..]),
+                 ("Walking zeros", &WALKING_ZEROS_STEPS[..])];
+    let walking = tests.map(|(name, steps)| (name, test(name, range.clone(), Pattern::WalkingOnes(0), steps)));
+    let mut inversions = 0;
+    for pass in 0 .. MOVING_INVERSIONS_PASSES {
+        inversions += test(format_args!("Moving inversions pass {}/{MOVING_INVERSIONS_PASSES}", pass + 1),
+                           range.clone(),
+                           Pattern::WalkingOnes(pass),
+                           &MOVING_INVERSIONS_STEPS);
+    }
+    for (name, errors) in walking.into_iter().chain([("Moving inversions", inversions)]) {
+        debug!("{name}: {}", if errors == 0 { "PASS" } else { "FAIL" });
+    }
+}
+
+/// Runs a test over a range of RAM.
+///
+/// * `name`: Name of the test.
+/// * `range`: Range of RAM to test.
+/// * `pattern`: Data pattern.
+/// * `steps`: Steps of the test.
+///
+/// Returns the number of mismatches found.
+fn test(name: impl Display, range: Range<usize>, pattern: Pattern, steps: &[Step]) -> usize
+{
+    if range.is_empty() {
+        debug!("No free RAM to test");
+        return 0;
+    }
+    debug!("{name} test of RAM from 0x{:X} to 0x{:X} ({}MB)",
+           range.start,
+           range.end,
+           range.len() >> 20);
+    let mut errors = 0;
+    for (idx, step) in steps.iter().enumerate() {
+        debug!("Step {}/{}: {}", idx + 1, steps.len(), step.desc);
+        errors += step.run(range.clone(), pattern, errors);
+    }
+    if errors == 0 {
+        debug!("{name} test passed");
+    } else {
+        debug!("{name} test failed with {errors} mismatches");
+    }
+    errors
+}
+
+impl Step
+{
+    /// Runs this step over a range of memory.
+    ///
+    /// * `range`: Range of memory to test.
+    /// * `pattern`: Data pattern.
+    /// * `reported`: Number of mismatches reported by the previous steps.
+    ///
+    /// Returns the number of mismatches found.
+    fn run(&self, range: Range<usize>, pattern: Pattern, reported: usize) -> usize
+    {
+        let mut errors = 0;
+        let mut visit = |addr: usize| {
+            if addr & (PROGRESS_INTERVAL - 1) == 0 {
+                debug!("Reached 0x{addr:X}");
+                watchdog::pet();
+            }
+            let ptr = addr as *mut u64;
+            if let Some(inverted) = self.read {
+                let expected = pattern.value(addr, inverted);
+                let actual = unsafe { ptr.read_volatile() };
+                if actual != expected {
+                    if reported + errors < MAX_REPORTS {
+                        debug!("Mismatch at 0x{addr:X}: Expected: 0x{expected:016X}, Actual: 0x{actual:016X}, Bits: 0x{:016X}",
+                               actual ^ expected);
+                    }
+                    errors += 1;
+                }
+            }
+            if let Some(inverted) = self.write {
+                unsafe { ptr.write_volatile(pattern.value(addr, inverted)) };
+            }
+        };
+        let addrs = range.step_by(8);
+        if self.descending {
+            addrs.rev().for_each(&mut visit);
+        } else {
+            addrs.for_each(&mut visit);
+        }
+        errors
+    }
+}
+
+impl Pattern
+{
+    /// Computes the value of this pattern for a double-word.
+    ///
+    /// * `addr`: Address of the double-word.
+    /// * `inverted`: Whether to invert the pattern.
+    ///
+    /// Returns the computed value.
+    fn value(self, addr: usize, inverted: bool) -> u64
+    {
+        let val = match self {
+            Self::Address => addr as u64,
+            Self::WalkingOnes(start) => 1 << ((addr / 8 + start as usize) % 64),
+        };
+        if inverted {
+            !val
+        } else {
+            val
+        }
+    }
+}
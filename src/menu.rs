@@ -0,0 +1,250 @@
+//! Interactive menu.
+//!
+//! Every line typed over the UART either selects a menu entry by its key or
+//! is run as a [`cli`] command.
+
+use core::fmt::Write;
+
+use crate::uart::UART;
+use crate::{bench, cli, debug, memtest, psci, smp, stream, watchdog};
+
+/// Line printed before a benchmark starts on several cores, for scripts
+/// capturing the output to match.
+pub const START_MARKER: &str = "===BENCH_START===";
+/// Line printed once a benchmark has finished on all the selected cores, for
+/// scripts capturing the output to match.
+pub const COMPLETE_MARKER: &str = "===BENCH_COMPLETE===";
+/// Misalignments in bytes measured by the misaligned stores entry.
+const MISALIGNMENTS: [usize; 4] = [4, 8, 16, 32];
+/// Menu entries.
+const ENTRIES: [Entry; 39] = [Entry { key: 'b',
+                                     desc: "Run the benchmark on all cores",
+                                     action: bench_all },
+                             Entry { key: 't',
+                                     desc: "Sample the write bandwidth of all cores every second to detect throttling",
+                                     action: throttle_all },
+                             Entry { key: 'C',
+                                     desc: "Measure the write bandwidth on this core continuously with a live rolling average until a key is pressed",
+                                     action: bench::continuous },
+                             Entry { key: 'D',
+                                     desc: "Read a shared region on cores #0 and #1 while cores #2 and #3 write another",
+                                     action: bench::bench_duplex },
+                             Entry { key: 'I',
+                                     desc: "Compare the DRAM write bandwidth of this core with the other cores spinning and waiting for interrupts",
+                                     action: bench::bench_idle },
+                             Entry { key: 'f',
+                                     desc: "Sweep the ARM clock rate running the benchmarks on this core",
+                                     action: bench::sweep },
+                             Entry { key: 'o',
+                                     desc: "Compare the throughput of the vector store forms on this core",
+                                     action: bench::bench_store_forms },
+                             Entry { key: 'e',
+                                     desc: "Compare the throughput of the store addressing modes on this core",
+                                     action: bench::bench_addressing },
+                             Entry { key: 'z',
+                                     desc: "Compare the memset strategies zeroing a large buffer on this core",
+                                     action: bench::bench_memset },
+                             Entry { key: 'y',
+                                     desc: "Run the STREAM Copy, Scale, Add, and Triad kernels on this core",
+                                     action: stream::run },
+                             Entry { key: 'l',
+                                     desc: "Measure the DRAM latency of row hits and row misses on this core",
+                                     action: bench::bench_dram_latency },
+                             Entry { key: 'B',
+                                     desc: "Measure the throughput and latency of the bit manipulation instructions on this core",
+                                     action: bench::bench_bits },
+                             Entry { key: 'M',
+                                     desc: "Measure the throughput and latency of the multiplication instructions on this core",
+                                     action: bench::bench_mul },
+                             Entry { key: 'R',
+                                     desc: "Compare random line writes ordered by a table and by a generator on this core",
+                                     action: bench::bench_random },
+                             Entry { key: 'P',
+                                     desc: "Compare streaming at cache line, page, and 2MB placements on this core",
+                                     action: bench::bench_placement },
+                             Entry { key: 'G',
+                                     desc: "Compare streaming and page strided reads with 4KB pages and 2MB blocks on this core",
+                                     action: bench::bench_granule },
+                             Entry { key: 'F',
+                                     desc: "Compare DRAM stream reads with and without software prefetching on this core",
+                                     action: bench::bench_prefetch },
+                             Entry { key: 'L',
+                                     desc: "Compare DRAM streams at every L2 prefetch distance setting on this core",
+                                     action: bench::bench_l2_prefetch },
+                             Entry { key: 'W',
+                                     desc: "Measure the bandwidth of incrementing a DRAM buffer in place on this core",
+                                     action: bench::bench_rmw },
+                             Entry { key: 'u',
+                                     desc: "Measure the penalty of misaligned stores on this core",
+                                     action: bench_unaligned },
+                             Entry { key: 'a',
+                                     desc: "Compare memory mapped as cacheable, non-cacheable, and device on this core",
+                                     action: bench::bench_attributes },
+                             Entry { key: 'k',
+                                     desc: "Compare the core benchmarks with the caches disabled and enabled on this core",
+                                     action: bench::bench_caches },
+                             Entry { key: 'q',
+                                     desc: "Compare the core benchmarks at EL2 and EL1 on this core",
+                                     action: bench::bench_levels },
+                             Entry { key: 'E',
+                                     desc: "Compare the DRAM write bandwidth and the supervisor call latency at EL0 and EL1 on this core",
+                                     action: bench::bench_user },
+                             Entry { key: 'g',
+                                     desc: "Measure the GPIO toggle rate on this core",
+                                     action: bench::bench_gpio },
+                             Entry { key: 'x',
+                                     desc: "Measure the mailbox round trip latency on this core",
+                                     action: bench::bench_mailbox },
+                             Entry { key: 'h',
+                                     desc: "Measure the supervisor call round trip latency on this core",
+                                     action: bench::bench_svc },
+                             Entry { key: 'j',
+                                     desc: "Measure the inter-core interrupt latency from this core to core #1",
+                                     action: bench::bench_sgi },
+                             Entry { key: 'd',
+                                     desc: "Compare DMA and CPU memory copies on this core",
+                                     action: bench::bench_dma },
+                             Entry { key: 'i',
+                                     desc: "Measure the interference between CPU stores and DMA copies on this core",
+                                     action: bench::bench_interference },
+                             Entry { key: 'c',
+                                     desc: "Measure the SD card sequential read bandwidth on this core",
+                                     action: bench::bench_emmc },
+                             Entry { key: 'n',
+                                     desc: "Measure the hardware random number generator throughput on this core",
+                                     action: bench::bench_rng },
+                             Entry { key: 'p',
+                                     desc: "Compare framebuffer and DRAM write bandwidth on this core",
+                                     action: bench::bench_framebuffer },
+                             Entry { key: 'm',
+                                     desc: "Test all the free RAM",
+                                     action: memtest::run },
+                             Entry { key: 'v',
+                                     desc: "Verify patterns across all the free RAM",
+                                     action: memtest::verify },
+                             Entry { key: 's',
+                                     desc: "Stress test the free RAM with walking bits and moving inversions",
+                                     action: memtest::stress },
+                             Entry { key: 'w',
+                                     desc: "Disable the watchdog",
+                                     action: disable_watchdog },
+                             Entry { key: 'r',
+                                     desc: "Reboot",
+                                     action: reboot },
+                             Entry { key: 'O',
+                                     desc: "Power off",
+                                     action: power_off }];
+
+/// Menu entry.
+struct Entry
+{
+    /// Key that selects the entry.
+    key: char,
+    /// Description.
+    desc: &'static str,
+    /// Action performed when the entry is selected.
+    action: fn(),
+}
+
+/// Presents the menu over the UART and performs the selected actions or runs
+/// the typed commands forever.
+pub fn run() -> !
+{
+    let mut buf = [0; cli::LINE_LEN];
+    loop {
+        let mut uart = UART.lock();
+        writeln!(uart, "Options:").unwrap();
+        for entry in ENTRIES.iter() {
+            writeln!(uart, "{}: {}", entry.key, entry.desc).unwrap();
+        }
+        write!(uart, "Select an option or type a command (help lists them): ").unwrap();
+        drop(uart);
+        let line = cli::read_line(&mut buf).trim();
+        let mut chars = line.chars();
+        match (chars.next(), chars.next()) {
+            (Some(key), None) => match ENTRIES.iter().find(|entry| entry.key == key) {
+                Some(entry) => (entry.action)(),
+                None => debug!("Unknown option: {key:?}"),
+            },
+            _ => cli::execute(line),
+        }
+    }
+}
+
+/// Runs the benchmark on all cores.
+fn bench_all()
+{
+    run_on(smp::ALL_CORES, bench::run)
+}
+
+/// Runs the throttling detection on all cores.
+fn throttle_all()
+{
+    run_on(smp::ALL_CORES, bench::throttle)
+}
+
+/// Runs a benchmark on a subset of the cores at the maximum ARM clock rate,
+/// reporting the SoC temperature before and after along with the combined
+/// write bandwidth recorded by the cores, between [`START_MARKER`] and
+/// [`COMPLETE_MARKER`].
+///
+/// * `mask`: Mask of the cores to run the benchmark on.
+/// * `job`: Benchmark to run, which records its write bandwidth in megabytes
+///   per second on every selected core.
+pub fn run_on(mask: u8, job: fn())
+{
+    debug!("{START_MARKER}");
+    bench::pin_clock();
+    bench::temperature("before");
+    smp::run_on(mask, job);
+    // The cores that are offline or have failed were left out of the job.
+    let mask = mask & smp::alive();
+    bench::temperature("after");
+    let results = smp::results();
+    debug!("Combined write bandwidth of cores 0b{mask:04b}: {}MB/s", results.iter().sum::<u64>());
+    bench::control_state();
+    // All the cores have met at the barrier at the end of the job by now.
+    debug!("{COMPLETE_MARKER}");
+}
+
+/// Runs the misaligned write benchmark at every misalignment in
+/// [`MISALIGNMENTS`].
+fn bench_unaligned()
+{
+    for offset in MISALIGNMENTS {
+        bench::bench_unaligned(offset);
+    }
+}
+
+/// Disables the watchdog, for instance to attach a debugger.
+fn disable_watchdog()
+{
+    watchdog::disable();
+    debug!("Watchdog disabled");
+}
+
+/// Reboots the board through PSCI if available, and with the watchdog
+/// otherwise or if PSCI fails.
+fn reboot()
+{
+    debug!("Rebooting");
+    if psci::available() {
+        UART.lock().flush();
+        let err = psci::system_reset();
+        debug!("Failed to reboot with PSCI, rebooting with the watchdog: {err}");
+    }
+    watchdog::reboot()
+}
+
+/// Powers the board off through PSCI, which is the only way to do so.
+fn power_off()
+{
+    if !psci::available() {
+        debug!("Powering off requires PSCI, which is not available");
+        return;
+    }
+    debug!("Powering off");
+    UART.lock().flush();
+    let err = psci::system_off();
+    debug!("Failed to power off with PSCI: {err}");
+}
@@ -0,0 +1,495 @@
+//! Memory management unit.
+//!
+//! The boot code only maps the kernel image, the peripherals, and the stacks,
+//! so any other memory has to be mapped at run time through the translation
+//! table that covers the first gigabyte of the address space.
+//!
+//! Everything is identity mapped with the 4KB translation granule, RAM in 2MB
+//! blocks of normal write-back cacheable memory apart from the kernel image,
+//! which is mapped in 4KB pages so that its sections get their own
+//! permissions, and the peripherals in 2MB blocks of Device-nGnRnE memory.
+//! [`translation`] reads back the configuration for the boot banner.  RAM can
+//! also be mapped in 4KB pages with [`map_pages`] to compare the reach of the
+//! TLBs with both mappings.
+//!
+//! Documentation:
+//!
+//! * [Arm Architecture Reference Manual for A-profile architecture](https://developer.arm.com/documentation/ddi0487/latest)
+//!   D8
+
+use core::arch::asm;
+use core::fmt::{Display, Formatter, Result as FormatResult};
+use core::ops::Range;
+use core::ptr::addr_of_mut;
+
+use crate::RAM_END;
+
+/// Size of each block mapped by the static translation table.
+pub const BLOCK_SIZE: usize = 0x200000;
+/// Size of each page mapped by a last level translation table.
+pub const PAGE_SIZE: usize = 0x1000;
+/// Number of records in a translation table.
+const TT_LEN: usize = 512;
+/// Type flag that turns a block descriptor into a table descriptor before the
+/// last level, or into a page descriptor at the last level.
+const TT_TABLE: u64 = 0x2;
+/// Block descriptor template for normal RAM without the memory attributes
+/// index and shareability fields.
+const RAM_BLOCK: u64 = 0x20 << 48 | 0x421;
+/// Page descriptor of the first page of the address space, which maps it as
+/// normal cacheable inner shareable memory that is writable but not
+/// executable at EL1.
+const FIRST_PAGE: u64 = 0x20 << 48 | 0x723;
+/// Shift of the memory attributes index field of a block descriptor.
+const ATTR_SHIFT: u64 = 2;
+/// Shift of the shareability field of a block descriptor.
+const SH_SHIFT: u64 = 8;
+/// Access permissions flag of a block descriptor that makes the block
+/// accessible at EL0 as well as at EL1.
+const AP_EL0: u64 = 0x40;
+/// Memory attributes index of the peripherals in the memory attribute
+/// indirection register.
+const PERIPHERAL_ATTR: u64 = 2;
+/// Mask of the size offset field of the translation control register.
+const TCR_T0SZ: u64 = 0x3F;
+/// Shift of the granule size field of the translation control register.
+const TCR_TG0_SHIFT: u64 = 14;
+/// Shift of the intermediate physical address size field of the translation
+/// control register.
+const TCR_IPS_SHIFT: u64 = 32;
+/// Physical address sizes in bits indexed by the intermediate physical
+/// address size field of the translation control register.
+const PA_BITS: [u32; 8] = [32, 36, 40, 42, 44, 48, 52, 0];
+/// Fault flag of the physical address register.
+const PAR_F: u64 = 0x1;
+/// Shift of the memory attributes field of the physical address register.
+const PAR_ATTR_SHIFT: u64 = 56;
+/// MMU enable flag of the system control register.
+const SCTLR_M: u64 = 0x1;
+/// Alignment check enable flag of the system control register.
+const SCTLR_A: u64 = 0x2;
+/// Stack alignment check enable flag of the system control register.
+const SCTLR_SA: u64 = 0x8;
+/// Data cache enable flag of the system control register.
+const SCTLR_C: u64 = 0x4;
+/// Instruction cache enable flag of the system control register.
+const SCTLR_I: u64 = 0x1000;
+/// EL0 access to the cache zeroing instruction flag of the system control
+/// register.
+const SCTLR_DZE: u64 = 0x4000;
+/// EL0 access to the cache type register flag of the system control register.
+const SCTLR_UCT: u64 = 0x8000;
+/// EL0 access to the cache maintenance instructions flag of the system control
+/// register.
+const SCTLR_UCI: u64 = 0x4000000;
+
+// RAM is only ever mapped in whole blocks of the static translation table.
+const _: () = assert!(RAM_END % BLOCK_SIZE == 0 && RAM_END <= BLOCK_SIZE * TT_LEN,
+                      "The static translation table cannot map RAM up to this end");
+
+extern "C" {
+    /// Translation table covering the first gigabyte of the address
+    /// space, defined in the boot code.
+    static mut static_tt: [u64; TT_LEN];
+    /// Translation table covering the first block of the address space in
+    /// pages, defined in the boot code.
+    static mut static_detail_tt: [u64; TT_LEN];
+}
+
+/// Shareability domain of a mapping.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u64)]
+pub enum Shareability
+{
+    /// Not shared with any other agent.
+    Non = 0b00,
+    /// Shared with the outer shareable domain.
+    Outer = 0b10,
+    /// Shared with the inner shareable domain, which includes all the cores.
+    Inner = 0b11,
+}
+
+/// Memory attributes of a mapping, as indices into the memory attribute
+/// indirection register set up by the boot code.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u64)]
+pub enum Attributes
+{
+    /// Normal write-back cacheable memory.
+    Cacheable = 0,
+    /// Normal non-cacheable memory.
+    NonCacheable = 1,
+    /// Device-nGnRE memory, which allows early write acknowledgement unlike
+    /// the Device-nGnRnE memory that the peripherals are mapped as.
+    Device = 3,
+}
+
+/// Value of the system control register, formatted for display along with the
+/// state of the MMU and the caches.
+#[derive(Clone, Copy, Debug)]
+pub struct Sctlr(pub u64);
+
+/// Value of the system control register, formatted for display as the state of
+/// the flags that control which cache related instructions and registers are
+/// accessible at EL0.
+#[derive(Clone, Copy, Debug)]
+pub struct UserAccess(pub u64);
+
+/// Translation configuration of the calling core, formatted for display.
+#[derive(Clone, Copy, Debug)]
+pub struct Translation
+{
+    /// Value of the translation control register.
+    tcr: u64,
+    /// Value of the memory attribute indirection register.
+    mair: u64,
+}
+
+/// Identity maps a range of RAM as normal cacheable inner shareable memory.
+///
+/// * `range`: Range of physical addresses to map, which must be aligned to
+///   [`BLOCK_SIZE`] and must not overlap the block containing the kernel image.
+pub fn map_ram(range: Range<usize>)
+{
+    remap_ram(range, Shareability::Inner)
+}
+
+/// Maps or unmaps the first page of the address space, which holds the
+/// firmware spin table and is otherwise left unmapped so that dereferencing a
+/// null pointer faults.
+///
+/// * `mapped`: Whether to map the page.
+pub fn map_first_page(mapped: bool)
+{
+    let tt = unsafe { addr_of_mut!(static_detail_tt) }.cast::<u64>();
+    unsafe {
+        tt.write_volatile(if mapped { FIRST_PAGE } else { 0 });
+        asm!("dsb ishst",
+             "tlbi vmalle1is",
+             "dsb ish",
+             "isb",
+             options (nostack, preserves_flags))
+    };
+}
+
+/// Identity maps a range of RAM as normal cacheable memory in a shareability
+/// domain, replacing any previous mapping with break-before-make.
+///
+/// The range must be cleaned and invalidated from the data cache before
+/// changing its shareability, since cached lines mapped with different
+/// attributes lose coherency.
+///
+/// * `range`: Range of physical addresses to map, which must be aligned to
+///   [`BLOCK_SIZE`] and must not overlap the block containing the kernel image.
+/// * `sh`: Shareability domain.
+pub fn remap_ram(range: Range<usize>, sh: Shareability)
+{
+    map(range, Attributes::Cacheable, sh)
+}
+
+/// Identity maps a range of memory with the given attributes, replacing any
+/// previous mapping with break-before-make.
+///
+/// The range must be cleaned and invalidated from the data cache before
+/// changing its attributes, since cached lines mapped with different
+/// attributes lose coherency.
+///
+/// * `range`: Range of physical addresses to map, which must be aligned to
+///   [`BLOCK_SIZE`] and must not overlap the block containing the kernel image.
+/// * `attrs`: Memory attributes.
+/// * `sh`: Shareability domain, which the hardware ignores for non-cacheable
+///   and device memory.
+pub fn map(range: Range<usize>, attrs: Attributes, sh: Shareability)
+{
+    write_blocks(range, RAM_BLOCK | (attrs as u64) << ATTR_SHIFT | (sh as u64) << SH_SHIFT)
+}
+
+/// Invalidates all the TLB entries of the EL2 translation regime on the calling
+/// core, which [`map`] and its relatives leave alone, even though the EL2
+/// translation regime shares the translation tables with EL1 when the
+/// firmware booted the kernel at EL2.
+///
+/// Must be called at EL2, such as through [`hyp::call`](crate::hyp::call),
+/// before running code that accesses remapped memory at EL2 and after
+/// remapping memory that was accessed at EL2.
+///
+/// * `_arg`: Ignored argument of the hypervisor call.
+///
+/// Returns zero.
+pub extern "C" fn invalidate_el2(_arg: usize) -> usize
+{
+    unsafe {
+        asm!("dsb ish",
+             "tlbi alle2",
+             "dsb nsh",
+             "isb",
+             options (nostack, preserves_flags))
+    };
+    0
+}
+
+/// Identity maps a range of RAM as normal cacheable inner shareable memory
+/// that is both readable and writable at EL0, replacing any previous mapping
+/// with break-before-make.
+///
+/// The range stops being accessible at EL0 once mapped again with
+/// [`map_ram`].
+///
+/// * `range`: Range of physical addresses to map, which must be aligned to
+///   [`BLOCK_SIZE`] and must not overlap the block containing the kernel image.
+pub fn map_user(range: Range<usize>)
+{
+    write_blocks(range,
+                 RAM_BLOCK | AP_EL0 | (Attributes::Cacheable as u64) << ATTR_SHIFT | (Shareability::Inner as u64) << SH_SHIFT)
+}
+
+/// Identity maps a range of RAM as normal cacheable inner shareable memory in
+/// pages of [`PAGE_SIZE`] bytes instead of blocks, replacing any previous
+/// mapping with break-before-make, so that covering it takes 512 times as many
+/// TLB entries.
+///
+/// The range goes back to blocks once mapped again with [`map_ram`].
+///
+/// * `range`: Range of physical addresses to map, which must be aligned to
+///   [`BLOCK_SIZE`] and must not overlap the block containing the kernel image.
+/// * `tables`: Physical address of room for a translation table of
+///   [`PAGE_SIZE`] bytes per block of the range, which must be aligned to
+///   [`PAGE_SIZE`], mapped, outside of the range, and left alone until the
+///   range is mapped in blocks again.
+pub fn map_pages(range: Range<usize>, tables: usize)
+{
+    assert!(tables % PAGE_SIZE == 0, "Translation tables at 0x{tables:X} are not page aligned");
+    let page = RAM_BLOCK | TT_TABLE | (Attributes::Cacheable as u64) << ATTR_SHIFT | (Shareability::Inner as u64) << SH_SHIFT;
+    for (idx, block) in range.clone().step_by(BLOCK_SIZE).enumerate() {
+        let tt = (tables + idx * PAGE_SIZE) as *mut u64;
+        for entry in 0 .. TT_LEN {
+            unsafe { tt.add(entry).write_volatile(page | (block + entry * PAGE_SIZE) as u64) };
+        }
+    }
+    let start = range.start;
+    write_records(range, |addr| 0x1 | TT_TABLE | (tables + (addr - start) / BLOCK_SIZE * PAGE_SIZE) as u64);
+}
+
+/// Writes block descriptors for a range of memory to the static translation
+/// table with break-before-make.
+///
+/// * `range`: Range of physical addresses to map, which must be aligned to
+///   [`BLOCK_SIZE`] and must not overlap the block containing the kernel image.
+/// * `block`: Block descriptor template without the output address.
+fn write_blocks(range: Range<usize>, block: u64)
+{
+    write_records(range, |addr| block | addr as u64)
+}
+
+/// Writes records for a range of memory to the static translation table with
+/// break-before-make.
+///
+/// * `range`: Range of physical addresses to map, which must be aligned to
+///   [`BLOCK_SIZE`] and must not overlap the block containing the kernel image.
+/// * `record`: Record of the block at every address.
+fn write_records(range: Range<usize>, record: impl Fn(usize) -> u64)
+{
+    assert!(range.start % BLOCK_SIZE == 0 && range.end % BLOCK_SIZE == 0,
+            "Memory range 0x{:X} .. 0x{:X} is not block aligned",
+            range.start,
+            range.end);
+    assert!(range.start >= BLOCK_SIZE && range.end <= RAM_END,
+            "Memory range 0x{:X} .. 0x{:X} cannot be mapped",
+            range.start,
+            range.end);
+    let tt = unsafe { addr_of_mut!(static_tt) }.cast::<u64>();
+    for addr in range.clone().step_by(BLOCK_SIZE) {
+        unsafe { tt.add(addr / BLOCK_SIZE).write_volatile(0) };
+    }
+    unsafe {
+        asm!("dsb ishst",
+             "tlbi vmalle1is",
+             "dsb ish",
+             options (nostack, preserves_flags))
+    };
+    for addr in range.step_by(BLOCK_SIZE) {
+        unsafe { tt.add(addr / BLOCK_SIZE).write_volatile(record(addr)) };
+    }
+    unsafe {
+        asm!("dsb ishst",
+             "tlbi vmalle1is",
+             "dsb ish",
+             "isb",
+             options (nostack, preserves_flags))
+    };
+}
+
+/// Reads the encoding of memory attributes from the memory attribute
+/// indirection register.
+///
+/// * `attrs`: Memory attributes.
+///
+/// Returns the encoding.
+pub fn encoding(attrs: Attributes) -> u8
+{
+    let mair: u64;
+    unsafe { asm!("mrs {mair}, mair_el1", mair = out (reg) mair, options (nomem, nostack, preserves_flags)) };
+    (mair >> (attrs as u64 * 8)) as u8
+}
+
+/// Reads the translation configuration of the calling core.
+///
+/// Returns the configuration.
+pub fn translation() -> Translation
+{
+    let (tcr, mair): (u64, u64);
+    unsafe {
+        asm!("mrs {tcr}, tcr_el1",
+             "mrs {mair}, mair_el1",
+             tcr = out (reg) tcr,
+             mair = out (reg) mair,
+             options (nomem, nostack, preserves_flags))
+    };
+    Translation { tcr, mair }
+}
+
+/// Looks up the memory attributes that an address is mapped with by
+/// translating it for reading at EL1.
+///
+/// * `addr`: Virtual address to look up.
+///
+/// Returns the encoding of the memory attributes as found in the memory
+/// attribute indirection register, or `None` if the address is not mapped.
+pub fn attributes_at(addr: usize) -> Option<u8>
+{
+    let par: u64;
+    unsafe {
+        asm!("at s1e1r, {addr}",
+             "isb",
+             "mrs {par}, par_el1",
+             addr = in (reg) addr,
+             par = out (reg) par,
+             options (nomem, nostack, preserves_flags))
+    };
+    (par & PAR_F == 0).then_some((par >> PAR_ATTR_SHIFT) as u8)
+}
+
+/// Checks whether a range is mapped as normal memory that is readable at EL1,
+/// which can be read without side effects.
+///
+/// * `range`: Range of virtual addresses to check.
+///
+/// Returns whether the whole range is mapped as normal memory.
+pub fn readable(range: Range<usize>) -> bool
+{
+    // Device memory has the upper half of its encoding clear.
+    (range.start & !0xFFF .. range.end).step_by(0x1000)
+                                       .all(|page| attributes_at(page).is_some_and(|encoding| encoding >> 4 != 0))
+}
+
+/// Names the memory type of an encoding of memory attributes.
+///
+/// * `encoding`: Encoding as found in the memory attribute indirection
+///   register.
+///
+/// Returns the name of the memory type.
+pub fn attribute_name(encoding: u8) -> &'static str
+{
+    match encoding {
+        0xFF => "Normal write-back",
+        0x44 => "Normal non-cacheable",
+        0x00 => "Device-nGnRnE",
+        0x04 => "Device-nGnRE",
+        _ => "unknown",
+    }
+}
+
+/// Reads the system control register of the calling core.
+///
+/// Returns the value of the register.
+pub fn sctlr() -> Sctlr
+{
+    let sctlr: u64;
+    unsafe { asm!("mrs {sctlr}, sctlr_el1", sctlr = out (reg) sctlr, options (nomem, nostack, preserves_flags)) };
+    Sctlr(sctlr)
+}
+
+/// Enables or disables the alignment check on the calling core, which the boot
+/// code enables so that any misaligned access faults.
+///
+/// * `enabled`: Whether misaligned accesses fault.
+pub fn check_alignment(enabled: bool)
+{
+    unsafe {
+        let mut sctlr: u64;
+        asm!("mrs {sctlr}, sctlr_el1", sctlr = out (reg) sctlr, options (nomem, nostack, preserves_flags));
+        if enabled {
+            sctlr |= SCTLR_A;
+        } else {
+            sctlr &= !SCTLR_A;
+        }
+        asm!("msr sctlr_el1, {sctlr}",
+             "isb",
+             sctlr = in (reg) sctlr,
+             options (nostack, preserves_flags));
+    }
+}
+
+impl Display for Sctlr
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let state = |flag| if self.0 & flag != 0 { "on" } else { "off" };
+        write!(fmt,
+               "SCTLR 0x{:08X} (MMU: {}, D-cache: {}, I-cache: {}, Alignment check: {}, Stack alignment check: {})",
+               self.0,
+               state(SCTLR_M),
+               state(SCTLR_C),
+               state(SCTLR_I),
+               state(SCTLR_A),
+               state(SCTLR_SA))
+    }
+}
+
+impl Sctlr
+{
+    /// Returns whether the data and unified caches are enabled.
+    pub fn data_cache(self) -> bool
+    {
+        self.0 & SCTLR_C != 0
+    }
+}
+
+impl Display for Translation
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let granule = match self.tcr >> TCR_TG0_SHIFT & 0x3 {
+            0b00 => "4KB",
+            0b01 => "64KB",
+            0b10 => "16KB",
+            _ => "reserved",
+        };
+        let attrs = |idx: u64| {
+            let encoding = (self.mair >> (idx * 8)) as u8;
+            (attribute_name(encoding), encoding)
+        };
+        let (ram, ram_encoding) = attrs(Attributes::Cacheable as u64);
+        let (perry, perry_encoding) = attrs(PERIPHERAL_ATTR);
+        write!(fmt,
+               "Translation: {granule} granule, {}-bit VA, {}-bit PA, RAM: {ram} (0x{ram_encoding:02X}) in {}MB blocks, \
+                Peripherals: {perry} (0x{perry_encoding:02X}), TCR 0x{:X}",
+               64 - (self.tcr & TCR_T0SZ),
+               PA_BITS[(self.tcr >> TCR_IPS_SHIFT & 0x7) as usize],
+               BLOCK_SIZE >> 20,
+               self.tcr)
+    }
+}
+
+impl Display for UserAccess
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let state = |flag| if self.0 & flag != 0 { "allowed" } else { "trapped" };
+        write!(fmt,
+               "EL0 access (CTR_EL0: {}, cache maintenance: {}, DC ZVA: {})",
+               state(SCTLR_UCT),
+               state(SCTLR_UCI),
+               state(SCTLR_DZE))
+    }
+}
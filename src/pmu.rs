@@ -0,0 +1,109 @@
+//! Performance monitors.
+//!
+//! Documentation:
+//!
+//! * [Arm Architecture Reference Manual for A-profile architecture](https://developer.arm.com/documentation/ddi0487/latest)
+//!   D13
+
+use core::arch::asm;
+
+use crate::timer;
+
+/// Bus access event.
+pub const BUS_ACCESS: u32 = 0x19;
+/// Non-secure EL2 filter flag of the event type registers, which makes the
+/// counters count at EL2 as well as at EL1.
+const FILTER_NSH: u32 = 1 << 27;
+/// Duration of the delay over which cycles are counted to measure the clock
+/// rate in milliseconds.
+const RATE_MSECS: u64 = 10;
+
+/// Enables the cycle counter and the first event counter of the current CPU
+/// core.
+pub fn enable()
+{
+    unsafe {
+        asm!(
+            "mrs {tmp}, pmcr_el0",
+            "orr {tmp}, {tmp}, #0x1", // Enable the counters.
+            "orr {tmp}, {tmp}, #0x40", // Make the cycle counter 64-bit wide.
+            "msr pmcr_el0, {tmp}",
+            "msr pmccfiltr_el0, {filter}", // Count at EL1 and EL2.
+            "mov {tmp}, #1 << 31",
+            "orr {tmp}, {tmp}, #0x1",
+            "msr pmcntenset_el0, {tmp}", // Enable the cycle counter and event counter 0.
+            "isb",
+            tmp = out (reg) _,
+            filter = in (reg) FILTER_NSH as u64,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+}
+
+/// Reads the cycle counter of the current CPU core.
+///
+/// The read is preceded by an instruction barrier so that it cannot be
+/// performed ahead of the code that comes before it.
+///
+/// Returns the current cycle count.
+pub fn cycles() -> usize
+{
+    let cycles: usize;
+    unsafe {
+        asm!(
+            "isb",
+            "mrs {cycles}, pmccntr_el0",
+            cycles = out (reg) cycles,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+    cycles
+}
+
+/// Measures the clock rate of the current CPU core by counting cycles over a
+/// delay on the generic timer, so that the result is off by as much as the
+/// frequency programmed into the generic timer is.
+///
+/// Returns the measured clock rate in hertz.
+pub fn clock_rate() -> usize
+{
+    let start = cycles();
+    timer::delay_ms(RATE_MSECS);
+    (cycles() - start) * 1000 / RATE_MSECS as usize
+}
+
+/// Selects the event counted by the first event counter of the current CPU
+/// core at EL1 and EL2.
+///
+/// * `event`: Event number.
+pub fn select(event: u32)
+{
+    unsafe {
+        asm!(
+            "msr pmevtyper0_el0, {event:x}",
+            "isb",
+            event = in (reg) event | FILTER_NSH,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+}
+
+/// Reads the first event counter of the current CPU core.
+///
+/// The read is preceded by an instruction barrier so that it cannot be
+/// performed ahead of the code that comes before it.
+///
+/// Returns the current event count.
+pub fn events() -> usize
+{
+    let events: usize;
+    unsafe {
+        asm!(
+            "isb",
+            "mrs {events}, pmevcntr0_el0",
+            events = out (reg) events,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+    events
+}
@@ -0,0 +1,346 @@
+//! Power State Coordination Interface client.
+//!
+//! Firmware that implements PSCI describes it in the device tree along with
+//! the conduit that reaches it, so the device tree passed by the firmware is
+//! searched for a `psci` node and the interface is only used if it answers a
+//! version query through the described conduit.  The stock Raspberry Pi
+//! firmware parks the secondary cores in a spin table instead and has nothing
+//! at EL3 to answer a secure monitor call, so probing the conduit blindly could
+//! hang the board.
+//!
+//! When the firmware boots the kernel at EL2, the hypervisor call conduit
+//! would reach the kernel itself, so only the secure monitor call conduit is
+//! used and calls are made from EL2, which makes the secondary cores started
+//! with [`cpu_on`] enter the kernel at EL2 as well.
+//!
+//! Documentation:
+//!
+//! * [Arm Power State Coordination Interface](https://developer.arm.com/documentation/den0022/latest)
+//!   5
+//! * [Devicetree Specification](https://www.devicetree.org/specifications/)
+//!   5
+
+use core::arch::asm;
+use core::fmt::{Display, Formatter, Result as FormatResult};
+use core::slice;
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::mmu::{self, BLOCK_SIZE};
+use crate::{debug, hyp, RAM_END};
+
+/// Function identifier of the version query.
+const PSCI_VERSION: u32 = 0x84000000;
+/// Function identifier of starting a core with 64-bit arguments.
+const CPU_ON: u32 = 0xC4000003;
+/// Function identifier of powering the system off.
+const SYSTEM_OFF: u32 = 0x84000008;
+/// Function identifier of resetting the system.
+const SYSTEM_RESET: u32 = 0x84000009;
+/// Magic number at the start of a flattened device tree.
+const FDT_MAGIC: u32 = 0xD00DFEED;
+/// Token that starts a node in the structure block of a flattened device
+/// tree.
+const FDT_BEGIN_NODE: u32 = 0x1;
+/// Token that ends a node in the structure block of a flattened device tree.
+const FDT_END_NODE: u32 = 0x2;
+/// Token that starts a property in the structure block of a flattened device
+/// tree.
+const FDT_PROP: u32 = 0x3;
+/// Token that is ignored in the structure block of a flattened device tree.
+const FDT_NOP: u32 = 0x4;
+
+/// Conduit that reaches the firmware, or zero if PSCI is not available.
+static CONDUIT: AtomicU8 = AtomicU8::new(0);
+
+/// Conduit that reaches the firmware.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum Conduit
+{
+    /// Secure monitor calls.
+    Smc = 1,
+    /// Hypervisor calls.
+    Hvc = 2,
+}
+
+/// PSCI errors.
+#[derive(Clone, Copy, Debug)]
+pub enum Error
+{
+    /// The function is not implemented.
+    NotSupported,
+    /// An argument is invalid.
+    InvalidParameters,
+    /// The caller is not allowed to perform the operation.
+    Denied,
+    /// The core is already on.
+    AlreadyOn,
+    /// The core is already being turned on.
+    OnPending,
+    /// The firmware failed internally.
+    InternalFailure,
+    /// The core does not exist.
+    NotPresent,
+    /// The core is disabled.
+    Disabled,
+    /// The entry point address is invalid.
+    InvalidAddress,
+    /// The firmware returned an undocumented error code.
+    Unknown(i32),
+}
+
+/// Looks for PSCI in the device tree and checks that it answers through the
+/// described conduit, reporting the outcome.
+///
+/// Must only be called once from core #0 before any other function in this
+/// module.
+///
+/// * `dtb`: Address of the device tree passed by the firmware.
+pub fn init(dtb: usize)
+{
+    let Some(conduit) = conduit(dtb) else {
+        debug!("PSCI: Not described by the device tree at 0x{dtb:X}");
+        return;
+    };
+    if conduit == Conduit::Hvc && hyp::available() {
+        debug!("PSCI: Described with the HVC conduit, which is unusable when booted at EL2");
+        return;
+    }
+    CONDUIT.store(conduit as u8, Ordering::Relaxed);
+    let version = invoke(PSCI_VERSION, 0, 0, 0);
+    if let Err(err) = result(version) {
+        CONDUIT.store(0, Ordering::Relaxed);
+        debug!("PSCI: Version query over {conduit:?} failed: {err}");
+        return;
+    }
+    debug!("PSCI: Version {}.{} over {conduit:?}", version >> 16, version & 0xFFFF);
+}
+
+/// Returns whether PSCI is available.
+pub fn available() -> bool
+{
+    CONDUIT.load(Ordering::Relaxed) != 0
+}
+
+/// Starts a core at an entry point.
+///
+/// Must only be called if [`available`] returns `true`.
+///
+/// * `core`: Logical index of the core, which is also its affinity on the
+///   Raspberry Pi.
+/// * `entry`: Physical address at which the core enters the kernel with the
+///   MMU disabled.
+///
+/// Returns an error if the core could not be started.
+pub fn cpu_on(core: usize, entry: usize) -> Result<(), Error>
+{
+    result(invoke(CPU_ON, core, entry, 0)).map(|_| ())
+}
+
+/// Resets the system.
+///
+/// Must only be called if [`available`] returns `true`.
+///
+/// Returns the error that prevented the reset, since it does not return
+/// otherwise.
+pub fn system_reset() -> Error
+{
+    result(invoke(SYSTEM_RESET, 0, 0, 0)).err().unwrap_or(Error::InternalFailure)
+}
+
+/// Powers the system off.
+///
+/// Must only be called if [`available`] returns `true`.
+///
+/// Returns the error that prevented powering off, since it does not return
+/// otherwise.
+pub fn system_off() -> Error
+{
+    result(invoke(SYSTEM_OFF, 0, 0, 0)).err().unwrap_or(Error::InternalFailure)
+}
+
+/// Calls a PSCI function, from EL2 if the kernel was booted at EL2.
+///
+/// * `func`: Function identifier.
+/// * `arg1`: First argument.
+/// * `arg2`: Second argument.
+/// * `arg3`: Third argument.
+///
+/// Returns the value returned by the function.
+fn invoke(func: u32, arg1: usize, arg2: usize, arg3: usize) -> i32
+{
+    let mut args = [func as usize, arg1, arg2, arg3];
+    if hyp::available() {
+        hyp::call(invoke_el2, &mut args as *mut [usize; 4] as usize) as i32
+    } else {
+        call(args) as i32
+    }
+}
+
+/// Calls a PSCI function at EL2.
+///
+/// * `arg`: Address of the function identifier and the arguments.
+///
+/// Returns the value returned by the function.
+extern "C" fn invoke_el2(arg: usize) -> usize
+{
+    call(unsafe { *(arg as *const [usize; 4]) })
+}
+
+/// Calls a PSCI function through the conduit.
+///
+/// * `args`: Function identifier and arguments.
+///
+/// Returns the value returned by the function.
+fn call(args: [usize; 4]) -> usize
+{
+    let [mut res, arg1, arg2, arg3] = args;
+    unsafe {
+        if CONDUIT.load(Ordering::Relaxed) == Conduit::Hvc as u8 {
+            asm!("hvc #0",
+                 inout ("x0") res,
+                 inout ("x1") arg1 => _,
+                 inout ("x2") arg2 => _,
+                 inout ("x3") arg3 => _,
+                 clobber_abi ("C"),
+                 options (nostack))
+        } else {
+            asm!("smc #0",
+                 inout ("x0") res,
+                 inout ("x1") arg1 => _,
+                 inout ("x2") arg2 => _,
+                 inout ("x3") arg3 => _,
+                 clobber_abi ("C"),
+                 options (nostack))
+        }
+    };
+    res
+}
+
+/// Decodes the value returned by a PSCI function.
+///
+/// * `val`: Returned value.
+///
+/// Returns the value if it is not an error code, or the decoded error.
+fn result(val: i32) -> Result<i32, Error>
+{
+    match val {
+        -1 => Err(Error::NotSupported),
+        -2 => Err(Error::InvalidParameters),
+        -3 => Err(Error::Denied),
+        -4 => Err(Error::AlreadyOn),
+        -5 => Err(Error::OnPending),
+        -6 => Err(Error::InternalFailure),
+        -7 => Err(Error::NotPresent),
+        -8 => Err(Error::Disabled),
+        -9 => Err(Error::InvalidAddress),
+        val if val < 0 => Err(Error::Unknown(val)),
+        val => Ok(val),
+    }
+}
+
+/// Searches a flattened device tree for the conduit of a `psci` node under
+/// the root, mapping the device tree along the way.
+///
+/// * `dtb`: Address of the device tree.
+///
+/// Returns the conduit, or `None` if there's no device tree at the address,
+/// it cannot be mapped, or it does not describe PSCI.
+fn conduit(dtb: usize) -> Option<Conduit>
+{
+    if dtb < BLOCK_SIZE || dtb % 4 != 0 || dtb >= RAM_END - BLOCK_SIZE {
+        return None;
+    }
+    let base = dtb & !(BLOCK_SIZE - 1);
+    mmu::map_ram(base .. base + BLOCK_SIZE);
+    let header = unsafe { slice::from_raw_parts(dtb as *const u8, 8) };
+    if be32(header, 0)? != FDT_MAGIC {
+        return None;
+    }
+    let size = be32(header, 4)? as usize;
+    let end = (dtb + size).next_multiple_of(BLOCK_SIZE);
+    if end > RAM_END {
+        return None;
+    }
+    mmu::map_ram(base .. end);
+    let fdt = unsafe { slice::from_raw_parts(dtb as *const u8, size) };
+    let strings = be32(fdt, 12)? as usize;
+    let mut off = be32(fdt, 8)? as usize;
+    let mut depth = 0;
+    let mut psci = false;
+    loop {
+        let token = be32(fdt, off)?;
+        off += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = name(fdt, off)?;
+                off += (name.len() + 1).next_multiple_of(4);
+                depth += 1;
+                // The root node is at depth 1.
+                psci = depth == 2 && (name == b"psci" || name.starts_with(b"psci@"));
+            }
+            FDT_END_NODE => {
+                depth -= 1;
+                psci = false;
+            }
+            FDT_PROP => {
+                let len = be32(fdt, off)? as usize;
+                let prop = name(fdt, strings + be32(fdt, off + 4)? as usize)?;
+                let val = fdt.get(off + 8 .. off + 8 + len)?;
+                off += 8 + len.next_multiple_of(4);
+                if psci && prop == b"method" {
+                    return match val {
+                        b"smc\0" => Some(Conduit::Smc),
+                        b"hvc\0" => Some(Conduit::Hvc),
+                        _ => None,
+                    };
+                }
+            }
+            FDT_NOP => (),
+            // The end token or anything malformed.
+            _ => return None,
+        }
+    }
+}
+
+/// Reads a big endian word from a device tree.
+///
+/// * `fdt`: Device tree.
+/// * `off`: Offset of the word.
+///
+/// Returns the word, or `None` if it is out of bounds.
+fn be32(fdt: &[u8], off: usize) -> Option<u32>
+{
+    Some(u32::from_be_bytes(fdt.get(off .. off + 4)?.try_into().unwrap()))
+}
+
+/// Reads a null terminated name from a device tree.
+///
+/// * `fdt`: Device tree.
+/// * `off`: Offset of the name.
+///
+/// Returns the name without the terminator, or `None` if it is out of bounds.
+fn name(fdt: &[u8], off: usize) -> Option<&[u8]>
+{
+    let tail = fdt.get(off ..)?;
+    Some(&tail[.. tail.iter().position(|&byte| byte == 0)?])
+}
+
+impl Display for Error
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        match self {
+            Self::NotSupported => write!(fmt, "Function not supported"),
+            Self::InvalidParameters => write!(fmt, "Invalid parameters"),
+            Self::Denied => write!(fmt, "Operation denied"),
+            Self::AlreadyOn => write!(fmt, "Core already on"),
+            Self::OnPending => write!(fmt, "Core already being turned on"),
+            Self::InternalFailure => write!(fmt, "Internal firmware failure"),
+            Self::NotPresent => write!(fmt, "Core not present"),
+            Self::Disabled => write!(fmt, "Core disabled"),
+            Self::InvalidAddress => write!(fmt, "Invalid entry point address"),
+            Self::Unknown(code) => write!(fmt, "Unknown error code {code}"),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Free RAM.
+//!
+//! All the RAM reported by the firmware beyond the stacks is free for the
+//! benchmarks and tests to use, and is identity mapped the first time that it
+//! is requested.
+
+use core::ops::Range;
+use core::ptr::addr_of;
+
+use crate::mmu::{self, BLOCK_SIZE};
+use crate::sync::Lazy;
+use crate::{debug, mbox, CPU_COUNT, RAM_END};
+
+/// Free RAM range.
+static FREE: Lazy<Range<usize>> = Lazy::new(detect);
+
+extern "C" {
+    /// End of the stacks, defined in the boot code.
+    static stacks_end: u8;
+}
+
+/// Returns the range of free RAM, which is empty if detection failed.
+pub fn free() -> Range<usize>
+{
+    FREE.clone()
+}
+
+/// Splits the free RAM among the logical CPUs.
+///
+/// * `core`: Logical CPU whose share to return.
+///
+/// Returns the block aligned share of the free RAM belonging to the logical
+/// CPU.
+pub fn share(core: usize) -> Range<usize>
+{
+    let free = free();
+    let len = ((free.end - free.start) / CPU_COUNT) & !(BLOCK_SIZE - 1);
+    let start = free.start + len * core;
+    start .. start + len
+}
+
+/// Detects and maps the free RAM.
+///
+/// Returns the detected range.
+fn detect() -> Range<usize>
+{
+    let ram = match mbox::arm_memory() {
+        Ok(ram) => ram,
+        Err(err) => {
+            debug!("Failed to detect the RAM size: {err}");
+            return 0 .. 0;
+        }
+    };
+    // Only the first gigabyte can be mapped, and on the Pi 3 only below the
+    // peripherals, which always includes all the RAM reported by the firmware
+    // as the rest is reserved for the VideoCore and the peripherals anyway.
+    let start = unsafe { addr_of!(stacks_end) } as usize;
+    let end = ram.end.min(RAM_END) & !(BLOCK_SIZE - 1);
+    if start >= end {
+        return 0 .. 0;
+    }
+    mmu::map_ram(start .. end);
+    start .. end
+}
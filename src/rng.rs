@@ -0,0 +1,124 @@
+//! Hardware random number generator driver.
+//!
+//! The BCM2711 has an RNG200 block whereas the older SoCs have a simpler
+//! block at the same offset with a different register layout, so the variant
+//! is selected by the part number of the cores.  Waiting for random data times
+//! out instead of hanging, in case the detection picks the wrong variant.
+//!
+//! Documentation:
+//!
+//! * [Linux BCM2711 RNG200 driver](https://github.com/raspberrypi/linux/blob/rpi-6.1.y/drivers/char/hw_random/iproc-rng200.c)
+//! * [Linux BCM2835 RNG driver](https://github.com/raspberrypi/linux/blob/rpi-6.1.y/drivers/char/hw_random/bcm2835-rng.c)
+
+use core::arch::asm;
+use core::fmt::{Display, Formatter, Result as FormatResult};
+use core::hint::spin_loop;
+
+use crate::{timer, PERRY_RANGE};
+
+/// Base address of the RNG registers.
+const RNG_BASE: usize = 0x2104000 + PERRY_RANGE.start;
+/// Control register.
+const RNG_CTRL: *mut u32 = RNG_BASE as _;
+/// Status register of the BCM2835 variant.
+const RNG_STATUS: *mut u32 = (RNG_BASE + 0x4) as _;
+/// Data register of the BCM2835 variant.
+const RNG_DATA: *const u32 = (RNG_BASE + 0x8) as _;
+/// Total bit count threshold register of the RNG200 variant.
+const RNG200_TOTAL_BIT_COUNT_THRESHOLD: *mut u32 = (RNG_BASE + 0x10) as _;
+/// FIFO data register of the RNG200 variant.
+const RNG200_FIFO_DATA: *const u32 = (RNG_BASE + 0x20) as _;
+/// FIFO count register of the RNG200 variant.
+const RNG200_FIFO_COUNT: *const u32 = (RNG_BASE + 0x24) as _;
+/// Generator enable mask of the control register.
+const CTRL_RBGEN_MASK: u32 = 0x1FFF;
+/// Number of bits discarded after enabling the generator while it warms up.
+const WARMUP_BITS: u32 = 0x40000;
+/// Part number of the Cortex-A72 found in the BCM2711.
+const CORTEX_A72: usize = 0xD08;
+/// Timeout waiting for random data in milliseconds.
+const TIMEOUT_MSECS: usize = 100;
+
+/// RNG block variant.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Variant
+{
+    /// RNG200 block found in the BCM2711.
+    Rng200,
+    /// Block found in the BCM2835, BCM2836, and BCM2837.
+    Bcm2835,
+}
+
+/// RNG errors.
+#[derive(Clone, Copy, Debug)]
+pub enum Error
+{
+    /// No random data became available in time.
+    Timeout,
+}
+
+impl Display for Error
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        match self {
+            Self::Timeout => write!(fmt, "RNG produced no data within {TIMEOUT_MSECS} milliseconds"),
+        }
+    }
+}
+
+/// Detects the variant of the RNG block from the part number of the cores.
+///
+/// Returns the detected variant.
+pub fn variant() -> Variant
+{
+    let midr: usize;
+    unsafe { asm!("mrs {midr}, midr_el1", midr = out (reg) midr, options (nomem, nostack, preserves_flags)) };
+    if midr >> 4 & 0xFFF == CORTEX_A72 {
+        Variant::Rng200
+    } else {
+        Variant::Bcm2835
+    }
+}
+
+/// Enables the generator unless it is already running.
+pub fn enable()
+{
+    unsafe {
+        if RNG_CTRL.read_volatile() & CTRL_RBGEN_MASK != 0 {
+            return;
+        }
+        match variant() {
+            Variant::Rng200 => RNG200_TOTAL_BIT_COUNT_THRESHOLD.write_volatile(WARMUP_BITS),
+            Variant::Bcm2835 => RNG_STATUS.write_volatile(WARMUP_BITS),
+        }
+        RNG_CTRL.write_volatile(0x1);
+    }
+}
+
+/// Reads a random word, waiting for one to become available.
+///
+/// * `variant`: Variant of the RNG block.
+///
+/// Returns the random word, or an error if none became available in time.
+pub fn read(variant: Variant) -> Result<u32, Error>
+{
+    let start = timer::now();
+    let timeout = timer::frequency() / 1000 * TIMEOUT_MSECS;
+    loop {
+        let available = match variant {
+            Variant::Rng200 => unsafe { RNG200_FIFO_COUNT.read_volatile() & 0xFF },
+            Variant::Bcm2835 => unsafe { RNG_STATUS.read_volatile() >> 24 },
+        };
+        if available != 0 {
+            return Ok(match variant {
+                          Variant::Rng200 => unsafe { RNG200_FIFO_DATA.read_volatile() },
+                          Variant::Bcm2835 => unsafe { RNG_DATA.read_volatile() },
+                      });
+        }
+        if timer::elapsed(start, timer::now()) >= timeout {
+            return Err(Error::Timeout);
+        }
+        spin_loop()
+    }
+}
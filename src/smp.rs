@@ -0,0 +1,277 @@
+//! Multi-core job dispatching.
+//!
+//! Core #0 runs the interactive menu while the secondary cores wait for jobs
+//! to be posted.  Every job runs simultaneously on the cores selected by a
+//! mask, while the others park at the barrier at the end of the job, and core
+//! #0 only returns from [`run_on`] once all the cores have met there, so it
+//! coordinates the reporting even when it isn't selected itself.  Jobs meet
+//! the other selected cores with [`sync`].
+//!
+//! Jobs can record a result per core with [`record`] without taking any lock,
+//! so recording doesn't serialize the cores right at the end of their
+//! measurements, and core #0 can read all of them with [`results`] once
+//! [`run_on`] returns.
+//!
+//! Once [`isolate`] is enabled, a secondary core that panics marks itself as
+//! failed with [`fail`] and leaves both barriers before halting, so that the
+//! other cores complete the job without it and it's never selected again.
+//!
+//! Core #0 starts the secondary cores with [`start_secondaries`], through PSCI
+//! if the firmware implements it and from the firmware spin table otherwise,
+//! and every secondary core checks in when it starts serving jobs.  Core #0
+//! waits up to [`CHECK_IN_MSECS`] for them in [`check_in`] and carries on
+//! without any core that doesn't make it, which halts itself if it shows up
+//! later.
+
+use core::arch::asm;
+use core::array;
+use core::hint::spin_loop;
+use core::mem::transmute;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+
+use crate::sync::Barrier;
+use crate::{cache, core_index, debug, halt, mmu, psci, timer, watchdog, CPU_COUNT};
+
+/// Mask that selects all cores.
+pub const ALL_CORES: u8 = (1 << CPU_COUNT) - 1;
+/// Time given to the secondary cores to check in after booting in
+/// milliseconds.
+const CHECK_IN_MSECS: usize = 1000;
+/// Flag of the checked in mask set once core #0 stops waiting for the
+/// secondary cores to check in.
+const CHECK_IN_CLOSED: u8 = 0x80;
+/// Address of the spin table entry of core #0 in the first page, which is
+/// followed by the entries of the other cores in order.
+const SPIN_TABLE: usize = 0xD8;
+
+/// Address of the last posted job.
+static JOB: AtomicUsize = AtomicUsize::new(0);
+/// Mask of the cores selected for the last posted job.
+static MASK: AtomicU8 = AtomicU8::new(ALL_CORES);
+/// Number of jobs posted so far.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+/// Barrier at which all the cores that have not failed meet after running a
+/// job.
+static BARRIER: Barrier = Barrier::new(CPU_COUNT);
+/// Barrier at which the cores selected for the current job meet.
+static SYNC: Barrier = Barrier::new(CPU_COUNT);
+/// Results recorded by every core during the last job.
+static RESULTS: [AtomicU64; CPU_COUNT] = [const { AtomicU64::new(0) }; CPU_COUNT];
+/// Mask of the cores that came online and have not failed.
+static ALIVE: AtomicU8 = AtomicU8::new(ALL_CORES);
+/// Whether a panicking secondary core only halts itself.
+static ISOLATE: AtomicBool = AtomicBool::new(false);
+/// Mask of the cores that have checked in, along with [`CHECK_IN_CLOSED`].
+static CHECKED_IN: AtomicU8 = AtomicU8::new(0x1);
+
+extern "C" {
+    /// Entry point of the boot code.
+    fn boot();
+}
+
+/// Starts the secondary cores at the boot code, trying PSCI first for each of
+/// them and falling back to releasing it from the firmware spin table,
+/// reporting which mechanism started every core.
+///
+/// Either way a woken core enters [`boot`] with the MMU and caches disabled, at
+/// the same exception level as core #0, with nothing in its registers that the
+/// boot code relies on.  The boot code derives everything from the core field
+/// of its affinity instead: its exception stack is the one at that index in
+/// the exception stacks, and its EL0 stack is the one at that index below the
+/// top of the address space, so the woken cores need no stack set up on their
+/// behalf.  The boot code then skips zeroing the BSS and building the
+/// translation tables, which core #0 has done already, and enters the Rust
+/// code at EL1, which sends the core to [`serve`].
+///
+/// Must only be called once from core #0 after initializing PSCI.
+pub fn start_secondaries()
+{
+    let entry = boot as *const () as usize;
+    let mut parked = 0u8;
+    for core in 1 .. CPU_COUNT {
+        if psci::available() {
+            match psci::cpu_on(core, entry) {
+                Ok(()) => {
+                    debug!("Core #{core} started with PSCI");
+                    continue;
+                }
+                Err(err) => debug!("Failed to start core #{core} with PSCI, falling back to the spin table: {err}"),
+            }
+        }
+        parked |= 1 << core;
+    }
+    if parked == 0 {
+        return;
+    }
+    // The parked cores poll their entries with the MMU disabled, so the entries
+    // have to reach the point of coherency before they are woken up.
+    mmu::map_first_page(true);
+    for core in (1 .. CPU_COUNT).filter(|core| parked & 1 << core != 0) {
+        let addr = SPIN_TABLE + core * 8;
+        unsafe { (addr as *mut u64).write_volatile(entry as u64) };
+        cache::clean_invalidate(addr .. addr + 8);
+    }
+    unsafe { asm!("sev", options (nomem, nostack, preserves_flags)) };
+    mmu::map_first_page(false);
+    debug!("Cores 0b{parked:04b} released from the spin table");
+}
+
+/// Waits for the secondary cores to check in, reporting every core that does
+/// not within [`CHECK_IN_MSECS`] and leaving it out of all the jobs.
+///
+/// Must only be called once from core #0 before posting any job.
+pub fn check_in()
+{
+    let ticks = timer::frequency() / 1000 * CHECK_IN_MSECS;
+    let start = timer::now();
+    while CHECKED_IN.load(Ordering::Acquire) != ALL_CORES && timer::elapsed(start, timer::now()) < ticks {
+        watchdog::pet();
+        spin_loop();
+    }
+    let missing = ALL_CORES & !CHECKED_IN.fetch_or(CHECK_IN_CLOSED, Ordering::AcqRel);
+    if missing == 0 {
+        debug!("All {CPU_COUNT} cores checked in");
+        return;
+    }
+    for core in (0 .. CPU_COUNT).filter(|core| missing & 1 << core != 0) {
+        debug!("WARNING: Core #{core} did not check in within {CHECK_IN_MSECS}ms, carrying on without it");
+        ALIVE.fetch_and(!(1 << core), Ordering::AcqRel);
+        BARRIER.leave();
+    }
+    debug!("{} of {CPU_COUNT} cores online: 0b{:04b}", alive().count_ones(), alive());
+}
+
+/// Runs a job on a subset of the cores.
+///
+/// Must only be called from core #0.
+///
+/// * `mask`: Mask with a bit set for every logical index of a core to run the
+///   job on, which must select at least one core and no core beyond
+///   [`CPU_COUNT`], with the failed cores left out.
+/// * `job`: Job to run.
+pub fn run_on(mask: u8, job: fn())
+{
+    assert!(mask != 0 && mask & !ALL_CORES == 0, "Invalid core mask: 0b{mask:b}");
+    let alive = alive();
+    if mask & !alive != 0 {
+        debug!("Leaving out the failed cores 0b{:04b}", mask & !alive);
+    }
+    let mask = mask & alive;
+    watchdog::pet();
+    RESULTS.iter().for_each(|result| result.store(0, Ordering::Relaxed));
+    JOB.store(job as usize, Ordering::Relaxed);
+    MASK.store(mask, Ordering::Relaxed);
+    SYNC.resize(mask.count_ones() as usize);
+    GENERATION.fetch_add(1, Ordering::Release);
+    unsafe { asm!("sev", options (nomem, nostack, preserves_flags)) };
+    if mask & 0x1 != 0 {
+        job();
+    }
+    BARRIER.wait();
+}
+
+/// Selects whether a panicking secondary core only halts itself or leaves the
+/// other cores waiting for it forever.
+///
+/// * `enabled`: Whether a panicking secondary core only halts itself.
+pub fn isolate(enabled: bool)
+{
+    ISOLATE.store(enabled, Ordering::Relaxed);
+}
+
+/// Marks the calling core as failed and removes it from the barriers if it is
+/// a secondary core and [`isolate`] is enabled, so that the other cores can
+/// carry on without it.
+///
+/// Must only be called right before halting the calling core.
+///
+/// Returns whether the calling core was removed.
+pub fn fail() -> bool
+{
+    let core = core_index();
+    if core == 0 || !ISOLATE.load(Ordering::Relaxed) {
+        return false;
+    }
+    let bit = 1 << core;
+    // A nested panic must not leave the barriers twice.
+    if ALIVE.fetch_and(!bit, Ordering::AcqRel) & bit == 0 {
+        return true;
+    }
+    if mask() & bit != 0 {
+        SYNC.leave();
+    }
+    BARRIER.leave();
+    true
+}
+
+/// Returns the mask of the cores that came online and have not failed.
+pub fn alive() -> u8
+{
+    ALIVE.load(Ordering::Acquire)
+}
+
+/// Returns the mask of the cores selected for the current or last job.
+pub fn mask() -> u8
+{
+    MASK.load(Ordering::Relaxed)
+}
+
+/// Returns the lowest logical index of the cores selected for the current or
+/// last job, which is the one that reports the results aggregated across
+/// them.
+pub fn leader() -> usize
+{
+    mask().trailing_zeros() as usize
+}
+
+/// Blocks the calling core until all the other cores selected for the current
+/// job have arrived.
+///
+/// Must only be called from a job.
+pub fn sync()
+{
+    SYNC.wait();
+}
+
+/// Records the result of the current job on the calling core.
+///
+/// * `result`: Result to record.
+pub fn record(result: u64)
+{
+    RESULTS[core_index()].store(result, Ordering::Release);
+}
+
+/// Reads the results recorded by every core during the last job.
+///
+/// Must only be called from core #0 after [`run_on`] returns, since every core
+/// records its result before arriving at the barrier at the end of the job and
+/// the barrier's release orders the recording before core #0 leaves it, which
+/// makes the acquire loads here see the recorded results.
+///
+/// Returns the results, which are zero for the cores that recorded none.
+pub fn results() -> [u64; CPU_COUNT]
+{
+    array::from_fn(|core| RESULTS[core].load(Ordering::Acquire))
+}
+
+/// Runs the jobs posted by core #0 on a secondary core.
+pub fn serve() -> !
+{
+    let core = core_index();
+    if CHECKED_IN.fetch_or(1 << core, Ordering::AcqRel) & CHECK_IN_CLOSED != 0 {
+        debug!("Core #{core} checked in too late");
+        halt();
+    }
+    let mut generation = 0;
+    loop {
+        while GENERATION.load(Ordering::Acquire) == generation {
+            unsafe { asm!("wfe", options (nomem, nostack, preserves_flags)) };
+        }
+        generation += 1;
+        if mask() & 1 << core != 0 {
+            let job = unsafe { transmute::<usize, fn()>(JOB.load(Ordering::Relaxed)) };
+            job();
+        }
+        BARRIER.wait();
+    }
+}
@@ -0,0 +1,202 @@
+//! STREAM memory bandwidth benchmark.
+//!
+//! The Copy, Scale, Add, and Triad kernels run over three arrays of double
+//! precision numbers that are far larger than the caches, with the arrays
+//! initialized and the traffic counted as in the reference implementation, so
+//! that only the bytes named by every kernel are counted and not the line
+//! fills caused by write allocation.  Bandwidths are reported in megabytes of
+//! 10^6 bytes per second like the reference implementation does, rather than
+//! in the binary megabytes reported by the other benchmarks, so the results
+//! compare directly with STREAM results from other platforms.
+//!
+//! Documentation:
+//!
+//! * [STREAM: Sustainable Memory Bandwidth in High Performance Computers](https://www.cs.virginia.edu/stream/)
+
+use core::arch::asm;
+use core::mem::size_of;
+
+use crate::bench::{self, Measurement};
+use crate::{core_index, debug, ram, timer};
+
+/// Number of elements of each array.
+const ARRAY_LEN: usize = 0x100000;
+/// Size of each array in bytes.
+const ARRAY_SIZE: usize = ARRAY_LEN * size_of::<f64>();
+/// Scalar used by the Scale and Triad kernels.
+const SCALAR: f64 = 3.0;
+/// Kernels along with their names and the number of arrays that they access.
+const KERNELS: [Kernel; 4] = [("Copy", 2, copy),
+                              ("Scale", 2, scale),
+                              ("Add", 3, add),
+                              ("Triad", 3, triad)];
+
+/// Kernel along with its name and the number of arrays that it accesses.
+type Kernel = (&'static str, usize, fn(&Arrays));
+
+/// Arrays operated on by the kernels.
+#[derive(Debug)]
+struct Arrays
+{
+    /// First array.
+    a: *mut f64,
+    /// Second array.
+    b: *mut f64,
+    /// Third array.
+    c: *mut f64,
+}
+
+/// Runs the STREAM kernels on the calling core in order, reporting the
+/// bandwidth of each and validating the arrays at the end.
+pub fn run()
+{
+    let core = core_index();
+    let share = ram::share(core);
+    if share.len() < ARRAY_SIZE * 3 {
+        debug!("Core #{core} does not have enough free RAM for the STREAM benchmark");
+        return;
+    }
+    let base = share.start as *mut f64;
+    let arrays = unsafe { Arrays { a: base,
+                                   b: base.add(ARRAY_LEN),
+                                   c: base.add(ARRAY_LEN * 2) } };
+    for idx in 0 .. ARRAY_LEN {
+        unsafe {
+            arrays.a.add(idx).write(1.0);
+            arrays.b.add(idx).write(2.0);
+            arrays.c.add(idx).write(0.0);
+        }
+    }
+    debug!("Core #{core} STREAM arrays of {ARRAY_LEN} elements ({}MB each)",
+           ARRAY_SIZE >> 20);
+    for (name, count, kernel) in KERNELS {
+        // Every kernel only reads arrays that it does not write, so repeating
+        // it leaves the arrays as they were after its first pass.
+        let Some(Measurement { iterations, ticks, .. }) = bench::measure(|iterations| {
+                                                                         for _ in 0 .. iterations {
+                                                                             kernel(&arrays);
+                                                                         }
+                                                                     },
+                                                                     || kernel(&arrays))
+        else {
+            continue;
+        };
+        let bytes = (iterations * ARRAY_SIZE * count) as u128;
+        let rate = bytes * timer::frequency() as u128 / ticks as u128 / 1000000;
+        debug!("Core #{core} STREAM {name}: {rate}MB/s ({iterations} iterations)");
+    }
+    // After Copy, Scale, Add, and Triad in that order, with every value exactly
+    // representable.
+    let expected = [SCALAR + SCALAR * (1.0 + SCALAR), SCALAR, 1.0 + SCALAR];
+    for (name, array, expected) in [("a", arrays.a, expected[0]), ("b", arrays.b, expected[1]), ("c", arrays.c, expected[2])] {
+        for idx in 0 .. ARRAY_LEN {
+            let actual = unsafe { array.add(idx).read() };
+            assert!(actual == expected,
+                    "Core #{core} STREAM validation failed at {name}[{idx}]: Expected: {expected}, Actual: {actual}");
+        }
+    }
+}
+
+/// Copy kernel, which computes `c = a`.
+///
+/// * `arrays`: Arrays to operate on.
+fn copy(arrays: &Arrays)
+{
+    unsafe {
+        asm!(
+            "0:",
+            "ldp {x0:q}, {x1:q}, [{a}], #32",
+            "stp {x0:q}, {x1:q}, [{c}], #32",
+            "cmp {c}, {ec}",
+            "bne 0b",
+            a = inout (reg) arrays.a => _,
+            c = inout (reg) arrays.c => _,
+            ec = in (reg) arrays.c.wrapping_add(ARRAY_LEN),
+            x0 = out (vreg) _,
+            x1 = out (vreg) _,
+            options (nostack)
+        );
+    }
+}
+
+/// Scale kernel, which computes `b = scalar * c`.
+///
+/// * `arrays`: Arrays to operate on.
+fn scale(arrays: &Arrays)
+{
+    unsafe {
+        asm!(
+            "0:",
+            "ldp {x0:q}, {x1:q}, [{c}], #32",
+            "fmul {x0:v}.2d, {x0:v}.2d, {s:v}.d[0]",
+            "fmul {x1:v}.2d, {x1:v}.2d, {s:v}.d[0]",
+            "stp {x0:q}, {x1:q}, [{b}], #32",
+            "cmp {b}, {eb}",
+            "bne 0b",
+            b = inout (reg) arrays.b => _,
+            c = inout (reg) arrays.c => _,
+            eb = in (reg) arrays.b.wrapping_add(ARRAY_LEN),
+            s = in (vreg) SCALAR,
+            x0 = out (vreg) _,
+            x1 = out (vreg) _,
+            options (nostack)
+        );
+    }
+}
+
+/// Add kernel, which computes `c = a + b`.
+///
+/// * `arrays`: Arrays to operate on.
+fn add(arrays: &Arrays)
+{
+    unsafe {
+        asm!(
+            "0:",
+            "ldp {x0:q}, {x1:q}, [{a}], #32",
+            "ldp {y0:q}, {y1:q}, [{b}], #32",
+            "fadd {x0:v}.2d, {x0:v}.2d, {y0:v}.2d",
+            "fadd {x1:v}.2d, {x1:v}.2d, {y1:v}.2d",
+            "stp {x0:q}, {x1:q}, [{c}], #32",
+            "cmp {c}, {ec}",
+            "bne 0b",
+            a = inout (reg) arrays.a => _,
+            b = inout (reg) arrays.b => _,
+            c = inout (reg) arrays.c => _,
+            ec = in (reg) arrays.c.wrapping_add(ARRAY_LEN),
+            x0 = out (vreg) _,
+            x1 = out (vreg) _,
+            y0 = out (vreg) _,
+            y1 = out (vreg) _,
+            options (nostack)
+        );
+    }
+}
+
+/// Triad kernel, which computes `a = b + scalar * c`.
+///
+/// * `arrays`: Arrays to operate on.
+fn triad(arrays: &Arrays)
+{
+    unsafe {
+        asm!(
+            "0:",
+            "ldp {x0:q}, {x1:q}, [{b}], #32",
+            "ldp {y0:q}, {y1:q}, [{c}], #32",
+            "fmla {x0:v}.2d, {y0:v}.2d, {s:v}.d[0]",
+            "fmla {x1:v}.2d, {y1:v}.2d, {s:v}.d[0]",
+            "stp {x0:q}, {x1:q}, [{a}], #32",
+            "cmp {a}, {ea}",
+            "bne 0b",
+            a = inout (reg) arrays.a => _,
+            b = inout (reg) arrays.b => _,
+            c = inout (reg) arrays.c => _,
+            ea = in (reg) arrays.a.wrapping_add(ARRAY_LEN),
+            s = in (vreg) SCALAR,
+            x0 = out (vreg) _,
+            x1 = out (vreg) _,
+            y0 = out (vreg) _,
+            y1 = out (vreg) _,
+            options (nostack)
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Scalable Vector Extension.
+//!
+//! None of the cores found in the Raspberry Pi boards implement SVE, so SVE
+//! instructions only ever appear in functions that are never called unless
+//! [`implemented`] says otherwise.  The boot code only stops trapping the
+//! FP/SIMD instructions, which [`enable_fp`] makes sure of again from the Rust
+//! code and [`access`] reports along with the SVE traps.
+//!
+//! Documentation:
+//!
+//! * [Arm Architecture Reference Manual for A-profile architecture](https://developer.arm.com/documentation/ddi0487/latest)
+//!   D1.5, D19.2
+
+use core::arch::asm;
+use core::fmt::{Display, Formatter, Result as FormatResult};
+
+use crate::hyp;
+
+/// Shift of the SVE field of the processor feature register 0.
+const PFR0_SVE_SHIFT: u64 = 32;
+/// SVE access enable field of the architectural feature access control
+/// register, which stops trapping SVE instructions at EL1 and EL0.
+const CPACR_ZEN: u64 = 0x3 << 16;
+/// FP/SIMD access enable field of the architectural feature access control
+/// register, which stops trapping FP/SIMD instructions at EL1 and EL0.
+const CPACR_FPEN: u64 = 0x3 << 20;
+/// FP/SIMD trap flag of the architectural feature trap register at EL2.
+const CPTR_TFP: u64 = 0x400;
+/// SVE trap flag of the architectural feature trap register at EL2.
+const CPTR_TZ: u64 = 0x100;
+/// Largest vector length field of the SVE control register, which the core
+/// clamps to the largest vector length that it implements.
+const ZCR_LEN_MAX: u64 = 0xF;
+
+/// State of the FP/SIMD and SVE traps of the calling core, formatted for
+/// display.
+#[derive(Clone, Copy, Debug)]
+pub struct Access
+{
+    /// Value of the architectural feature access control register.
+    cpacr: u64,
+    /// Value of the architectural feature trap register at EL2, if the kernel
+    /// was booted at EL2.
+    cptr: Option<u64>,
+}
+
+/// Reads the state of the FP/SIMD and SVE traps of the calling core at EL1
+/// and, if the kernel was booted at EL2, at EL2.
+///
+/// Returns the state of the traps.
+pub fn access() -> Access
+{
+    let cpacr: u64;
+    unsafe { asm!("mrs {cpacr}, cpacr_el1", cpacr = out (reg) cpacr, options (nomem, nostack, preserves_flags)) };
+    let cptr = hyp::available().then(|| hyp::call(read_cptr, 0) as u64);
+    Access { cpacr, cptr }
+}
+
+/// Reads the architectural feature trap register at EL2.
+///
+/// Returns the value of the register.
+extern "C" fn read_cptr(_: usize) -> usize
+{
+    let cptr: usize;
+    unsafe { asm!("mrs {cptr}, cptr_el2", cptr = out (reg) cptr, options (nomem, nostack, preserves_flags)) };
+    cptr
+}
+
+/// Stops trapping the FP/SIMD instructions at EL1 and EL0 on the calling core
+/// and, if the kernel was booted at EL2, at EL2, once again.
+///
+/// The boot code must already have done so before entering Rust, since the
+/// compiler is free to use the FP/SIMD registers in any function, including
+/// this one, so this only re-checks the requirement of the vector kernels at
+/// every level they run at, and a boot path that misses it faults with
+/// trapped FP/SIMD accesses before getting here.
+///
+/// Must be called on every core before running any benchmark.
+pub fn enable_fp()
+{
+    unsafe {
+        asm!(
+            "mrs {tmp}, cpacr_el1",
+            "orr {tmp}, {tmp}, {fpen}",
+            "msr cpacr_el1, {tmp}",
+            "isb",
+            tmp = out (reg) _,
+            fpen = in (reg) CPACR_FPEN,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+    if hyp::available() {
+        hyp::call(enable_fp_el2, 0);
+    }
+}
+
+/// Stops trapping the FP/SIMD instructions at EL2.
+extern "C" fn enable_fp_el2(_: usize) -> usize
+{
+    unsafe {
+        asm!(
+            "mrs {tmp}, cptr_el2",
+            "bic {tmp}, {tmp}, {tfp}",
+            "msr cptr_el2, {tmp}",
+            "isb",
+            tmp = out (reg) _,
+            tfp = in (reg) CPTR_TFP,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+    0
+}
+
+/// Checks whether the calling core implements SVE.
+///
+/// Returns whether SVE is implemented.
+pub fn implemented() -> bool
+{
+    let pfr0: u64;
+    unsafe { asm!("mrs {pfr0}, id_aa64pfr0_el1", pfr0 = out (reg) pfr0, options (nomem, nostack, preserves_flags)) };
+    pfr0 >> PFR0_SVE_SHIFT & 0xF != 0
+}
+
+/// Enables SVE on the calling core with the largest vector length that it
+/// implements.
+///
+/// Must only be called if [`implemented`] returns `true`.
+///
+/// Returns the vector length in bytes.
+#[inline(never)]
+pub fn enable() -> usize
+{
+    assert!(implemented(), "SVE is not implemented");
+    let len: usize;
+    unsafe {
+        asm!(
+            ".arch_extension sve",
+            "mrs {tmp}, cpacr_el1",
+            "orr {tmp}, {tmp}, {zen}",
+            "msr cpacr_el1, {tmp}",
+            "isb",
+            "msr s3_0_c1_c2_0, {zcr}", // ZCR_EL1.
+            "isb",
+            "rdvl {len}, #1",
+            tmp = out (reg) _,
+            zen = in (reg) CPACR_ZEN,
+            zcr = in (reg) ZCR_LEN_MAX,
+            len = out (reg) len,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+    len
+}
+
+impl Display for Access
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let state = |field: u64, trap: u64| {
+            if self.cpacr & field != field || self.cptr.is_some_and(|cptr| cptr & trap != 0) {
+                "trapped"
+            } else {
+                "enabled"
+            }
+        };
+        write!(fmt, "CPACR_EL1 0x{:08X}, CPTR_EL2 ", self.cpacr)?;
+        match self.cptr {
+            Some(cptr) => write!(fmt, "0x{cptr:08X}")?,
+            None => write!(fmt, "N/A")?,
+        }
+        write!(fmt,
+               " (FP/SIMD: {}, SVE: {})",
+               state(CPACR_FPEN, CPTR_TFP),
+               state(CPACR_ZEN, CPTR_TZ))
+    }
+}
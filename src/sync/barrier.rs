@@ -0,0 +1,90 @@
+//! Barrier synchronization.
+
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Shift of the number of logical CPUs that have to arrive in the state of a
+/// barrier.
+const COUNT_SHIFT: u32 = 32;
+/// Mask of the number of logical CPUs that have arrived in the state of a
+/// barrier.
+const ARRIVED_MASK: usize = (1 << COUNT_SHIFT) - 1;
+
+/// Reusable barrier at which a number of logical CPUs meet.
+///
+/// The number of logical CPUs that have to arrive and the number that have
+/// arrived share a single atomic word, so that exactly one of the logical CPUs
+/// arriving at or leaving the barrier observes it becoming complete and
+/// releases it.
+#[derive(Debug)]
+pub struct Barrier
+{
+    /// Number of logical CPUs that have to arrive to release the barrier,
+    /// shifted by [`COUNT_SHIFT`], along with the number of logical CPUs that
+    /// have arrived so far.
+    state: AtomicUsize,
+    /// Number of times that the barrier has been released.
+    generation: AtomicUsize,
+}
+
+impl Barrier
+{
+    /// Creates and initializes a new barrier.
+    ///
+    /// * `count`: Number of logical CPUs that have to arrive to release the
+    ///   barrier.
+    ///
+    /// Returns the newly created barrier.
+    pub const fn new(count: usize) -> Self
+    {
+        Self { state: AtomicUsize::new(count << COUNT_SHIFT),
+               generation: AtomicUsize::new(0) }
+    }
+
+    /// Changes the number of logical CPUs that have to arrive to release the
+    /// barrier.
+    ///
+    /// Must not be called while any logical CPU is waiting at the barrier.
+    ///
+    /// * `count`: Number of logical CPUs that have to arrive to release the
+    ///   barrier.
+    pub fn resize(&self, count: usize)
+    {
+        self.state.store(count << COUNT_SHIFT, Ordering::Relaxed);
+    }
+
+    /// Blocks the calling logical CPU until all the others have arrived.
+    pub fn wait(&self)
+    {
+        let generation = self.generation.load(Ordering::Acquire);
+        let state = self.state.fetch_add(1, Ordering::AcqRel) + 1;
+        if state & ARRIVED_MASK == state >> COUNT_SHIFT {
+            self.release();
+            return;
+        }
+        while self.generation.load(Ordering::Acquire) == generation {
+            spin_loop()
+        }
+    }
+
+    /// Permanently removes one logical CPU from the logical CPUs that have to
+    /// arrive, releasing the others if they were only waiting for it.
+    ///
+    /// Must only be called on behalf of a logical CPU that is not waiting at
+    /// the barrier and will never wait at it again.
+    pub fn leave(&self)
+    {
+        let state = self.state.fetch_sub(1 << COUNT_SHIFT, Ordering::AcqRel) - (1 << COUNT_SHIFT);
+        let arrived = state & ARRIVED_MASK;
+        if arrived != 0 && arrived == state >> COUNT_SHIFT {
+            self.release();
+        }
+    }
+
+    /// Releases the logical CPUs waiting at the barrier.
+    fn release(&self)
+    {
+        self.state.fetch_and(!ARRIVED_MASK, Ordering::Relaxed);
+        self.generation.fetch_add(1, Ordering::Release);
+    }
+}
@@ -1,8 +1,18 @@
 //! Locking primitives.
+//!
+//! Locks are ticket locks, so they are granted in the order in which they were
+//! requested and no logical CPU can starve under contention.  Waiting logical
+//! CPUs sleep with `wfe` on an exclusive monitor armed on the ticket being
+//! served, and are woken up when its value changes, which keeps the bus quiet
+//! while they spin.
+//!
+//! A lock records the logical CPU holding it, so that the panic handler can
+//! take over a hold left behind by the code that panicked on the same logical
+//! CPU instead of waiting for it forever.
 
-use core::cell::UnsafeCell;
 #[cfg(not(test))]
-use core::hint::spin_loop;
+use core::arch::asm;
+use core::cell::UnsafeCell;
 use core::marker::PhantomData;
 use core::ops::{Deref, DerefMut};
 #[cfg(test)]
@@ -11,7 +21,7 @@ use core::sync::atomic::{AtomicBool, Ordering};
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 #[cfg(not(test))]
-use crate::{cpu_id, CPU_COUNT};
+use crate::{core_index, CPU_COUNT};
 
 /// Lock guard whose lifetime determines how long the lock is held.
 #[derive(Debug)]
@@ -41,6 +51,10 @@ pub struct Advisor
 {
     /// The Logical CPU that currently holds the lock.
     affinity: AtomicUsize,
+    /// Next ticket to hand out.
+    next: AtomicUsize,
+    /// Ticket currently allowed to hold the lock.
+    serving: AtomicUsize,
 }
 
 /// Dummy lock advisor implementation for tests.
@@ -64,6 +78,18 @@ impl<'a, T: ?Sized> Guard<'a, T>
         Self { lock,
                _data: PhantomData }
     }
+
+    /// Creates a guard over a lock that the calling logical CPU already holds
+    /// without placing another hold on it.
+    ///
+    /// * `lock`: Lock to be released when this guard is dropped.
+    ///
+    /// Returns the newly created guard.
+    fn adopt(lock: &'a Lock<T>) -> Self
+    {
+        Self { lock,
+               _data: PhantomData }
+    }
 }
 
 impl<'a, T: ?Sized> Deref for Guard<'a, T>
@@ -115,6 +141,25 @@ impl<T: ?Sized> Lock<T>
     {
         Guard::new(self)
     }
+
+    /// Locks access to the content like [`lock`](Self::lock), unless the
+    /// calling logical CPU already holds the lock, in which case the hold is
+    /// taken over instead of deadlocking.
+    ///
+    /// The caller must ensure that the code holding the lock on this logical
+    /// CPU never resumes, as is the case in the panic handler, and must cope
+    /// with content that may have been left in an inconsistent state.
+    ///
+    /// Returns a [`Guard`] which allows access to the content and holds the
+    /// lock until dropped.
+    pub unsafe fn reclaim(&self) -> Guard<T>
+    {
+        if self.advisor.is_held() {
+            Guard::adopt(self)
+        } else {
+            Guard::new(self)
+        }
+    }
 }
 
 #[cfg(not(test))]
@@ -125,7 +170,9 @@ impl Advisor
     /// Returns the newly created lock advisor.
     pub const fn new() -> Self
     {
-        Self { affinity: AtomicUsize::new(CPU_COUNT) }
+        Self { affinity: AtomicUsize::new(CPU_COUNT),
+               next: AtomicUsize::new(0),
+               serving: AtomicUsize::new(0) }
     }
 
     /// Places a hold on the lock, blocking the logical CPU if another logical
@@ -134,15 +181,35 @@ impl Advisor
     /// The caller must ensure that this is called before a critical section.
     pub unsafe fn lock(&self)
     {
-        let affinity = cpu_id();
+        let affinity = core_index();
         assert!(self.affinity.load(Ordering::Relaxed) != affinity,
                 "Deadlock detected on core #{affinity}");
-        while self.affinity
-                  .compare_exchange_weak(CPU_COUNT, affinity, Ordering::SeqCst, Ordering::Relaxed)
-                  .is_err()
-        {
-            spin_loop()
-        }
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+        // The load-acquire exclusive arms the monitor, so any write to the
+        // ticket being served by the holder generates the event that wakes
+        // this logical CPU up.  No wake-up can be missed, since a write that
+        // lands between the load and the wait sets the event register and
+        // makes the wait return immediately, which is why releasing the lock
+        // doesn't need a `sev`.
+        asm!(
+            "sevl",
+            "0:",
+            "wfe",
+            "ldaxr {serving}, [{addr}]",
+            "cmp {serving}, {ticket}",
+            "bne 0b",
+            addr = in (reg) self.serving.as_ptr(),
+            ticket = in (reg) ticket,
+            serving = out (reg) _,
+            options (nostack)
+        );
+        self.affinity.store(affinity, Ordering::Relaxed);
+    }
+
+    /// Returns whether the calling logical CPU holds the lock.
+    pub fn is_held(&self) -> bool
+    {
+        self.affinity.load(Ordering::Relaxed) == core_index()
     }
 
     /// Relinquishes the hold on a lock, unblocking another logical CPU that
@@ -154,10 +221,12 @@ impl Advisor
     /// section.
     pub unsafe fn unlock(&self)
     {
-        let affinity = cpu_id();
+        let affinity = core_index();
         assert!(affinity == self.affinity.load(Ordering::Relaxed),
                 "Core #{affinity} attempted to relinquish a lock that it doesn't hold");
-        self.affinity.store(CPU_COUNT, Ordering::SeqCst);
+        self.affinity.store(CPU_COUNT, Ordering::Relaxed);
+        let serving = self.serving.load(Ordering::Relaxed);
+        self.serving.store(serving.wrapping_add(1), Ordering::Release);
     }
 }
 
@@ -175,6 +244,11 @@ impl Advisor
         self.is_locked.store(true, Ordering::Relaxed);
     }
 
+    pub fn is_held(&self) -> bool
+    {
+        self.is_locked.load(Ordering::Relaxed)
+    }
+
     pub unsafe fn unlock(&self)
     {
         assert!(self.is_locked.load(Ordering::Relaxed),
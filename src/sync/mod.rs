@@ -1,9 +1,13 @@
 //! Synchronization primitives.
 
+mod barrier;
 #[cfg(not(test))]
 mod lazy;
 mod lock;
+mod rwlock;
 
+pub use self::barrier::Barrier;
 #[cfg(not(test))]
 pub use self::lazy::Lazy;
 pub use self::lock::{Guard as LockGuard, Lock};
+pub use self::rwlock::RwLock;
@@ -0,0 +1,235 @@
+//! Reader-writer locks.
+//!
+//! Any number of readers can hold a [`RwLock`] at the same time as long as no
+//! writer holds it.  Writers take precedence: once a writer is waiting, new
+//! readers wait too, so writers can't starve under continuous reading, though
+//! readers can starve under continuous writing.  Waiting writers compete for
+//! the lock among themselves without any particular order.
+//!
+//! Waiting logical CPUs sleep with `wfe` on an exclusive monitor armed on the
+//! word that they are waiting for, like those waiting for a
+//! [`Lock`](super::Lock).
+
+#[cfg(not(test))]
+use core::arch::asm;
+use core::cell::UnsafeCell;
+#[cfg(test)]
+use core::hint::spin_loop;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// State flag that indicates that a writer holds the lock, with the remaining
+/// bits counting the readers holding it.
+const WRITER: usize = 1 << (usize::BITS - 1);
+
+/// Reader-writer lock container.
+#[derive(Debug)]
+pub struct RwLock<T: ?Sized>
+{
+    /// Writer flag and number of readers holding the lock.
+    state: AtomicUsize,
+    /// Number of writers waiting for or holding the lock.
+    writers: AtomicUsize,
+    /// Protected content.
+    content: UnsafeCell<T>,
+}
+
+/// Read guard whose lifetime determines how long the lock is held for
+/// reading.
+#[derive(Debug)]
+pub struct ReadGuard<'a, T: ?Sized>
+{
+    /// Lock to be released once this guard is dropped.
+    lock: &'a RwLock<T>,
+    /// Zero-sized field to remove the Send trait.
+    _data: PhantomData<*mut ()>,
+}
+
+/// Write guard whose lifetime determines how long the lock is held for
+/// writing.
+#[derive(Debug)]
+pub struct WriteGuard<'a, T: ?Sized>
+{
+    /// Lock to be released once this guard is dropped.
+    lock: &'a RwLock<T>,
+    /// Zero-sized field to remove the Send trait.
+    _data: PhantomData<*mut ()>,
+}
+
+impl<T: ?Sized> RwLock<T>
+{
+    /// Creates and initializes a new reader-writer lock.
+    ///
+    /// `content`: Content to protect.
+    ///
+    /// Returns the newly created reader-writer lock.
+    pub const fn new(content: T) -> Self
+        where T: Sized
+    {
+        Self { state: AtomicUsize::new(0),
+               writers: AtomicUsize::new(0),
+               content: UnsafeCell::new(content) }
+    }
+
+    /// Locks access to the content for reading, blocking execution while a
+    /// writer is waiting for or holding the lock.
+    ///
+    /// Returns a [`ReadGuard`] which allows shared access to the content and
+    /// holds the lock until dropped.
+    pub fn read(&self) -> ReadGuard<'_, T>
+    {
+        loop {
+            wait_zero(&self.writers);
+            let state = self.state.fetch_add(1, Ordering::Acquire);
+            if state & WRITER == 0 && self.writers.load(Ordering::Relaxed) == 0 {
+                break;
+            }
+            // Back off so that the waiting writer can get in.  A writer that
+            // holds the lock meanwhile only clears its own flag on release,
+            // so this count survives it.
+            self.state.fetch_sub(1, Ordering::Relaxed);
+        }
+        ReadGuard { lock: self,
+                    _data: PhantomData }
+    }
+
+    /// Locks access to the content for writing, blocking execution while
+    /// readers or another writer hold the lock.
+    ///
+    /// Returns a [`WriteGuard`] which allows exclusive access to the content
+    /// and holds the lock until dropped.
+    pub fn write(&self) -> WriteGuard<'_, T>
+    {
+        self.writers.fetch_add(1, Ordering::Relaxed);
+        loop {
+            wait_zero(&self.state);
+            if self.state
+                   .compare_exchange_weak(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+                   .is_ok()
+            {
+                break;
+            }
+        }
+        WriteGuard { lock: self,
+                     _data: PhantomData }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for ReadGuard<'a, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &'a Self::Target
+    {
+        unsafe { &*self.lock.content.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for ReadGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<'a, T: ?Sized> Deref for WriteGuard<'a, T>
+{
+    type Target = T;
+
+    fn deref(&self) -> &'a Self::Target
+    {
+        unsafe { &*self.lock.content.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for WriteGuard<'a, T>
+{
+    fn deref_mut(&mut self) -> &'a mut Self::Target
+    {
+        unsafe { &mut *self.lock.content.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for WriteGuard<'a, T>
+{
+    fn drop(&mut self)
+    {
+        self.lock.writers.fetch_sub(1, Ordering::Relaxed);
+        self.lock.state.fetch_and(!WRITER, Ordering::Release);
+    }
+}
+
+/// Blocks the logical CPU until an atomic word reads zero.
+///
+/// * `word`: Word to wait for.
+#[cfg(not(test))]
+fn wait_zero(word: &AtomicUsize)
+{
+    // Any write to the word by another logical CPU clears the monitor armed by
+    // the load exclusive and generates the event that wakes this logical CPU
+    // up, as in the ticket locks.
+    unsafe {
+        asm!(
+            "sevl",
+            "0:",
+            "wfe",
+            "ldxr {value}, [{addr}]",
+            "cbnz {value}, 0b",
+            addr = in (reg) word.as_ptr(),
+            value = out (reg) _,
+            options (nostack, readonly)
+        )
+    };
+}
+
+/// Spins until an atomic word reads zero, for tests.
+///
+/// * `word`: Word to wait for.
+#[cfg(test)]
+fn wait_zero(word: &AtomicUsize)
+{
+    while word.load(Ordering::Relaxed) != 0 {
+        spin_loop()
+    }
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    #[test]
+    fn read_write()
+    {
+        let lock = RwLock::new(0);
+        *lock.write() += 1;
+        let (first, second) = (lock.read(), lock.read());
+        assert_eq!((*first, *second), (1, 1));
+        drop((first, second));
+        assert_eq!(lock.state.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn reader_backs_off_across_writer_release()
+    {
+        let lock = RwLock::new(());
+        let guard = lock.write();
+        // A reader that passed the writer check before the writer arrived
+        // counts itself in while the writer holds the lock...
+        let state = lock.state.fetch_add(1, Ordering::Acquire);
+        assert_ne!(state & WRITER, 0);
+        drop(guard);
+        // ...and only backs off once the writer has released it.
+        lock.state.fetch_sub(1, Ordering::Relaxed);
+        assert_eq!(lock.state.load(Ordering::Relaxed), 0);
+        drop(lock.write());
+        drop(lock.read());
+        assert_eq!(lock.state.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,38 @@
+//! BCM system timer.
+//!
+//! The system timer is a free running 64-bit counter that ticks at 1MHz
+//! regardless of the configuration of the ARM cores, which makes it a good
+//! reference for the generic timer.
+//!
+//! Documentation:
+//!
+//! * [BCM2711 ARM Peripherals](https://datasheets.raspberrypi.com/bcm2711/bcm2711-peripherals.pdf)
+//!   10
+
+use crate::PERRY_RANGE;
+
+/// Base address of the system timer registers.
+const ST_BASE: usize = 0x2003000 + PERRY_RANGE.start;
+/// Lower word of the counter.
+const ST_CLO: *const u32 = (ST_BASE + 0x4) as _;
+/// Upper word of the counter.
+const ST_CHI: *const u32 = (ST_BASE + 0x8) as _;
+/// Frequency of the counter in hertz.
+pub const FREQUENCY: usize = 1000000;
+
+/// Reads the counter.
+///
+/// Returns the current count.
+pub fn now() -> usize
+{
+    unsafe {
+        let hi = ST_CHI.read_volatile();
+        let lo = ST_CLO.read_volatile();
+        // The lower word wrapped between the reads, so read it again along
+        // with the upper word that it wrapped into.
+        if ST_CHI.read_volatile() != hi {
+            return (ST_CHI.read_volatile() as usize) << 32 | ST_CLO.read_volatile() as usize;
+        }
+        (hi as usize) << 32 | lo as usize
+    }
+}
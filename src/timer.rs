@@ -0,0 +1,231 @@
+//! Benchmark timing.
+//!
+//! Benchmarks are timed with the ARM generic timer by default, or with the BCM
+//! system timer once selected, so that results can be reproduced with either
+//! clock.  Reading the timer is not free, so the median cost of two
+//! back-to-back reads is measured by [`calibrate`] and subtracted from every
+//! interval computed by [`elapsed`].
+//!
+//! Documentation:
+//!
+//! * [Arm Architecture Reference Manual for A-profile architecture](https://developer.arm.com/documentation/ddi0487/latest)
+//!   D11
+
+use core::arch::asm;
+use core::fmt::{Display, Formatter, Result as FormatResult};
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::systimer;
+
+/// Number of samples taken to calibrate the overhead of reading the timer.
+const CALIBRATION_SAMPLES: usize = 4096;
+/// Duration of the interval measured to cross-check the generic timer
+/// against the system timer, in system timer ticks.
+const CROSS_CHECK_TICKS: usize = 100000;
+
+/// Median overhead of reading the timer twice in ticks.
+static OVERHEAD: AtomicUsize = AtomicUsize::new(0);
+/// Whether benchmarks are timed with the system timer.
+static SYSTEM: AtomicBool = AtomicBool::new(false);
+
+/// Timing source.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Source
+{
+    /// ARM generic timer.
+    Generic,
+    /// BCM system timer.
+    System,
+}
+
+/// Interval in timer ticks, formatted for display in seconds for long
+/// intervals and in smaller units with three decimal places for short ones.
+#[derive(Clone, Copy, Debug)]
+pub struct Duration(pub usize);
+
+/// Reads the count of the selected timing source.
+///
+/// Returns the current count.
+pub fn now() -> usize
+{
+    match source() {
+        Source::Generic => generic_now(),
+        Source::System => systimer::now(),
+    }
+}
+
+/// Returns the frequency of the selected timing source in hertz.
+pub fn frequency() -> usize
+{
+    match source() {
+        Source::Generic => generic_frequency(),
+        Source::System => systimer::FREQUENCY,
+    }
+}
+
+/// Returns the selected timing source.
+pub fn source() -> Source
+{
+    if SYSTEM.load(Ordering::Relaxed) {
+        Source::System
+    } else {
+        Source::Generic
+    }
+}
+
+/// Selects the timing source and calibrates the overhead of reading it.
+///
+/// * `source`: Timing source to select.
+pub fn select(source: Source)
+{
+    SYSTEM.store(source == Source::System, Ordering::Relaxed);
+    calibrate();
+}
+
+/// Measures the frequency of the generic timer against the system timer, to
+/// catch firmware that programs the wrong frequency into the generic timer.
+///
+/// Returns the measured frequency in hertz.
+pub fn cross_check() -> usize
+{
+    let start = systimer::now();
+    // Start right on a tick of the system timer to not lose up to one tick.
+    while systimer::now() == start {
+        spin_loop()
+    }
+    let start = systimer::now();
+    let generic = generic_now();
+    while systimer::now() - start < CROSS_CHECK_TICKS {
+        spin_loop()
+    }
+    let ticks = generic_now() - generic;
+    (ticks as u128 * systimer::FREQUENCY as u128 / CROSS_CHECK_TICKS as u128) as usize
+}
+
+/// Busy waits for at least a number of microseconds on the generic timer,
+/// regardless of the selected timing source.
+///
+/// * `us`: Number of microseconds to wait.
+pub fn delay_us(us: u64)
+{
+    delay(us, 1000000)
+}
+
+/// Busy waits for at least a number of milliseconds on the generic timer,
+/// regardless of the selected timing source.
+///
+/// * `ms`: Number of milliseconds to wait.
+pub fn delay_ms(ms: u64)
+{
+    delay(ms, 1000)
+}
+
+/// Busy waits for at least an interval on the generic timer.
+///
+/// The number of ticks is computed in 128-bit arithmetic and rounded up, so it
+/// neither overflows nor falls short with frequencies that aren't a multiple of
+/// the unit.
+///
+/// * `count`: Length of the interval in units.
+/// * `units`: Number of units per second.
+fn delay(count: u64, units: u64)
+{
+    let ticks = (count as u128 * generic_frequency() as u128 + units as u128 - 1) / units as u128;
+    let start = generic_now();
+    while ((generic_now() - start) as u128) < ticks {
+        spin_loop()
+    }
+}
+
+/// Reads the physical count of the generic timer.
+///
+/// The read is preceded by an instruction barrier so that it cannot be
+/// performed ahead of the code that comes before it.  Unlike [`now`], this
+/// doesn't access any statics, so it can be called at EL0.
+///
+/// Returns the current count.
+pub fn generic_now() -> usize
+{
+    let now: usize;
+    unsafe {
+        asm!(
+            "isb",
+            "mrs {now}, cntpct_el0",
+            now = out (reg) now,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+    now
+}
+
+/// Reads the frequency of the generic timer.
+///
+/// Returns the frequency in hertz.
+pub fn generic_frequency() -> usize
+{
+    let freq: usize;
+    unsafe {
+        asm!(
+            "mrs {freq}, cntfrq_el0",
+            freq = out (reg) freq,
+            options (nomem, nostack, preserves_flags)
+        );
+    }
+    freq
+}
+
+/// Measures the median overhead of reading the timer twice in a row.
+pub fn calibrate()
+{
+    let mut samples = [0; CALIBRATION_SAMPLES];
+    for sample in samples.iter_mut() {
+        let start = now();
+        let end = now();
+        *sample = end - start;
+    }
+    samples.sort_unstable();
+    OVERHEAD.store(samples[CALIBRATION_SAMPLES / 2], Ordering::Relaxed);
+}
+
+/// Returns the calibrated overhead of reading the timer twice in ticks.
+pub fn overhead() -> usize
+{
+    OVERHEAD.load(Ordering::Relaxed)
+}
+
+/// Computes the time elapsed between two timer reads without the overhead of
+/// reading the timer.
+///
+/// * `start`: Count read at the start of the interval.
+/// * `end`: Count read at the end of the interval.
+///
+/// Returns the elapsed ticks, which are never negative.
+pub fn elapsed(start: usize, end: usize) -> usize
+{
+    (end - start).saturating_sub(overhead())
+}
+
+/// Converts an interval to nanoseconds.
+///
+/// * `ticks`: Interval in timer ticks.
+///
+/// Returns the interval in nanoseconds.
+pub fn nanos(ticks: usize) -> u128
+{
+    ticks as u128 * 1_000_000_000 / frequency().max(1) as u128
+}
+
+impl Display for Duration
+{
+    fn fmt(&self, fmt: &mut Formatter) -> FormatResult
+    {
+        let nsecs = nanos(self.0);
+        match nsecs {
+            1_000_000_000 .. => write!(fmt, "{}.{:03} secs", nsecs / 1_000_000_000, nsecs / 1_000_000 % 1000),
+            1_000_000 .. => write!(fmt, "{}.{:03} msecs", nsecs / 1_000_000, nsecs / 1000 % 1000),
+            1000 .. => write!(fmt, "{}.{:03} usecs", nsecs / 1000, nsecs % 1000),
+            _ => write!(fmt, "{nsecs} nsecs"),
+        }
+    }
+}
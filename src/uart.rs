@@ -1,42 +1,173 @@
-//! Mini UART driver.
+//! UART driver.
+//!
+//! Output is queued in a ring buffer and moved to the transmission FIFO
+//! whenever there is room, either by the transmission interrupt once it is
+//! enabled with [`use_interrupts`], which lets the cores carry on computing
+//! while output is being sent, or by the writers themselves until then.
+//!
+//! The Mini UART is driven by default, and the PL011 UART instead with the
+//! `pl011` feature, for instance with `./build --cfg 'feature="pl011"'`, since
+//! the one that reaches GPIOs 14 and 15 depends on the configuration of the
+//! firmware: the PL011 UART is only free for the console with
+//! `dtoverlay=disable-bt` or `dtoverlay=miniuart-bt` in `config.txt`, and
+//! nothing appears on the console if the other one is driven.  Only the
+//! register accesses differ between both, so the rest of the driver and its
+//! interface stay the same.
 //!
 //! Documentation:
 //!
 //! * [BCM2711 ARM Peripherals](https://datasheets.raspberrypi.com/bcm2711/bcm2711-peripherals.pdf)
-//!   2 and 5
+//!   2, 5, and 11
+//! * [BCM2835 ARM Peripherals](https://datasheets.raspberrypi.com/bcm2835/bcm2835-peripherals.pdf)
+//!   6.1
+//! * [PrimeCell UART (PL011) Technical Reference Manual](https://developer.arm.com/documentation/ddi0183/latest)
+//!   3
 
+use core::arch::asm;
+use core::array;
 use core::fmt::{Result as FormatResult, Write};
 use core::hint::spin_loop;
 use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
 
 use crate::sync::{Lazy, Lock};
+#[cfg(feature = "pi3")]
+use crate::timer;
 use crate::PERRY_RANGE;
 
+/// Number of bytes printed on every line of a hex dump.
+const DUMP_LINE_LEN: usize = 16;
 /// Base of the auxiliary peripheral configuration registers
+#[cfg(not(feature = "pl011"))]
 const AUX_BASE: usize = 0x2215000 + PERRY_RANGE.start;
 /// Auxiliary peripheral enabler register.
+#[cfg(not(feature = "pl011"))]
 const AUX_ENABLES: *mut u32 = (AUX_BASE + 0x4) as _;
 /// Input / output Mini UART register.
+#[cfg(not(feature = "pl011"))]
 const AUX_MU_IO: *mut u32 = (AUX_BASE + 0x40) as _;
+/// Interrupt enable Mini UART register.
+#[cfg(not(feature = "pl011"))]
+const AUX_MU_IER: *mut u32 = (AUX_BASE + 0x44) as _;
 /// Data status Mini UART register.
+#[cfg(not(feature = "pl011"))]
 const AUX_MU_LCR: *mut u32 = (AUX_BASE + 0x4C) as _;
+/// Line status Mini UART register.
+#[cfg(not(feature = "pl011"))]
+const AUX_MU_LSR: *const u32 = (AUX_BASE + 0x54) as _;
 /// Control MiniUART register.
+#[cfg(not(feature = "pl011"))]
 const AUX_MU_CNTL: *mut u32 = (AUX_BASE + 0x60) as _;
 /// Mini UART status register.
+#[cfg(not(feature = "pl011"))]
 const AUX_MU_STAT: *const u32 = (AUX_BASE + 0x64) as _;
 /// Mini UART BAUD rate divisor.
+#[cfg(not(feature = "pl011"))]
 const AUX_MU_BAUD: *mut u32 = (AUX_BASE + 0x68) as _;
+/// Rate of the VPU core clock that drives the Mini UART of the Pi 4 with
+/// `force_turbo` set, in hertz.
+#[cfg(all(not(feature = "pl011"), not(feature = "pi3")))]
+const CORE_CLOCK: u32 = 500000000;
+/// Rate of the VPU core clock that drives the Mini UART of the Pi 3 with
+/// `force_turbo` set, in hertz.
+#[cfg(all(not(feature = "pl011"), feature = "pi3"))]
+const CORE_CLOCK: u32 = 400000000;
+/// Selection of the alternate function 5 for GPIOs 14 and 15, which connects
+/// them to the Mini UART.
+#[cfg(not(feature = "pl011"))]
+const FSEL_UART: u32 = 0x12000;
+/// Transmission interrupt enable flag.
+#[cfg(not(feature = "pl011"))]
+const IER_TX: u32 = 0x2;
+/// Interrupt identifier of the auxiliary peripherals at the GIC.
+#[cfg(not(feature = "pl011"))]
+pub const IRQ: u32 = 125;
+/// Base of the PL011 UART registers.
+#[cfg(feature = "pl011")]
+const UART0_BASE: usize = 0x2201000 + PERRY_RANGE.start;
+/// PL011 data register.
+#[cfg(feature = "pl011")]
+const UART0_DR: *mut u32 = UART0_BASE as _;
+/// PL011 flag register.
+#[cfg(feature = "pl011")]
+const UART0_FR: *const u32 = (UART0_BASE + 0x18) as _;
+/// PL011 integer BAUD rate divisor register.
+#[cfg(feature = "pl011")]
+const UART0_IBRD: *mut u32 = (UART0_BASE + 0x24) as _;
+/// PL011 fractional BAUD rate divisor register.
+#[cfg(feature = "pl011")]
+const UART0_FBRD: *mut u32 = (UART0_BASE + 0x28) as _;
+/// PL011 line control register.
+#[cfg(feature = "pl011")]
+const UART0_LCRH: *mut u32 = (UART0_BASE + 0x2C) as _;
+/// PL011 control register.
+#[cfg(feature = "pl011")]
+const UART0_CR: *mut u32 = (UART0_BASE + 0x30) as _;
+/// PL011 interrupt mask set / clear register.
+#[cfg(feature = "pl011")]
+const UART0_IMSC: *mut u32 = (UART0_BASE + 0x38) as _;
+/// PL011 interrupt clear register.
+#[cfg(feature = "pl011")]
+const UART0_ICR: *mut u32 = (UART0_BASE + 0x44) as _;
+/// Rate of the clock that drives the PL011 UART as set up by the firmware, in
+/// hertz.
+#[cfg(feature = "pl011")]
+const UART_CLOCK: u32 = 48000000;
+/// Receive FIFO empty flag of the PL011 flag register.
+#[cfg(feature = "pl011")]
+const FR_RXFE: u32 = 0x10;
+/// Transmit FIFO full flag of the PL011 flag register.
+#[cfg(feature = "pl011")]
+const FR_TXFF: u32 = 0x20;
+/// Busy flag of the PL011 flag register, which is set until the last byte
+/// has left the transmitter.
+#[cfg(feature = "pl011")]
+const FR_BUSY: u32 = 0x8;
+/// Transmit interrupt flag of the PL011 interrupt registers.
+#[cfg(feature = "pl011")]
+const INT_TX: u32 = 0x20;
+/// Selection of the alternate function 0 for GPIOs 14 and 15, which connects
+/// them to the PL011 UART.
+#[cfg(feature = "pl011")]
+const FSEL_UART: u32 = 0x24000;
+/// Interrupt identifier of the PL011 UARTs at the GIC.
+#[cfg(feature = "pl011")]
+pub const IRQ: u32 = 153;
 /// Base address of the GPIO registers.
 const GPIO_BASE: usize = 0x2200000 + PERRY_RANGE.start;
 /// GPIO function selection register 1.
 const GPIO_FSEL1: *mut u32 = (GPIO_BASE + 0x4) as _;
 /// GPIO pull-up / pull-down register 0.
+#[cfg(not(feature = "pi3"))]
 const GPIO_PUPD0: *mut u32 = (GPIO_BASE + 0xE4) as _;
+/// GPIO pull-up / pull-down enable register of the Pi 3.
+#[cfg(feature = "pi3")]
+const GPIO_PUD: *mut u32 = (GPIO_BASE + 0x94) as _;
+/// GPIO pull-up / pull-down clock register 0 of the Pi 3.
+#[cfg(feature = "pi3")]
+const GPIO_PUDCLK0: *mut u32 = (GPIO_BASE + 0x98) as _;
+/// Set-up and hold time of the pull-up / pull-down control signal of the Pi 3
+/// in microseconds, which covers the required 150 VPU clock cycles.
+#[cfg(feature = "pi3")]
+const PUD_SETTLE_USECS: u64 = 1;
+/// Size of the transmission ring buffer in bytes.
+const RING_LEN: usize = 0x1000;
+
+/// Transmission ring buffer.
+static RING: [AtomicU8; RING_LEN] = [const { AtomicU8::new(0) }; RING_LEN];
+/// Number of bytes queued so far, only written with the UART lock held.
+static HEAD: AtomicUsize = AtomicUsize::new(0);
+/// Number of bytes moved to the transmission FIFO so far.
+static TAIL: AtomicUsize = AtomicUsize::new(0);
+/// Whether some core is moving bytes to the transmission FIFO.
+static DRAINING: AtomicBool = AtomicBool::new(false);
+/// Whether the transmission interrupt drains the ring buffer.
+static INTERRUPTS: AtomicBool = AtomicBool::new(false);
 
 /// Global UART driver instance.
 pub static UART: Lazy<Lock<Uart>> = Lazy::new(Uart::new);
 
-/// Send formatted diagnostic messages over the Mini UART.
+/// Send formatted diagnostic messages over the UART.
 #[macro_export]
 macro_rules! debug {
     ($($arg:tt)*) => {{
@@ -46,7 +177,7 @@ macro_rules! debug {
     }};
 }
 
-/// Mini UART driver.
+/// UART driver.
 #[derive(Debug)]
 pub struct Uart
 {
@@ -56,26 +187,65 @@ pub struct Uart
 
 impl Uart
 {
-    /// Creates and initializes a new Mini UART driver instance.
+    /// Creates and initializes a new UART driver instance.
     ///
-    /// Returns the newly created Mini UART driver instance.
+    /// Returns the newly created UART driver instance.
     fn new() -> Lock<Self>
     {
+        disable();
         unsafe {
-            AUX_ENABLES.write_volatile(0x1); // Enable the Mini UART.
-            AUX_MU_CNTL.write_volatile(0x0); // Temporarily disable transmission and reception..
             let val = GPIO_FSEL1.read_volatile();
-            GPIO_FSEL1.write_volatile(val & 0xFFFC0FFF | 0x12000); // Set alt function 5 for GPIOs 14 and 15.
-            let val = GPIO_PUPD0.read_volatile();
-            GPIO_PUPD0.write_volatile(val & 0xFFFFFF); // Set neither pull-up nor pull-down state for GPIOs 14 and 15.
-            AUX_MU_LCR.write_volatile(0x3); // Set data bits to 8 (the documentation is wrong).
-            AUX_MU_BAUD.write_volatile(500000000 / 115200 / 8 - 1); // Set the BAUD rate to 115200.
-            AUX_MU_CNTL.write_volatile(0x3); // Enable the transmitter and
-                                             // receiver.
+            GPIO_FSEL1.write_volatile(val & 0xFFFC0FFF | FSEL_UART); // Connect GPIOs 14 and 15 to the UART.
         }
+        disable_pulls();
+        enable();
         let this = Self { _dummy: PhantomData };
         Lock::new(this)
     }
+
+    /// Reads a byte from the receiver without blocking.
+    ///
+    /// Returns the byte read, or `None` if no data is available.
+    pub fn read(&mut self) -> Option<u8>
+    {
+        receive()
+    }
+
+    /// Prints a memory range in hexadecimal and ASCII, 16 bytes per line
+    /// prefixed with the address of the first byte.
+    ///
+    /// Must only be called with a range mapped as normal memory, since reading
+    /// anything else may have side effects or fault.
+    ///
+    /// * `addr`: Address of the first byte.
+    /// * `len`: Number of bytes to print.
+    pub fn hex_dump(&mut self, addr: usize, len: usize)
+    {
+        for line in (addr .. addr + len).step_by(DUMP_LINE_LEN) {
+            let bytes: [Option<u8>; DUMP_LINE_LEN] = array::from_fn(|idx| {
+                (line + idx < addr + len).then(|| unsafe { (line as *const u8).add(idx).read_volatile() })
+            });
+            write!(self, "{line:016X}:").unwrap();
+            for byte in bytes {
+                match byte {
+                    Some(byte) => write!(self, " {byte:02X}").unwrap(),
+                    None => write!(self, "   ").unwrap(),
+                }
+            }
+            write!(self, "  |").unwrap();
+            for byte in bytes.iter().flatten() {
+                let printable = if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' };
+                write!(self, "{printable}").unwrap();
+            }
+            writeln!(self, "|").unwrap();
+        }
+    }
+
+    /// Blocks until all the pending data has been transmitted.
+    pub fn flush(&mut self)
+    {
+        settle()
+    }
 }
 
 impl Write for Uart
@@ -83,11 +253,281 @@ impl Write for Uart
     fn write_str(&mut self, msg: &str) -> FormatResult
     {
         for byte in msg.as_bytes() {
-            while unsafe { AUX_MU_STAT.read_volatile() } & 0x20 != 0 {
+            loop {
+                let head = HEAD.load(Ordering::Relaxed);
+                if head - TAIL.load(Ordering::Acquire) < RING_LEN {
+                    RING[head % RING_LEN].store(*byte, Ordering::Relaxed);
+                    HEAD.store(head + 1, Ordering::SeqCst);
+                    break;
+                }
+                drain();
+                spin_loop()
+            } // Ring buffer full.
+        }
+        drain();
+        if !INTERRUPTS.load(Ordering::Relaxed) {
+            while TAIL.load(Ordering::Acquire) != HEAD.load(Ordering::Relaxed) {
+                drain();
                 spin_loop()
-            } // FIFO full.
-            unsafe { AUX_MU_IO.write_volatile(*byte as _) };
+            }
         }
         Ok(())
     }
 }
+
+/// Writes bytes straight to the transmission FIFO, bypassing the lock and the
+/// ring buffer, for when neither can be trusted.
+///
+/// Output still queued in the ring buffer may be interleaved with or follow
+/// the bytes.
+///
+/// * `bytes`: Bytes to write.
+pub fn write_raw(bytes: &[u8])
+{
+    for &byte in bytes {
+        while transmit_full() {
+            spin_loop()
+        }
+        transmit(byte);
+    }
+}
+
+/// Lets the transmission interrupt drain the ring buffer from now on.
+///
+/// Must be called once the interrupt is routed to a core that accepts it.
+pub fn use_interrupts()
+{
+    INTERRUPTS.store(true, Ordering::Relaxed);
+}
+
+/// Handles the transmission interrupt.
+pub fn interrupt()
+{
+    drain()
+}
+
+/// Blocks until all the queued output has been transmitted, so that the
+/// transmission interrupt doesn't disturb what follows.
+///
+/// Doesn't touch any lock or exclusive monitor if nothing is queued.
+pub fn settle()
+{
+    while TAIL.load(Ordering::Acquire) != HEAD.load(Ordering::SeqCst) {
+        drain();
+        spin_loop()
+    }
+    while !transmit_idle() {
+        spin_loop()
+    }
+}
+
+/// Moves as many queued bytes to the transmission FIFO as fit, leaving the
+/// transmission interrupt enabled only if bytes remain queued and interrupts
+/// are in use.
+///
+/// Nothing is moved if another core is already draining the ring buffer.
+/// Interrupts are masked on the calling core meanwhile so that the interrupt
+/// handler never waits for the code that it interrupted.
+fn drain()
+{
+    let daif: usize;
+    unsafe {
+        asm!("mrs {daif}, daif",
+             "msr daifset, #0x2",
+             daif = out (reg) daif,
+             options (nostack, preserves_flags))
+    };
+    while !DRAINING.swap(true, Ordering::SeqCst) {
+        let head = HEAD.load(Ordering::Acquire);
+        let mut tail = TAIL.load(Ordering::Relaxed);
+        while tail != head && !transmit_full() {
+            transmit(RING[tail % RING_LEN].load(Ordering::Relaxed));
+            tail += 1;
+        }
+        TAIL.store(tail, Ordering::Release);
+        let pending = tail != head;
+        transmit_interrupt(pending && INTERRUPTS.load(Ordering::Relaxed));
+        DRAINING.store(false, Ordering::SeqCst);
+        // A byte queued after the head was read would be stranded if the
+        // interrupt was just disabled.
+        if pending || HEAD.load(Ordering::SeqCst) == tail {
+            break;
+        }
+    }
+    unsafe { asm!("msr daif, {daif}", daif = in (reg) daif, options (nostack, preserves_flags)) };
+}
+
+/// Enables the Mini UART with its transmitter and receiver disabled while it
+/// is being set up.
+#[cfg(not(feature = "pl011"))]
+fn disable()
+{
+    unsafe {
+        AUX_ENABLES.write_volatile(0x1); // Enable the Mini UART.
+        AUX_MU_CNTL.write_volatile(0x0); // Temporarily disable transmission and reception..
+    }
+}
+
+/// Sets up the line of the Mini UART and enables its transmitter and
+/// receiver.
+#[cfg(not(feature = "pl011"))]
+fn enable()
+{
+    unsafe {
+        AUX_MU_LCR.write_volatile(0x3); // Set data bits to 8 (the documentation is wrong).
+        AUX_MU_BAUD.write_volatile(CORE_CLOCK / 115200 / 8 - 1); // Set the BAUD rate to 115200.
+        AUX_MU_CNTL.write_volatile(0x3); // Enable the transmitter and
+                                         // receiver.
+    }
+}
+
+/// Reads a byte from the receive FIFO of the Mini UART without blocking.
+///
+/// Returns the byte read, or `None` if the FIFO is empty.
+#[cfg(not(feature = "pl011"))]
+fn receive() -> Option<u8>
+{
+    if unsafe { AUX_MU_LSR.read_volatile() } & 0x1 == 0 {
+        return None; // FIFO empty.
+    }
+    Some(unsafe { AUX_MU_IO.read_volatile() } as _)
+}
+
+/// Returns whether the transmit FIFO of the Mini UART is full.
+#[cfg(not(feature = "pl011"))]
+fn transmit_full() -> bool
+{
+    unsafe { AUX_MU_STAT.read_volatile() & 0x20 != 0 }
+}
+
+/// Writes a byte to the transmit FIFO of the Mini UART, which must not be
+/// full.
+///
+/// * `byte`: Byte to write.
+#[cfg(not(feature = "pl011"))]
+fn transmit(byte: u8)
+{
+    unsafe { AUX_MU_IO.write_volatile(byte as _) }
+}
+
+/// Returns whether the Mini UART has transmitted everything written to it.
+#[cfg(not(feature = "pl011"))]
+fn transmit_idle() -> bool
+{
+    unsafe { AUX_MU_LSR.read_volatile() & 0x40 != 0 }
+}
+
+/// Enables or disables the transmission interrupt of the Mini UART.
+///
+/// * `enable`: Whether to enable the interrupt.
+#[cfg(not(feature = "pl011"))]
+fn transmit_interrupt(enable: bool)
+{
+    unsafe { AUX_MU_IER.write_volatile(if enable { IER_TX } else { 0 }) }
+}
+
+/// Disables the PL011 UART while it is being set up, as its line control
+/// register must not change while it is enabled.
+#[cfg(feature = "pl011")]
+fn disable()
+{
+    unsafe {
+        UART0_CR.write_volatile(0x0);
+        while UART0_FR.read_volatile() & FR_BUSY != 0 {
+            spin_loop()
+        } // Transmitter busy.
+        UART0_IMSC.write_volatile(0x0); // Mask all the interrupts.
+        UART0_ICR.write_volatile(0x7FF); // Clear all the interrupts.
+    }
+}
+
+/// Sets up the line of the PL011 UART and enables its FIFOs, transmitter, and
+/// receiver.
+#[cfg(feature = "pl011")]
+fn enable()
+{
+    // The divisor is in 64ths, rounded to the nearest.
+    let divisor = (UART_CLOCK * 4 + 115200 / 2) / 115200;
+    unsafe {
+        UART0_IBRD.write_volatile(divisor >> 6); // Set the BAUD rate to 115200.
+        UART0_FBRD.write_volatile(divisor & 0x3F);
+        UART0_LCRH.write_volatile(0x70); // Set data bits to 8 and enable the FIFOs.
+        UART0_CR.write_volatile(0x301); // Enable the UART, the transmitter, and
+                                        // the receiver.
+    }
+}
+
+/// Reads a byte from the receive FIFO of the PL011 UART without blocking.
+///
+/// Returns the byte read, or `None` if the FIFO is empty.
+#[cfg(feature = "pl011")]
+fn receive() -> Option<u8>
+{
+    if unsafe { UART0_FR.read_volatile() } & FR_RXFE != 0 {
+        return None;
+    }
+    // The upper bits hold the error flags of the byte.
+    Some(unsafe { UART0_DR.read_volatile() } as _)
+}
+
+/// Returns whether the transmit FIFO of the PL011 UART is full.
+#[cfg(feature = "pl011")]
+fn transmit_full() -> bool
+{
+    unsafe { UART0_FR.read_volatile() & FR_TXFF != 0 }
+}
+
+/// Writes a byte to the transmit FIFO of the PL011 UART, which must not be
+/// full.
+///
+/// * `byte`: Byte to write.
+#[cfg(feature = "pl011")]
+fn transmit(byte: u8)
+{
+    unsafe { UART0_DR.write_volatile(byte as _) }
+}
+
+/// Returns whether the PL011 UART has transmitted everything written to it.
+#[cfg(feature = "pl011")]
+fn transmit_idle() -> bool
+{
+    unsafe { UART0_FR.read_volatile() & FR_BUSY == 0 }
+}
+
+/// Enables or disables the transmission interrupt of the PL011 UART.
+///
+/// The interrupt is raised once the transmit FIFO drains below its trigger
+/// level, which refilling it above clears, so it only needs to be masked once
+/// there's nothing left to send.
+///
+/// * `enable`: Whether to enable the interrupt.
+#[cfg(feature = "pl011")]
+fn transmit_interrupt(enable: bool)
+{
+    unsafe { UART0_IMSC.write_volatile(if enable { INT_TX } else { 0 }) }
+}
+
+/// Sets neither pull-up nor pull-down state for GPIOs 14 and 15.
+#[cfg(not(feature = "pi3"))]
+fn disable_pulls()
+{
+    unsafe {
+        let val = GPIO_PUPD0.read_volatile();
+        GPIO_PUPD0.write_volatile(val & 0xFFFFFF);
+    }
+}
+
+/// Sets neither pull-up nor pull-down state for GPIOs 14 and 15 by clocking
+/// the control signal into them, since the GPIO controller of the Pi 3 has no
+/// pull-up / pull-down state registers.
+#[cfg(feature = "pi3")]
+fn disable_pulls()
+{
+    unsafe {
+        GPIO_PUD.write_volatile(0x0); // Select neither pull-up nor pull-down.
+        timer::delay_us(PUD_SETTLE_USECS);
+        GPIO_PUDCLK0.write_volatile(0xC000); // Clock the control signal into GPIOs 14 and 15.
+        timer::delay_us(PUD_SETTLE_USECS);
+        GPIO_PUDCLK0.write_volatile(0x0);
+    }
+}
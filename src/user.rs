@@ -0,0 +1,63 @@
+//! Unprivileged execution.
+//!
+//! The boot code maps the kernel code and read-only data as readable at EL0
+//! but none of the writable data, so the functions passed to [`call`] must
+//! only write to memory mapped with [`mmu::map_user`], which must also hold
+//! their stack, and must not access any statics, which rules out reporting
+//! anything or reading the timer with [`timer::now`].  They can read the
+//! generic timer with [`timer::generic_now`] and the cycle counter with
+//! [`pmu::cycles`], since [`call`] makes both accessible at EL0.  Faults at EL0
+//! are caught by the exception vector at EL1 and reported like any other.
+//!
+//! Documentation:
+//!
+//! * [Arm Architecture Reference Manual for A-profile architecture](https://developer.arm.com/documentation/ddi0487/latest)
+//!   D1.2, D11.1, D13.3
+//!
+//! [`mmu::map_user`]: crate::mmu::map_user
+//! [`timer::now`]: crate::timer::now
+//! [`timer::generic_now`]: crate::timer::generic_now
+//! [`pmu::cycles`]: crate::pmu::cycles
+
+use core::arch::asm;
+
+/// Enable flag of the performance monitors user enable register, which gives
+/// EL0 access to all the performance monitors registers.
+const PMUSERENR_EN: u64 = 0x1;
+/// EL0 physical and virtual count access flags of the counter-timer kernel
+/// control register.
+const CNTKCTL_EL0CTEN: u64 = 0x3;
+
+extern "C" {
+    /// Calls a function at EL0, defined in the boot code.
+    fn user_call(func: extern "C" fn(usize) -> usize, arg: usize, stack: usize) -> usize;
+}
+
+/// Calls a function at EL0 with the interrupt mask of the caller, after giving
+/// EL0 access to the physical count of the generic timer and to the
+/// performance monitors, which remain accessible afterwards.
+///
+/// * `func`: Function to call.
+/// * `arg`: Argument to pass to the function.
+/// * `stack`: Top of the stack of the function, which must be 16 byte aligned
+///   and mapped with [`mmu::map_user`](crate::mmu::map_user).
+///
+/// Returns the result of the function.
+pub fn call(func: extern "C" fn(usize) -> usize, arg: usize, stack: usize) -> usize
+{
+    assert!(stack % 16 == 0, "User stack 0x{stack:X} is not 16 byte aligned");
+    unsafe {
+        asm!(
+            "msr pmuserenr_el0, {pmu}",
+            "mrs {tmp}, cntkctl_el1",
+            "orr {tmp}, {tmp}, {cnt}",
+            "msr cntkctl_el1, {tmp}",
+            "isb",
+            pmu = in (reg) PMUSERENR_EN,
+            cnt = in (reg) CNTKCTL_EL0CTEN,
+            tmp = out (reg) _,
+            options (nomem, nostack, preserves_flags)
+        );
+        user_call(func, arg, stack)
+    }
+}
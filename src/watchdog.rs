@@ -0,0 +1,89 @@
+//! Power management watchdog driver.
+//!
+//! The watchdog resets the whole SoC unless it is petted before the timeout
+//! expires, so a benchmark that stalls on any core reboots the board instead
+//! of wedging it.
+//!
+//! Documentation:
+//!
+//! * [Linux BCM2835 watchdog driver](https://github.com/raspberrypi/linux/blob/rpi-6.1.y/drivers/watchdog/bcm2835_wdt.c)
+
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::uart::UART;
+use crate::PERRY_RANGE;
+
+/// Base address of the power management registers.
+const PM_BASE: usize = 0x2100000 + PERRY_RANGE.start;
+/// Reset control register.
+const PM_RSTC: *mut u32 = (PM_BASE + 0x1C) as _;
+/// Watchdog register.
+const PM_WDOG: *mut u32 = (PM_BASE + 0x24) as _;
+/// Password that must be included in every write to the power management
+/// registers.
+const PM_PASSWORD: u32 = 0x5A000000;
+/// Mask of the watchdog time field, in ticks of 1/65536 seconds.
+const PM_WDOG_TIME_SET: u32 = 0xFFFFF;
+/// Mask that clears the reset configuration field.
+const PM_RSTC_WRCFG_CLR: u32 = 0xFFFFFFCF;
+/// Full reset configuration.
+const PM_RSTC_WRCFG_FULL_RESET: u32 = 0x20;
+/// Reset configuration that disables the watchdog.
+const PM_RSTC_RESET: u32 = 0x102;
+/// Maximum timeout in seconds.
+pub const MAX_TIMEOUT: u32 = PM_WDOG_TIME_SET >> 16;
+/// Watchdog ticks before an immediate reset, about 150 microseconds.
+const RESET_TICKS: u32 = 10;
+
+/// Timeout in seconds, or zero if the watchdog is disabled.
+static TIMEOUT: AtomicU32 = AtomicU32::new(0);
+
+/// Arms the watchdog.
+///
+/// * `secs`: Timeout in seconds, which must not exceed [`MAX_TIMEOUT`].
+pub fn arm(secs: u32)
+{
+    assert!(secs > 0 && secs <= MAX_TIMEOUT,
+            "Watchdog timeout of {secs} seconds out of range");
+    TIMEOUT.store(secs, Ordering::Relaxed);
+    start(secs << 16);
+}
+
+/// Restarts the watchdog countdown, if armed.
+pub fn pet()
+{
+    let secs = TIMEOUT.load(Ordering::Relaxed);
+    if secs != 0 {
+        start(secs << 16);
+    }
+}
+
+/// Disables the watchdog.
+pub fn disable()
+{
+    TIMEOUT.store(0, Ordering::Relaxed);
+    unsafe { PM_RSTC.write_volatile(PM_PASSWORD | PM_RSTC_RESET) };
+}
+
+/// Resets the SoC after flushing the UART so that no output is lost.
+pub fn reboot() -> !
+{
+    UART.lock().flush();
+    start(RESET_TICKS);
+    loop {
+        spin_loop()
+    }
+}
+
+/// Starts the watchdog countdown.
+///
+/// * `ticks`: Timeout in ticks of 1/65536 seconds.
+fn start(ticks: u32)
+{
+    unsafe {
+        PM_WDOG.write_volatile(PM_PASSWORD | ticks & PM_WDOG_TIME_SET);
+        let val = PM_RSTC.read_volatile();
+        PM_RSTC.write_volatile(PM_PASSWORD | val & PM_RSTC_WRCFG_CLR | PM_RSTC_WRCFG_FULL_RESET);
+    }
+}